@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A hash an analyst or user confirmed as a false positive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowlistEntry {
+	pub sha256: String,
+	pub approved_by: String,
+	pub reason: String,
+	pub added_at: SystemTime,
+	/// Detection that was overridden, for auditing.
+	#[serde(default)]
+	pub detection_name: Option<String>,
+}
+
+/// Local SHA-256 allowlist persisted as JSON. Allowlisted files are neither reported by a
+/// [`crate::Scanner`] that has the list set nor accepted into quarantine.
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+	path: PathBuf,
+	entries: Vec<AllowlistEntry>,
+}
+
+impl Allowlist {
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let entries = match fs::read(&path) {
+			Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("Corrupt allowlist: {}", path.display()))?,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+			Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+		};
+		Ok(Self { path, entries })
+	}
+
+	/// `allowlist.json` in the application data directory.
+	pub fn load_default() -> Result<Self> {
+		Self::load(crate::quarantine::app_data_dir().join("allowlist.json"))
+	}
+
+	pub fn entries(&self) -> &[AllowlistEntry] {
+		&self.entries
+	}
+
+	pub fn get(&self, sha256: &str) -> Option<&AllowlistEntry> {
+		self.entries.iter().find(|e| e.sha256.eq_ignore_ascii_case(sha256))
+	}
+
+	pub fn contains(&self, sha256: &str) -> bool {
+		self.get(sha256).is_some()
+	}
+
+	/// Adds (or re-approves) a hash and saves the list.
+	pub fn add(&mut self, sha256: &str, approved_by: &str, reason: &str, detection_name: Option<String>) -> Result<()> {
+		self.entries.retain(|e| !e.sha256.eq_ignore_ascii_case(sha256));
+		self.entries.push(AllowlistEntry {
+			sha256: sha256.to_lowercase(),
+			approved_by: approved_by.to_string(),
+			reason: reason.to_string(),
+			added_at: SystemTime::now(),
+			detection_name,
+		});
+		log::info!("{} allowlisted {}: {}", approved_by, sha256, reason);
+		self.save()
+	}
+
+	pub fn remove(&mut self, sha256: &str) -> Result<bool> {
+		let before = self.entries.len();
+		self.entries.retain(|e| !e.sha256.eq_ignore_ascii_case(sha256));
+		if self.entries.len() == before { return Ok(false); }
+		self.save()?;
+		Ok(true)
+	}
+
+	fn save(&self) -> Result<()> {
+		if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+			fs::create_dir_all(dir).with_context(|| format!("Failed to create dir: {}", dir.display()))?;
+		}
+		let tmp = self.path.with_extension("json.tmp");
+		fs::write(&tmp, serde_json::to_vec_pretty(&self.entries)?).with_context(|| format!("Failed to write {}", tmp.display()))?;
+		fs::rename(&tmp, &self.path).with_context(|| format!("Failed to replace {}", self.path.display()))
+	}
+}
@@ -0,0 +1,349 @@
+use anyhow::{anyhow, Context, Result};
+use regex::bytes::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::signature::{
+	BodyPattern, BodySignature, CountOp, HashKind, HashSignature, LogicExpr, LogicalSignature, Offset, SignatureSet,
+	TargetType,
+};
+
+const REGEX_SIZE_LIMIT: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClamFormat {
+	/// `MD5:FileSize:MalwareName`
+	Hdb,
+	/// `SHA1|SHA256:FileSize:MalwareName`
+	Hsb,
+	/// `MalwareName:TargetType:Offset:HexSignature[:MinFL[:MaxFL]]`
+	Ndb,
+	/// `SignatureName;TargetDescriptionBlock;LogicalExpression;Subsig0;Subsig1;...`
+	Ldb,
+}
+
+impl ClamFormat {
+	pub fn from_path(path: &Path) -> Option<Self> {
+		match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+			"hdb" => Some(ClamFormat::Hdb),
+			"hsb" => Some(ClamFormat::Hsb),
+			"ndb" => Some(ClamFormat::Ndb),
+			"ldb" => Some(ClamFormat::Ldb),
+			_ => None,
+		}
+	}
+}
+
+/// A line that could not be imported, with the reason.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportIssue {
+	pub line: usize,
+	pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+	pub file: Option<PathBuf>,
+	pub imported: usize,
+	pub issues: Vec<ImportIssue>,
+}
+
+/// Family for a ClamAV name such as `Win.Trojan.Remcos-9953124-0` -> `Remcos`.
+pub fn family_from_name(name: &str) -> String {
+	let parts: Vec<&str> = name.split('.').collect();
+	let family = if parts.len() >= 3 { parts[2] } else { parts.last().copied().unwrap_or(name) };
+	let family = family.split('-').next().unwrap_or(family);
+	if family.is_empty() { "ClamAV".to_string() } else { family.to_string() }
+}
+
+pub fn import_file<P: AsRef<Path>>(path: P) -> Result<(SignatureSet, ImportReport)> {
+	let path = path.as_ref();
+	let format = ClamFormat::from_path(path).ok_or_else(|| anyhow!("Not a ClamAV signature file: {}", path.display()))?;
+	let raw = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+	let (set, mut report) = import_str(format, &String::from_utf8_lossy(&raw));
+	report.file = Some(path.to_path_buf());
+	Ok((set, report))
+}
+
+pub fn import_str(format: ClamFormat, text: &str) -> (SignatureSet, ImportReport) {
+	let mut set = SignatureSet::default();
+	let mut report = ImportReport::default();
+	for (i, line) in text.lines().enumerate() {
+		let line = line.trim_end_matches('\r');
+		if line.trim().is_empty() || line.starts_with('#') { continue; }
+		let res = match format {
+			ClamFormat::Hdb | ClamFormat::Hsb => parse_hash(line, format).map(|s| set.add_hash(s)),
+			ClamFormat::Ndb => parse_ndb(line).map(|s| set.add_body(s)),
+			ClamFormat::Ldb => parse_ldb(line).map(|s| set.add_logical(s)),
+		};
+		match res {
+			Ok(()) => report.imported += 1,
+			Err(message) => report.issues.push(ImportIssue { line: i + 1, message }),
+		}
+	}
+	(set, report)
+}
+
+fn parse_hash(line: &str, format: ClamFormat) -> Result<HashSignature, String> {
+	let fields: Vec<&str> = line.split(':').collect();
+	if fields.len() < 3 { return Err("expected HashString:FileSize:MalwareName".into()); }
+	let digest = fields[0].trim().to_lowercase();
+	if !digest.chars().all(|c| c.is_ascii_hexdigit()) { return Err(format!("invalid hash '{}'", fields[0])); }
+	let kind = HashKind::from_hex_len(digest.len()).ok_or_else(|| format!("unsupported hash length {}", digest.len()))?;
+	if format == ClamFormat::Hdb && kind != HashKind::Md5 { return Err(".hdb entries must be MD5".into()); }
+	if format == ClamFormat::Hsb && kind == HashKind::Md5 { return Err(".hsb entries must be SHA1 or SHA256".into()); }
+	let size = match fields[1].trim() {
+		"*" => None,
+		s => Some(s.parse::<u64>().map_err(|_| format!("invalid file size '{}'", s))?),
+	};
+	let name = fields[2].trim();
+	if name.is_empty() { return Err("missing malware name".into()); }
+	Ok(HashSignature { name: name.to_string(), kind, digest, size })
+}
+
+fn parse_target(s: &str) -> Result<TargetType, String> {
+	Ok(match s.trim() {
+		"0" => TargetType::Any,
+		"1" => TargetType::Pe,
+		"3" => TargetType::Html,
+		"4" => TargetType::Mail,
+		"6" => TargetType::Elf,
+		"7" => TargetType::Text,
+		"9" => TargetType::MachO,
+		"10" => TargetType::Pdf,
+		other => return Err(format!("unsupported target type {}", other)),
+	})
+}
+
+fn parse_offset(s: &str) -> Result<Offset, String> {
+	let s = s.trim();
+	if s == "*" { return Ok(Offset::Any); }
+	let (base, shift) = match s.split_once(',') {
+		Some((b, sh)) => (b, sh.parse::<u64>().map_err(|_| format!("invalid offset shift '{}'", sh))?),
+		None => (s, 0),
+	};
+	let num = |v: &str| v.parse::<u64>().map_err(|_| format!("invalid offset '{}'", s));
+	if let Some(rest) = base.strip_prefix("EOF-") {
+		Ok(Offset::FromEof { offset: num(rest)?, shift })
+	} else if let Some(rest) = base.strip_prefix("EP+") {
+		Ok(Offset::EntryPoint { delta: num(rest)? as i64, shift })
+	} else if let Some(rest) = base.strip_prefix("EP-") {
+		Ok(Offset::EntryPoint { delta: -(num(rest)? as i64), shift })
+	} else if base.starts_with('S') || base == "VI" || base.starts_with('$') {
+		Err(format!("unsupported offset type '{}'", base))
+	} else {
+		Ok(Offset::Absolute { offset: num(base)?, shift })
+	}
+}
+
+fn hex_val(c: u8) -> Option<u8> {
+	(c as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Translates a ClamAV hex signature body into a byte regex fragment.
+fn hex_to_regex(sig: &str, wide: bool) -> Result<String, String> {
+	let b = sig.as_bytes();
+	let mut out = String::new();
+	let mut i = 0;
+	while i < b.len() {
+		match b[i] {
+			b'*' => {
+				out.push_str("(?s:.)*?");
+				i += 1;
+			}
+			b'{' | b'[' => {
+				let close = if b[i] == b'{' { '}' } else { ']' };
+				let end = sig[i..].find(close).ok_or("unterminated jump")? + i;
+				let body = &sig[i + 1..end];
+				let (lo, hi) = match body.split_once('-') {
+					Some(("", h)) => ("0", h),
+					Some((l, h)) => (l, h),
+					None => (body, body),
+				};
+				let lo: usize = lo.parse().map_err(|_| format!("invalid jump '{}'", body))?;
+				let width = if wide { 2 } else { 1 };
+				if hi.is_empty() {
+					out.push_str(&format!("(?s:.){{{},}}", lo * width));
+				} else {
+					let hi: usize = hi.parse().map_err(|_| format!("invalid jump '{}'", body))?;
+					out.push_str(&format!("(?s:.){{{},{}}}", lo * width, hi * width));
+				}
+				i = end + 1;
+			}
+			b'!' => return Err("negated alternatives are not supported".into()),
+			b'(' => {
+				let end = sig[i..].find(')').ok_or("unterminated alternative")? + i;
+				let body = &sig[i + 1..end];
+				if body == "B" || body == "L" { return Err(format!("anchor ({}) is not supported", body)); }
+				let alts: Result<Vec<String>, String> = body.split('|').map(|a| hex_to_regex(a, wide)).collect();
+				out.push_str(&format!("(?:{})", alts?.join("|")));
+				i = end + 1;
+			}
+			_ => {
+				let (hi, lo) = (b[i], *b.get(i + 1).ok_or("odd number of hex digits")?);
+				let atom = match (hex_val(hi), hex_val(lo)) {
+					(Some(h), Some(l)) => format!("\\x{:02x}", (h << 4) | l),
+					(None, None) if hi == b'?' && lo == b'?' => "(?s:.)".to_string(),
+					(Some(h), None) if lo == b'?' => format!("[\\x{:02x}-\\x{:02x}]", h << 4, (h << 4) | 0xf),
+					(None, Some(l)) if hi == b'?' => {
+						format!("[{}]", (0..16u8).map(|h| format!("\\x{:02x}", (h << 4) | l)).collect::<String>())
+					}
+					_ => return Err(format!("invalid hex signature near '{}'", String::from_utf8_lossy(&b[i..(i + 2).min(b.len())]))),
+				};
+				out.push_str(&atom);
+				if wide { out.push_str("\\x00"); }
+				i += 2;
+			}
+		}
+	}
+	Ok(out)
+}
+
+fn compile_pattern(offset: Offset, hex: &str, nocase: bool, wide: bool, ascii: bool) -> Result<BodyPattern, String> {
+	let body = match (wide, ascii) {
+		(true, true) => format!("(?:{}|{})", hex_to_regex(hex, false)?, hex_to_regex(hex, true)?),
+		(true, false) => hex_to_regex(hex, true)?,
+		_ => hex_to_regex(hex, false)?,
+	};
+	let anchored = match offset {
+		Offset::Any => body,
+		Offset::Absolute { shift, .. } | Offset::FromEof { shift, .. } | Offset::EntryPoint { shift, .. } => {
+			format!("\\A(?s:.){{0,{}}}(?:{})", shift, body)
+		}
+	};
+	let regex = RegexBuilder::new(&anchored)
+		.unicode(false)
+		.case_insensitive(nocase)
+		.size_limit(REGEX_SIZE_LIMIT)
+		.build()
+		.map_err(|e| format!("signature does not compile: {}", e))?;
+	Ok(BodyPattern::new(offset, regex))
+}
+
+fn parse_ndb(line: &str) -> Result<BodySignature, String> {
+	let fields: Vec<&str> = line.split(':').collect();
+	if fields.len() < 4 { return Err("expected MalwareName:TargetType:Offset:HexSignature".into()); }
+	let target = parse_target(fields[1])?;
+	let offset = parse_offset(fields[2])?;
+	let pattern = compile_pattern(offset, fields[3].trim(), false, false, false)?;
+	Ok(BodySignature { name: fields[0].trim().to_string(), target, pattern })
+}
+
+/// Subsignature: `[offset:]hex[::modifiers]` where modifiers are any of `i`, `w`, `a`, `f`.
+fn parse_subsig(s: &str) -> Result<BodyPattern, String> {
+	if s.contains('/') { return Err("PCRE subsignatures are not supported".into()); }
+	if s.contains('#') { return Err("byte-compare subsignatures are not supported".into()); }
+	if s.starts_with('$') || s.starts_with("${") { return Err("macro subsignatures are not supported".into()); }
+	let (body, mods) = match s.rsplit_once("::") {
+		Some((b, m)) => (b, m),
+		None => (s, ""),
+	};
+	if mods.contains('f') { return Err("fullword modifier is not supported".into()); }
+	if let Some(c) = mods.chars().find(|c| !matches!(c, 'i' | 'w' | 'a')) {
+		return Err(format!("unknown subsignature modifier '{}'", c));
+	}
+	let (offset, hex) = match body.split_once(':') {
+		Some((o, h)) => (parse_offset(o)?, h),
+		None => (Offset::Any, body),
+	};
+	compile_pattern(offset, hex.trim(), mods.contains('i'), mods.contains('w'), mods.contains('a'))
+}
+
+struct ExprParser<'a> {
+	s: &'a [u8],
+	pos: usize,
+}
+
+impl ExprParser<'_> {
+	fn peek(&self) -> Option<u8> {
+		self.s.get(self.pos).copied()
+	}
+
+	fn number(&mut self) -> Result<usize, String> {
+		let start = self.pos;
+		while self.peek().is_some_and(|c| c.is_ascii_digit()) { self.pos += 1; }
+		std::str::from_utf8(&self.s[start..self.pos]).unwrap_or_default().parse().map_err(|_| format!("expected number at {}", start))
+	}
+
+	fn expr(&mut self) -> Result<LogicExpr, String> {
+		let mut items = vec![self.term()?];
+		while self.peek() == Some(b'|') {
+			self.pos += 1;
+			items.push(self.term()?);
+		}
+		Ok(if items.len() == 1 { items.remove(0) } else { LogicExpr::Or(items) })
+	}
+
+	fn term(&mut self) -> Result<LogicExpr, String> {
+		let mut items = vec![self.factor()?];
+		while self.peek() == Some(b'&') {
+			self.pos += 1;
+			items.push(self.factor()?);
+		}
+		Ok(if items.len() == 1 { items.remove(0) } else { LogicExpr::And(items) })
+	}
+
+	fn factor(&mut self) -> Result<LogicExpr, String> {
+		let primary = if self.peek() == Some(b'(') {
+			self.pos += 1;
+			let e = self.expr()?;
+			if self.peek() != Some(b')') { return Err(format!("expected ')' at {}", self.pos)); }
+			self.pos += 1;
+			e
+		} else {
+			LogicExpr::Sub(self.number()?)
+		};
+		let op = match self.peek() {
+			Some(b'=') => CountOp::Eq,
+			Some(b'>') => CountOp::Gt,
+			Some(b'<') => CountOp::Lt,
+			_ => return Ok(primary),
+		};
+		self.pos += 1;
+		let n = self.number()?;
+		let distinct = if self.peek() == Some(b',') {
+			self.pos += 1;
+			Some(self.number()?)
+		} else {
+			None
+		};
+		Ok(LogicExpr::Count { expr: Box::new(primary), op, n, distinct })
+	}
+}
+
+fn parse_logic(expr: &str) -> Result<LogicExpr, String> {
+	let compact: String = expr.chars().filter(|c| !c.is_whitespace()).collect();
+	let mut p = ExprParser { s: compact.as_bytes(), pos: 0 };
+	let e = p.expr()?;
+	if p.pos != compact.len() { return Err(format!("unexpected '{}' in logical expression", &compact[p.pos..])); }
+	Ok(e)
+}
+
+fn parse_ldb(line: &str) -> Result<LogicalSignature, String> {
+	let fields: Vec<&str> = line.split(';').collect();
+	if fields.len() < 4 { return Err("expected Name;TargetDescriptionBlock;LogicalExpression;Subsig0;...".into()); }
+	let mut target = TargetType::Any;
+	let mut file_size = None;
+	for attr in fields[1].split(',') {
+		let (k, v) = attr.split_once(':').unwrap_or((attr, ""));
+		match k.trim() {
+			"Target" => target = parse_target(v)?,
+			"Engine" => {}
+			"FileSize" => {
+				let (lo, hi) = v.split_once('-').unwrap_or((v, v));
+				let lo = lo.parse::<u64>().map_err(|_| format!("invalid FileSize '{}'", v))?;
+				let hi = hi.parse::<u64>().map_err(|_| format!("invalid FileSize '{}'", v))?;
+				file_size = Some((lo, hi));
+			}
+			other => return Err(format!("unsupported target description '{}'", other)),
+		}
+	}
+	let expr = parse_logic(fields[2])?;
+	let subsigs: Result<Vec<BodyPattern>, String> = fields[3..].iter().map(|s| parse_subsig(s)).collect();
+	let subsigs = subsigs?;
+	if expr.max_index().is_some_and(|m| m >= subsigs.len()) {
+		return Err("logical expression references a missing subsignature".into());
+	}
+	Ok(LogicalSignature { name: fields[0].trim().to_string(), target, file_size, subsigs, expr })
+}
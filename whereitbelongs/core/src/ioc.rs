@@ -0,0 +1,240 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::OnceLock;
+
+use crate::ratconfig::utf16_strings;
+
+const MAX_IOCS: usize = 512;
+
+// Generic TLDs seen in the wild; two-letter ccTLDs are accepted separately minus common file extensions
+const GENERIC_TLDS: [&str; 40] = [
+	"com", "net", "org", "info", "biz", "io", "xyz", "top", "club", "online", "site", "website", "space", "live",
+	"store", "tech", "pro", "app", "dev", "cloud", "link", "click", "icu", "vip", "work", "shop", "win", "bid",
+	"loan", "gov", "edu", "mil", "int", "onion", "duckdns", "tk", "ml", "ga", "cf", "gq",
+];
+const FILE_EXT_TLDS: [&str; 17] = ["js", "py", "sh", "cs", "rb", "pl", "md", "ps", "so", "db", "gz", "xz", "vb", "ts", "hh", "rs", "go"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IocKind {
+	Url,
+	Ipv4,
+	Ipv6,
+	Domain,
+	Email,
+	BitcoinAddress,
+	EthereumAddress,
+	MoneroAddress,
+	FilePath,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Ioc {
+	pub kind: IocKind,
+	pub value: String,
+}
+
+impl Ioc {
+	/// Safe-to-share form, e.g. `hxxp://evil[.]com` / `10[.]0[.]0[.]1`.
+	pub fn defanged(&self) -> String {
+		match self.kind {
+			IocKind::Url => defang(&defang_scheme(&self.value)),
+			IocKind::Domain | IocKind::Ipv4 | IocKind::Email => defang(&self.value),
+			IocKind::Ipv6 => self.value.replace(':', "[:]"),
+			_ => self.value.clone(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IocOptions {
+	/// Keep loopback, RFC 1918, link-local and other non-routable addresses.
+	pub include_private: bool,
+}
+
+struct IocRegexes {
+	refang: Vec<(Regex, &'static str)>,
+	url: Regex,
+	email: Regex,
+	ipv4: Regex,
+	ipv6: Regex,
+	domain: Regex,
+	btc: Regex,
+	eth: Regex,
+	xmr: Regex,
+	path: Regex,
+}
+
+fn regexes() -> &'static IocRegexes {
+	static RE: OnceLock<IocRegexes> = OnceLock::new();
+	RE.get_or_init(|| {
+		let re = |p: &str| Regex::new(p).expect("invalid IOC regex");
+		IocRegexes {
+			refang: vec![
+				(re(r"(?i)\bhxxp"), "http"),
+				(re(r"(?i)\bfxp://"), "ftp://"),
+				(re(r"\[://\]"), "://"),
+				(re(r"(?i)\[\.\]|\(\.\)|\{\.\}|\[dot\]|\(dot\)"), "."),
+				(re(r"\[:\]"), ":"),
+				(re(r"(?i)\[@\]|\[at\]|\(at\)"), "@"),
+			],
+			url: re(r#"(?i)\b(?:https?|ftp)://[^\s"'<>()\[\]{}|\\^`]+"#),
+			email: re(r"(?i)\b[a-z0-9._%+-]+@(?:[a-z0-9-]+\.)+[a-z]{2,24}\b"),
+			ipv4: re(r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b"),
+			ipv6: re(r"(?i)(?:[0-9a-f]{0,4}:){2,7}[0-9a-f]{0,4}"),
+			domain: re(r"(?i)\b(?:[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?\.)+[a-z]{2,24}\b"),
+			btc: re(r"\b(?:[13][a-km-zA-HJ-NP-Z1-9]{25,34}|bc1[ac-hj-np-z02-9]{11,71})\b"),
+			eth: re(r"\b0x[a-fA-F0-9]{40}\b"),
+			xmr: re(r"\b4[0-9AB][1-9A-HJ-NP-Za-km-z]{93}\b"),
+			path: re(r#"(?i)(?:\b[a-z]:\\|%[a-z]+%\\|(?:^|[\s"'=])/(?:tmp|etc|var|usr|home|root|dev/shm|opt|bin|sbin)/)[^\s"'<>|*?;]+"#),
+		}
+	})
+}
+
+/// Undoes common defanging (`hxxp`, `[.]`, `(dot)`, `[@]`, ...) so indicators can be matched.
+pub fn refang(text: &str) -> String {
+	let mut out = text.to_string();
+	for (re, rep) in &regexes().refang {
+		out = re.replace_all(&out, *rep).into_owned();
+	}
+	out
+}
+
+pub fn defang(value: &str) -> String {
+	value.replace('.', "[.]").replace('@', "[@]")
+}
+
+// Only a leading scheme is rewritten, so `myhttpserver.com` keeps refanging to itself
+fn defang_scheme(url: &str) -> String {
+	for (scheme, defanged) in [("http", "hxxp"), ("ftp://", "fxp://")] {
+		if url.get(..scheme.len()).is_some_and(|p| p.eq_ignore_ascii_case(scheme)) { return format!("{}{}", defanged, &url[scheme.len()..]); }
+	}
+	url.to_string()
+}
+
+fn is_private_v4(ip: &Ipv4Addr) -> bool {
+	let o = ip.octets();
+	ip.is_private()
+		|| ip.is_loopback()
+		|| ip.is_link_local()
+		|| ip.is_unspecified()
+		|| ip.is_broadcast()
+		|| ip.is_documentation()
+		|| ip.is_multicast()
+		|| (o[0] == 100 && (64..128).contains(&o[1])) // CGNAT
+}
+
+fn is_private_v6(ip: &Ipv6Addr) -> bool {
+	let seg0 = ip.segments()[0];
+	ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || (seg0 & 0xfe00) == 0xfc00 || (seg0 & 0xffc0) == 0xfe80
+}
+
+pub fn is_private_ip(ip: &IpAddr) -> bool {
+	match ip {
+		IpAddr::V4(v4) => is_private_v4(v4),
+		IpAddr::V6(v6) => is_private_v6(v6),
+	}
+}
+
+fn valid_tld(domain: &str) -> bool {
+	let tld = domain.rsplit('.').next().unwrap_or_default().to_lowercase();
+	GENERIC_TLDS.contains(&tld.as_str()) || (tld.len() == 2 && !FILE_EXT_TLDS.contains(&tld.as_str()))
+}
+
+fn url_host(url: &str) -> &str {
+	let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+	let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+	let host = authority.rsplit('@').next().unwrap_or_default();
+	if host.starts_with('[') {
+		host.trim_start_matches('[').split(']').next().unwrap_or_default()
+	} else {
+		host.split(':').next().unwrap_or_default()
+	}
+}
+
+fn base58check_valid(addr: &str) -> bool {
+	const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+	let mut bytes = [0u8; 25];
+	for c in addr.bytes() {
+		let Some(mut carry) = ALPHABET.iter().position(|a| *a == c).map(|p| p as u32) else { return false };
+		for b in bytes.iter_mut().rev() {
+			carry += (*b as u32) * 58;
+			*b = (carry & 0xff) as u8;
+			carry >>= 8;
+		}
+		if carry != 0 { return false; }
+	}
+	let check = Sha256::digest(Sha256::digest(&bytes[..21]));
+	check[..4] == bytes[21..]
+}
+
+/// Extracts typed indicators from text, refanging it first. Results are de-duplicated and capped.
+pub fn extract_iocs(text: &str, options: &IocOptions) -> Vec<Ioc> {
+	let re = regexes();
+	let text = refang(text);
+	let mut seen = HashSet::new();
+	let mut out = Vec::new();
+	let mut push = |kind: IocKind, value: &str| {
+		let value = value.trim_end_matches(['.', ',', ';', ')']).to_string();
+		if out.len() < MAX_IOCS && seen.insert((kind, value.to_lowercase())) {
+			out.push(Ioc { kind, value });
+		}
+	};
+	let keep_ip = |ip: &IpAddr| options.include_private || !is_private_ip(ip);
+
+	for m in re.url.find_iter(&text) {
+		let host = url_host(m.as_str());
+		let public = match host.parse::<IpAddr>() {
+			Ok(ip) => keep_ip(&ip),
+			Err(_) => options.include_private || !host.eq_ignore_ascii_case("localhost"),
+		};
+		if public { push(IocKind::Url, m.as_str()); }
+	}
+	for m in re.email.find_iter(&text) {
+		if valid_tld(m.as_str()) { push(IocKind::Email, m.as_str()); }
+	}
+	for m in re.ipv4.find_iter(&text) {
+		if let Ok(ip) = m.as_str().parse::<IpAddr>() {
+			if keep_ip(&ip) { push(IocKind::Ipv4, m.as_str()); }
+		}
+	}
+	for m in re.ipv6.find_iter(&text) {
+		let s = m.as_str();
+		if s.matches(':').count() < 2 || !s.chars().any(|c| c.is_ascii_hexdigit()) { continue; }
+		if let Ok(ip) = s.parse::<Ipv6Addr>() {
+			if keep_ip(&IpAddr::V6(ip)) { push(IocKind::Ipv6, s); }
+		}
+	}
+	for m in re.domain.find_iter(&text) {
+		let d = m.as_str();
+		// email hosts are already covered; dotted identifiers like System.Net.WebClient fail the TLD check
+		if text[..m.start()].ends_with('@') || !valid_tld(d) { continue; }
+		push(IocKind::Domain, d);
+	}
+	for m in re.btc.find_iter(&text) {
+		let s = m.as_str();
+		if s.starts_with("bc1") || base58check_valid(s) { push(IocKind::BitcoinAddress, s); }
+	}
+	for m in re.eth.find_iter(&text) {
+		push(IocKind::EthereumAddress, m.as_str());
+	}
+	for m in re.xmr.find_iter(&text) {
+		push(IocKind::MoneroAddress, m.as_str());
+	}
+	for m in re.path.find_iter(&text) {
+		push(IocKind::FilePath, m.as_str().trim_start_matches([' ', '\t', '\n', '\r', '"', '\'', '=']));
+	}
+	out
+}
+
+/// Runs [`extract_iocs`] over the lossy text of `bytes` plus any UTF-16LE strings (PE/.NET literals).
+pub fn extract_iocs_from_bytes(bytes: &[u8], options: &IocOptions) -> Vec<Ioc> {
+	let mut text = String::from_utf8_lossy(bytes).into_owned();
+	for s in utf16_strings(bytes, 6) {
+		text.push('\n');
+		text.push_str(&s);
+	}
+	extract_iocs(&text, options)
+}
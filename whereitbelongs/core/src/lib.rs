@@ -1,5 +1,15 @@
 pub mod scan;
 pub mod quarantine;
 pub mod unrat;
+pub mod ratconfig;
+pub mod ioc;
+pub mod mail;
+mod pe;
+pub mod signature;
+pub mod clamav;
+pub mod allowlist;
 
-pub use scan::{scan_paths, Detection, DetectionKind, ScanOptions};
\ No newline at end of file
+pub use scan::{scan_bytes, scan_paths, scan_reader, virtual_path, Detection, DetectionKind, ScanOptions, Scanner};
+pub use ratconfig::{extract_config, C2Endpoint, RatConfig};
+pub use ioc::{extract_iocs, Ioc, IocKind, IocOptions};
+pub use allowlist::{Allowlist, AllowlistEntry};
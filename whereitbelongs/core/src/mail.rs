@@ -0,0 +1,233 @@
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine as _;
+use std::path::Path;
+
+const MAX_DEPTH: usize = 10;
+const MAX_PARTS: usize = 1000;
+
+// Mail encoders wrap lines and are sloppy with padding
+const LENIENT_B64: GeneralPurpose = GeneralPurpose::new(
+	&alphabet::STANDARD,
+	GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// A decoded leaf part of a MIME message (attachment or inline body).
+#[derive(Debug, Clone)]
+pub struct MailPart {
+	pub name: String,
+	pub content_type: String,
+	pub data: Vec<u8>,
+}
+
+/// True for `.eml`/`.mbox` files and for content that starts like an RFC 5322 message or mbox.
+pub fn looks_like_mail(path: &Path, data: &[u8]) -> bool {
+	let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+	if matches!(ext.as_deref(), Some("eml" | "mbox" | "mbx")) { return true; }
+	let head = String::from_utf8_lossy(&data[..data.len().min(4096)]).to_lowercase();
+	(head.starts_with("from ") || head.starts_with("return-path:") || head.starts_with("received:"))
+		&& head.contains("\nsubject:")
+		&& (head.contains("\nmime-version:") || head.contains("\ncontent-type:"))
+}
+
+/// Splits an mbox file on its `From ` separator lines; a single message is returned as-is.
+/// Only data that itself starts with a separator is treated as mbox, and later separators must
+/// follow a blank line, so a body line such as "From the team" never cuts a message apart.
+pub fn split_mbox(data: &[u8]) -> Vec<&[u8]> {
+	if !data.starts_with(b"From ") { return vec![data]; }
+	let mut starts: Vec<usize> = vec![0];
+	let mut line_start = 0;
+	let mut prev_blank = false;
+	while line_start < data.len() {
+		let line_end = data[line_start..].iter().position(|b| *b == b'\n').map(|p| line_start + p + 1);
+		let line = &data[line_start..line_end.unwrap_or(data.len())];
+		if line_start > 0 && prev_blank && line.starts_with(b"From ") { starts.push(line_start); }
+		prev_blank = line.iter().all(|b| matches!(b, b'\r' | b'\n'));
+		match line_end {
+			Some(end) => line_start = end,
+			None => break,
+		}
+	}
+	starts.iter().enumerate().map(|(i, s)| &data[*s..starts.get(i + 1).copied().unwrap_or(data.len())]).collect()
+}
+
+/// Decodes every leaf part of `raw` (base64, quoted-printable, uuencode), descending into
+/// multiparts and attached messages.
+pub fn parse_message(raw: &[u8]) -> Vec<MailPart> {
+	let mut parts = Vec::new();
+	walk(raw, 0, &mut parts);
+	parts
+}
+
+fn walk(raw: &[u8], depth: usize, out: &mut Vec<MailPart>) {
+	if depth > MAX_DEPTH || out.len() >= MAX_PARTS { return; }
+	let (headers, body) = split_headers(raw);
+	let get = |name: &str| headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str());
+	let content_type = get("Content-Type").unwrap_or("text/plain").to_string();
+	let mime = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+	let encoding = get("Content-Transfer-Encoding").unwrap_or("7bit").trim().to_lowercase();
+
+	if mime.starts_with("multipart/") {
+		if let Some(boundary) = header_param(&content_type, "boundary") {
+			for part in split_multipart(body, &boundary) {
+				walk(part, depth + 1, out);
+			}
+			return;
+		}
+	}
+	let data = decode_body(body, &encoding);
+	if mime == "message/rfc822" {
+		walk(&data, depth + 1, out);
+		return;
+	}
+	let name = get("Content-Disposition")
+		.and_then(|d| header_param(d, "filename"))
+		.or_else(|| header_param(&content_type, "name"))
+		.unwrap_or_else(|| format!("part{}", out.len() + 1));
+	// text bodies can carry inline uuencoded attachments
+	if mime.starts_with("text/") {
+		out.extend(inline_uuencoded(&data));
+	}
+	out.push(MailPart { name, content_type: mime, data });
+}
+
+fn split_headers(raw: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+	let (head, body) = match find(raw, b"\r\n\r\n").map(|p| (p, 4)).or_else(|| find(raw, b"\n\n").map(|p| (p, 2))) {
+		Some((p, sep)) => (&raw[..p], &raw[p + sep..]),
+		None => (raw, &raw[raw.len()..]),
+	};
+	let mut headers: Vec<(String, String)> = Vec::new();
+	for line in String::from_utf8_lossy(head).lines() {
+		if line.starts_with([' ', '\t']) {
+			if let Some((_, v)) = headers.last_mut() {
+				v.push(' ');
+				v.push_str(line.trim());
+			}
+		} else if let Some((k, v)) = line.split_once(':') {
+			headers.push((k.trim().to_string(), v.trim().to_string()));
+		}
+	}
+	(headers, body)
+}
+
+fn find(hay: &[u8], needle: &[u8]) -> Option<usize> {
+	hay.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Reads `name=value` / `name="value"` / RFC 2231 `name*=charset''value` from a header value.
+fn header_param(value: &str, name: &str) -> Option<String> {
+	for part in value.split(';').skip(1) {
+		let Some((k, v)) = part.split_once('=') else { continue };
+		let k = k.trim().to_lowercase();
+		let v = v.trim().trim_matches('"');
+		if k == name {
+			return Some(v.to_string());
+		}
+		if k == format!("{}*", name) {
+			return Some(v.rsplit("''").next().unwrap_or(v).to_string());
+		}
+	}
+	None
+}
+
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+	let delim = format!("--{}", boundary);
+	let mut parts = Vec::new();
+	let mut start: Option<usize> = None;
+	let mut pos = 0;
+	while pos < body.len() {
+		let end = body[pos..].iter().position(|b| *b == b'\n').map(|p| pos + p + 1).unwrap_or(body.len());
+		let line = &body[pos..end];
+		let trimmed = String::from_utf8_lossy(line);
+		let trimmed = trimmed.trim_end();
+		if trimmed.starts_with(&delim) {
+			if let Some(s) = start {
+				// the CRLF before a delimiter belongs to the delimiter
+				let mut e = pos;
+				if e > s && body[e - 1] == b'\n' { e -= 1; }
+				if e > s && body[e - 1] == b'\r' { e -= 1; }
+				parts.push(&body[s..e]);
+			}
+			if trimmed == format!("{}--", delim) { return parts; }
+			start = Some(end);
+		}
+		pos = end;
+	}
+	if let Some(s) = start { parts.push(&body[s..]); }
+	parts
+}
+
+fn decode_body(body: &[u8], encoding: &str) -> Vec<u8> {
+	match encoding {
+		"base64" => {
+			let cleaned: Vec<u8> = body.iter().copied().filter(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=')).collect();
+			let cleaned: &[u8] = match cleaned.iter().position(|b| *b == b'=') {
+				Some(p) => &cleaned[..p],
+				None => &cleaned,
+			};
+			LENIENT_B64.decode(cleaned).unwrap_or_else(|_| body.to_vec())
+		}
+		"quoted-printable" => decode_quoted_printable(body),
+		"x-uuencode" | "uuencode" | "x-uue" => uudecode(body).map(|(_, d)| d).unwrap_or_else(|| body.to_vec()),
+		_ => body.to_vec(),
+	}
+}
+
+fn decode_quoted_printable(body: &[u8]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(body.len());
+	let mut i = 0;
+	while i < body.len() {
+		if body[i] == b'=' {
+			if body[i + 1..].starts_with(b"\r\n") {
+				i += 3;
+				continue;
+			}
+			if body[i + 1..].starts_with(b"\n") {
+				i += 2;
+				continue;
+			}
+			if let Some(hex) = body.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()) {
+				if let Ok(b) = u8::from_str_radix(hex, 16) {
+					out.push(b);
+					i += 3;
+					continue;
+				}
+			}
+		}
+		out.push(body[i]);
+		i += 1;
+	}
+	out
+}
+
+/// Decodes the first `begin <mode> <name>` ... `end` block in `data`.
+fn uudecode(data: &[u8]) -> Option<(String, Vec<u8>)> {
+	let text = String::from_utf8_lossy(data);
+	let mut lines = text.lines().skip_while(|l| !l.starts_with("begin "));
+	let name = lines.next()?.splitn(3, ' ').nth(2).unwrap_or("uuencoded").trim().to_string();
+	let mut out = Vec::new();
+	for line in lines {
+		if line == "end" || line == "`" { break; }
+		let bytes = line.as_bytes();
+		let Some(&first) = bytes.first() else { continue };
+		let len = ((first.wrapping_sub(32)) & 63) as usize;
+		if len == 0 { continue; }
+		let vals: Vec<u8> = bytes[1..].iter().map(|c| (c.wrapping_sub(32)) & 63).collect();
+		let mut decoded = Vec::with_capacity(len);
+		for g in vals.chunks(4) {
+			let g = [g[0], *g.get(1).unwrap_or(&0), *g.get(2).unwrap_or(&0), *g.get(3).unwrap_or(&0)];
+			decoded.extend([(g[0] << 2) | (g[1] >> 4), (g[1] << 4) | (g[2] >> 2), (g[2] << 6) | g[3]]);
+		}
+		decoded.truncate(len);
+		out.extend(decoded);
+	}
+	Some((name, out))
+}
+
+fn inline_uuencoded(data: &[u8]) -> Option<MailPart> {
+	let text = String::from_utf8_lossy(data);
+	if !text.lines().any(|l| l.starts_with("begin ") && l.split(' ').nth(1).is_some_and(|m| m.chars().all(|c| c.is_ascii_digit()))) {
+		return None;
+	}
+	uudecode(data).map(|(name, data)| MailPart { name, content_type: "application/octet-stream".into(), data })
+}
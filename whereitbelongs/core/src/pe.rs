@@ -0,0 +1,87 @@
+// Just enough PE parsing for resource lookup and entry-point relative signature offsets.
+
+const RT_RCDATA: u32 = 10;
+
+pub(crate) fn read_u16(data: &[u8], off: usize) -> Option<u16> {
+	data.get(off..off + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+pub(crate) fn read_u32(data: &[u8], off: usize) -> Option<u32> {
+	data.get(off..off + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+struct PeHeaders {
+	opt: usize,
+	dirs: usize,
+	sections: usize,
+	nsections: usize,
+}
+
+fn headers(data: &[u8]) -> Option<PeHeaders> {
+	if data.get(0..2)? != b"MZ" { return None; }
+	let pe = read_u32(data, 0x3c)? as usize;
+	if data.get(pe..pe + 4)? != b"PE\0\0" { return None; }
+	let nsections = read_u16(data, pe + 6)? as usize;
+	let opt_size = read_u16(data, pe + 20)? as usize;
+	let opt = pe + 24;
+	let dirs = match read_u16(data, opt)? {
+		0x10b => opt + 96,
+		0x20b => opt + 112,
+		_ => return None,
+	};
+	Some(PeHeaders { opt, dirs, sections: opt + opt_size, nsections })
+}
+
+fn rva_to_offset(data: &[u8], h: &PeHeaders, rva: u32) -> Option<usize> {
+	(0..h.nsections).find_map(|i| {
+		let s = h.sections + i * 40;
+		let vsize = read_u32(data, s + 8)?;
+		let va = read_u32(data, s + 12)?;
+		let raw_size = read_u32(data, s + 16)?;
+		let raw = read_u32(data, s + 20)?;
+		// section headers are attacker-controlled; an overflowing range matches nothing
+		let end = va.checked_add(vsize.max(raw_size))?;
+		if rva >= va && rva < end { Some((rva - va).checked_add(raw)? as usize) } else { None }
+	})
+}
+
+pub(crate) fn is_pe(data: &[u8]) -> bool {
+	headers(data).is_some()
+}
+
+/// File offset of AddressOfEntryPoint.
+pub(crate) fn entry_point_offset(data: &[u8]) -> Option<usize> {
+	let h = headers(data)?;
+	let ep = read_u32(data, h.opt + 16)?;
+	rva_to_offset(data, &h, ep)
+}
+
+/// Minimal PE resource lookup: returns the bytes of the RT_RCDATA resource named `name`.
+pub(crate) fn rcdata(data: &[u8], name: &str) -> Option<Vec<u8>> {
+	let h = headers(data)?;
+	let rsrc_rva = read_u32(data, h.dirs + 2 * 8)?;
+	if rsrc_rva == 0 { return None; }
+	let root = rva_to_offset(data, &h, rsrc_rva)?;
+	let entries = |dir: usize| -> Vec<(u32, u32)> {
+		let count = read_u16(data, dir + 12).unwrap_or(0) as usize + read_u16(data, dir + 14).unwrap_or(0) as usize;
+		(0..count)
+			.filter_map(|i| Some((read_u32(data, dir + 16 + i * 8)?, read_u32(data, dir + 20 + i * 8)?)))
+			.collect()
+	};
+	let entry_name = |id: u32| -> Option<String> {
+		let off = root + (id & 0x7fff_ffff) as usize;
+		let len = read_u16(data, off)? as usize;
+		let units: Vec<u16> = (0..len).filter_map(|i| read_u16(data, off + 2 + i * 2)).collect();
+		String::from_utf16(&units).ok()
+	};
+	let (_, types) = entries(root).into_iter().find(|(id, off)| *id == RT_RCDATA && off & 0x8000_0000 != 0)?;
+	let names_dir = root + (types & 0x7fff_ffff) as usize;
+	let (_, langs) = entries(names_dir).into_iter().find(|(id, off)| {
+		id & 0x8000_0000 != 0 && off & 0x8000_0000 != 0 && entry_name(*id).is_some_and(|n| n.eq_ignore_ascii_case(name))
+	})?;
+	let (_, data_entry) = entries(root + (langs & 0x7fff_ffff) as usize).into_iter().next()?;
+	let entry = root + (data_entry & 0x7fff_ffff) as usize;
+	let off = rva_to_offset(data, &h, read_u32(data, entry)?)?;
+	let size = read_u32(data, entry + 4)? as usize;
+	data.get(off..off + size).map(<[u8]>::to_vec)
+}
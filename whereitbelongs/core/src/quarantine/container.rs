@@ -0,0 +1,233 @@
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::scan::Detection;
+
+// Container layout:
+//   "WIBQ" | version u8 | nonce prefix [8] | header nonce [12] | header len u32 LE | header ciphertext
+//   then frames: last-flag u8 | len u32 LE | ciphertext (AES-256-GCM, nonce = prefix || counter BE, aad = flag)
+const MAGIC: &[u8; 4] = b"WIBQ";
+const VERSION: u8 = 1;
+const CHUNK: usize = 64 * 1024;
+const MAX_HEADER: usize = 1024 * 1024;
+const KEY_FILE: &str = "quarantine.key";
+
+/// Metadata stored in the encrypted header of a quarantine container.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineHeader {
+	pub original_path: PathBuf,
+	pub size: u64,
+	pub sha256: String,
+	pub uid: Option<u32>,
+	pub gid: Option<u32>,
+	/// Unix permission bits; `None` on platforms without them.
+	pub mode: Option<u32>,
+	pub readonly: bool,
+	pub modified: Option<SystemTime>,
+	pub accessed: Option<SystemTime>,
+	pub created: Option<SystemTime>,
+	pub detection: Option<Detection>,
+	#[serde(default)]
+	pub quarantined_by: String,
+	pub quarantined_at: SystemTime,
+}
+
+impl QuarantineHeader {
+	pub(crate) fn capture(path: &Path, meta: &fs::Metadata, sha256: String, detection: Option<&Detection>, by: &str) -> Self {
+		#[cfg(unix)]
+		let (uid, gid, mode) = {
+			use std::os::unix::fs::MetadataExt;
+			(Some(meta.uid()), Some(meta.gid()), Some(meta.mode() & 0o7777))
+		};
+		#[cfg(not(unix))]
+		let (uid, gid, mode) = (None, None, None);
+		Self {
+			original_path: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+			size: meta.len(),
+			sha256,
+			uid,
+			gid,
+			mode,
+			readonly: meta.permissions().readonly(),
+			modified: meta.modified().ok(),
+			accessed: meta.accessed().ok(),
+			created: meta.created().ok(),
+			detection: detection.cloned(),
+			quarantined_by: by.to_string(),
+			quarantined_at: SystemTime::now(),
+		}
+	}
+
+	/// Re-applies owner, permissions and timestamps to a restored file. Ownership needs
+	/// privileges, so a failed chown is only logged.
+	pub(crate) fn apply_metadata(&self, path: &Path) -> Result<()> {
+		// times first: the restored file is still writable by us, which a read-only mode would undo
+		let file = fs::OpenOptions::new().write(true).open(path)
+			.with_context(|| format!("Failed to open {}", path.display()))?;
+		let mut times = fs::FileTimes::new();
+		if let Some(t) = self.accessed { times = times.set_accessed(t); }
+		if let Some(t) = self.modified { times = times.set_modified(t); }
+		#[cfg(windows)]
+		if let Some(t) = self.created {
+			use std::os::windows::fs::FileTimesExt;
+			times = times.set_created(t);
+		}
+		file.set_times(times).with_context(|| format!("Failed to set times on {}", path.display()))?;
+		drop(file);
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			if self.uid.is_some() || self.gid.is_some() {
+				if let Err(e) = std::os::unix::fs::chown(path, self.uid, self.gid) {
+					log::warn!("Could not restore owner of {}: {}", path.display(), e);
+				}
+			}
+			// chmod after chown, which may clear setuid/setgid bits
+			if let Some(mode) = self.mode {
+				fs::set_permissions(path, fs::Permissions::from_mode(mode))
+					.with_context(|| format!("Failed to set permissions on {}", path.display()))?;
+			}
+		}
+		#[cfg(not(unix))]
+		if self.readonly {
+			let mut perms = fs::metadata(path)?.permissions();
+			perms.set_readonly(true);
+			fs::set_permissions(path, perms)?;
+		}
+		Ok(())
+	}
+}
+
+/// Loads an existing per-quarantine key; a missing key is an error rather than a fresh key that
+/// could never decrypt the containers beside it.
+pub(crate) fn load_key(dir: &Path) -> Result<[u8; 32]> {
+	let path = dir.join(KEY_FILE);
+	let bytes = fs::read(&path).with_context(|| format!("Failed to read quarantine key {}", path.display()))?;
+	bytes.try_into().map_err(|_| anyhow!("Corrupt quarantine key: {}", path.display()))
+}
+
+/// Loads the per-quarantine key, creating it (owner-only) on first use.
+pub(crate) fn load_or_create_key(dir: &Path) -> Result<[u8; 32]> {
+	let path = dir.join(KEY_FILE);
+	if path.exists() { return load_key(dir); }
+	let mut key = [0u8; 32];
+	rand::thread_rng().fill_bytes(&mut key);
+	let mut opts = fs::OpenOptions::new();
+	opts.write(true).create_new(true);
+	#[cfg(unix)]
+	std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+	let mut f = opts.open(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+	f.write_all(&key)?;
+	f.sync_all()?;
+	Ok(key)
+}
+
+fn frame_nonce(prefix: &[u8; 8], counter: u32) -> [u8; 12] {
+	let mut n = [0u8; 12];
+	n[..8].copy_from_slice(prefix);
+	n[8..].copy_from_slice(&counter.to_be_bytes());
+	n
+}
+
+// Fills `buf` as far as possible; short only at end of input.
+fn read_full<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+	let mut n = 0;
+	while n < buf.len() {
+		match r.read(&mut buf[n..]) {
+			Ok(0) => break,
+			Ok(k) => n += k,
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(n)
+}
+
+/// Encrypts `header` and the contents of `src` into `out`.
+pub(crate) fn write_container<R: Read, W: Write>(key: &[u8; 32], header: &QuarantineHeader, mut src: R, mut out: W) -> Result<()> {
+	let cipher = Aes256Gcm::new(key.into());
+	let mut prefix = [0u8; 8];
+	let mut header_nonce = [0u8; 12];
+	rand::thread_rng().fill_bytes(&mut prefix);
+	rand::thread_rng().fill_bytes(&mut header_nonce);
+	let header_ct = cipher
+		.encrypt(Nonce::from_slice(&header_nonce), Payload { msg: &serde_json::to_vec(header)?, aad: MAGIC })
+		.map_err(|_| anyhow!("Failed to encrypt quarantine header"))?;
+	out.write_all(MAGIC)?;
+	out.write_all(&[VERSION])?;
+	out.write_all(&prefix)?;
+	out.write_all(&header_nonce)?;
+	out.write_all(&(header_ct.len() as u32).to_le_bytes())?;
+	out.write_all(&header_ct)?;
+
+	let mut buf = vec![0u8; CHUNK];
+	let mut n = read_full(&mut src, &mut buf)?;
+	let mut counter = 0u32;
+	loop {
+		let mut next = vec![0u8; CHUNK];
+		let next_n = if n == CHUNK { read_full(&mut src, &mut next)? } else { 0 };
+		let last = next_n == 0;
+		let flag = [u8::from(last)];
+		let ct = cipher
+			.encrypt(Nonce::from_slice(&frame_nonce(&prefix, counter)), Payload { msg: &buf[..n], aad: &flag })
+			.map_err(|_| anyhow!("Failed to encrypt quarantine payload"))?;
+		out.write_all(&flag)?;
+		out.write_all(&(ct.len() as u32).to_le_bytes())?;
+		out.write_all(&ct)?;
+		if last { break; }
+		counter = counter.checked_add(1).context("Quarantine payload too large")?;
+		buf = next;
+		n = next_n;
+	}
+	out.flush()?;
+	Ok(())
+}
+
+/// Reads and decrypts the header, leaving `r` positioned at the payload. Returns the frame
+/// nonce prefix needed by [`read_payload`].
+pub(crate) fn read_header<R: Read>(key: &[u8; 32], r: &mut R) -> Result<(QuarantineHeader, [u8; 8])> {
+	let mut fixed = [0u8; 4 + 1 + 8 + 12 + 4];
+	r.read_exact(&mut fixed).context("Truncated quarantine container")?;
+	if &fixed[..4] != MAGIC { bail!("Not a quarantine container"); }
+	if fixed[4] != VERSION { bail!("Unsupported quarantine container version {}", fixed[4]); }
+	let prefix: [u8; 8] = fixed[5..13].try_into().unwrap();
+	let header_len = u32::from_le_bytes(fixed[25..29].try_into().unwrap()) as usize;
+	if header_len > MAX_HEADER { bail!("Corrupt quarantine header length"); }
+	let mut header_ct = vec![0u8; header_len];
+	r.read_exact(&mut header_ct).context("Truncated quarantine header")?;
+	let plain = Aes256Gcm::new(key.into())
+		.decrypt(Nonce::from_slice(&fixed[13..25]), Payload { msg: &header_ct, aad: MAGIC })
+		.map_err(|_| anyhow!("Quarantine header failed authentication (wrong key or tampered)"))?;
+	Ok((serde_json::from_slice(&plain)?, prefix))
+}
+
+/// Decrypts the payload frames into `out`, returning the number of plaintext bytes.
+pub(crate) fn read_payload<R: Read, W: Write>(key: &[u8; 32], prefix: &[u8; 8], r: &mut R, mut out: W) -> Result<u64> {
+	let cipher = Aes256Gcm::new(key.into());
+	let mut total = 0u64;
+	let mut counter = 0u32;
+	loop {
+		let mut frame = [0u8; 5];
+		r.read_exact(&mut frame).context("Truncated quarantine payload")?;
+		let len = u32::from_le_bytes(frame[1..].try_into().unwrap()) as usize;
+		if len > CHUNK + 16 { bail!("Corrupt quarantine frame length"); }
+		let mut ct = vec![0u8; len];
+		r.read_exact(&mut ct).context("Truncated quarantine payload")?;
+		let plain = cipher
+			.decrypt(Nonce::from_slice(&frame_nonce(prefix, counter)), Payload { msg: &ct, aad: &frame[..1] })
+			.map_err(|_| anyhow!("Quarantine payload failed authentication"))?;
+		out.write_all(&plain)?;
+		total += plain.len() as u64;
+		if frame[0] == 1 { break; }
+		counter = counter.checked_add(1).context("Corrupt quarantine payload")?;
+	}
+	out.flush()?;
+	Ok(total)
+}
@@ -0,0 +1,306 @@
+use anyhow::{bail, Context, Result};
+use md5::Md5;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{current_user, Quarantine};
+use crate::scan::Detection;
+
+/// The password malware researchers conventionally expect on sample archives.
+pub const DEFAULT_EXPORT_PASSWORD: &str = "infected";
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+	pub password: String,
+	pub exported_by: String,
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		Self { password: DEFAULT_EXPORT_PASSWORD.into(), exported_by: current_user() }
+	}
+}
+
+/// One sample in an export, as written to the JSON sidecar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedSample {
+	pub id: String,
+	/// Name of the member inside the ZIP.
+	pub archive_name: String,
+	pub size: u64,
+	pub md5: String,
+	pub sha1: String,
+	pub sha256: String,
+	pub original_path: PathBuf,
+	pub detection: Option<Detection>,
+	pub quarantined_by: String,
+	pub quarantined_at: SystemTime,
+}
+
+/// Contents of the `<archive>.json` sidecar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+	pub archive: PathBuf,
+	pub exported_by: String,
+	pub exported_at: SystemTime,
+	pub samples: Vec<ExportedSample>,
+}
+
+/// Sidecar written next to an export archive.
+pub fn sidecar_path(archive: &Path) -> PathBuf {
+	let mut name = archive.file_name().unwrap_or_default().to_os_string();
+	name.push(".json");
+	archive.with_file_name(name)
+}
+
+#[derive(Default)]
+struct Digests {
+	crc: crc32fast::Hasher,
+	md5: Md5,
+	sha1: Sha1,
+	sha256: Sha256,
+	size: u64,
+}
+
+impl Write for Digests {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.crc.update(buf);
+		self.md5.update(buf);
+		self.sha1.update(buf);
+		self.sha256.update(buf);
+		self.size += buf.len() as u64;
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Quarantine {
+	/// Packages the given entries into a ZipCrypto-protected ZIP at `archive` and writes a JSON
+	/// sidecar with hashes, original paths and detections. Callers must restrict this to Admin+.
+	pub fn export(&self, ids: &[String], archive: &Path, opts: &ExportOptions) -> Result<ExportManifest> {
+		let entries = self.entries()?;
+		let out = fs::File::create(archive).with_context(|| format!("Failed to create {}", archive.display()))?;
+		let mut zip = ZipCryptoWriter::new(BufWriter::new(out), opts.password.as_bytes());
+		let mut samples: Vec<ExportedSample> = Vec::new();
+		for id in ids {
+			let entry = entries.iter().find(|e| &e.id == id).with_context(|| format!("No quarantine entry {}", id))?;
+			// first pass hashes the plaintext (ZipCrypto needs the CRC up front), second pass writes it
+			let mut digests = Digests::default();
+			self.decrypt_payload(id, &mut digests)?;
+			let sha256 = format!("{:x}", digests.sha256.finalize());
+			if sha256 != entry.sha256 { bail!("Quarantine entry {} is corrupt (hash mismatch)", id); }
+			let archive_name = sha256.clone();
+			if !samples.iter().any(|s| s.archive_name == archive_name) {
+				zip.start_file(&archive_name, digests.crc.finalize(), digests.size)?;
+				self.decrypt_payload(id, &mut zip)?;
+				zip.finish_file()?;
+			}
+			samples.push(ExportedSample {
+				id: id.clone(),
+				archive_name,
+				size: digests.size,
+				md5: format!("{:x}", digests.md5.finalize()),
+				sha1: format!("{:x}", digests.sha1.finalize()),
+				sha256,
+				original_path: entry.original_path.clone(),
+				detection: entry.detection.clone(),
+				quarantined_by: entry.quarantined_by.clone(),
+				quarantined_at: entry.quarantined_at,
+			});
+		}
+		zip.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+		let manifest = ExportManifest { archive: archive.to_path_buf(), exported_by: opts.exported_by.clone(), exported_at: SystemTime::now(), samples };
+		let sidecar = sidecar_path(archive);
+		fs::write(&sidecar, serde_json::to_vec_pretty(&manifest)?).with_context(|| format!("Failed to write {}", sidecar.display()))?;
+		log::info!("{} exported {} quarantine entries to {}", opts.exported_by, manifest.samples.len(), archive.display());
+		Ok(manifest)
+	}
+}
+
+// Traditional PKWARE encryption. Weak, but it is what every unzip tool and sandbox accepts for
+// "infected" archives; it only has to stop accidental execution and AV re-detection.
+const CRC_TABLE: [u32; 256] = {
+	let mut table = [0u32; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut c = i as u32;
+		let mut k = 0;
+		while k < 8 {
+			c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+			k += 1;
+		}
+		table[i] = c;
+		i += 1;
+	}
+	table
+};
+
+fn crc_byte(crc: u32, b: u8) -> u32 {
+	CRC_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+}
+
+struct ZipCryptoKeys([u32; 3]);
+
+impl ZipCryptoKeys {
+	fn new(password: &[u8]) -> Self {
+		let mut keys = Self([0x1234_5678, 0x2345_6789, 0x3456_7890]);
+		for b in password {
+			keys.update(*b);
+		}
+		keys
+	}
+
+	fn update(&mut self, b: u8) {
+		let k = &mut self.0;
+		k[0] = crc_byte(k[0], b);
+		k[1] = k[1].wrapping_add(k[0] & 0xff).wrapping_mul(134_775_813).wrapping_add(1);
+		k[2] = crc_byte(k[2], (k[1] >> 24) as u8);
+	}
+
+	fn encrypt(&mut self, b: u8) -> u8 {
+		let t = (self.0[2] | 2) as u16;
+		let out = b ^ (t.wrapping_mul(t ^ 1) >> 8) as u8;
+		self.update(b);
+		out
+	}
+}
+
+struct CentralRecord {
+	name: String,
+	crc: u32,
+	size: u32,
+	offset: u32,
+}
+
+/// Minimal ZIP writer: stored members, ZipCrypto, no ZIP64.
+struct ZipCryptoWriter<W: Write> {
+	out: W,
+	password: Vec<u8>,
+	keys: Option<ZipCryptoKeys>,
+	offset: u64,
+	records: Vec<CentralRecord>,
+	time: (u16, u16),
+}
+
+const ZIP_FLAGS: u16 = 0x0001 | 0x0800; // encrypted, UTF-8 names
+
+impl<W: Write> ZipCryptoWriter<W> {
+	fn new(out: W, password: &[u8]) -> Self {
+		Self { out, password: password.to_vec(), keys: None, offset: 0, records: Vec::new(), time: dos_datetime(SystemTime::now()) }
+	}
+
+	fn put(&mut self, bytes: &[u8]) -> io::Result<()> {
+		self.out.write_all(bytes)?;
+		self.offset += bytes.len() as u64;
+		Ok(())
+	}
+
+	fn start_file(&mut self, name: &str, crc: u32, size: u64) -> Result<()> {
+		let size = u32::try_from(size).ok().filter(|s| *s <= u32::MAX - 12).context("Sample too large for ZIP export")?;
+		let offset = u32::try_from(self.offset).context("Export archive too large")?;
+		let mut h = Vec::with_capacity(30 + name.len());
+		h.extend(0x0403_4b50u32.to_le_bytes());
+		h.extend(20u16.to_le_bytes());
+		h.extend(ZIP_FLAGS.to_le_bytes());
+		h.extend(0u16.to_le_bytes()); // stored
+		h.extend(self.time.1.to_le_bytes());
+		h.extend(self.time.0.to_le_bytes());
+		h.extend(crc.to_le_bytes());
+		h.extend((size + 12).to_le_bytes());
+		h.extend(size.to_le_bytes());
+		h.extend((name.len() as u16).to_le_bytes());
+		h.extend(0u16.to_le_bytes());
+		h.extend(name.as_bytes());
+		self.put(&h)?;
+		let mut keys = ZipCryptoKeys::new(&self.password);
+		let mut header = [0u8; 12];
+		rand::thread_rng().fill_bytes(&mut header[..11]);
+		header[11] = (crc >> 24) as u8;
+		let header: Vec<u8> = header.iter().map(|b| keys.encrypt(*b)).collect();
+		self.put(&header)?;
+		self.keys = Some(keys);
+		self.records.push(CentralRecord { name: name.to_string(), crc, size, offset });
+		Ok(())
+	}
+
+	fn finish_file(&mut self) -> Result<()> {
+		self.keys = None;
+		Ok(())
+	}
+
+	fn finish(mut self) -> Result<W> {
+		let cd_start = u32::try_from(self.offset).context("Export archive too large")?;
+		let records = std::mem::take(&mut self.records);
+		for r in &records {
+			let mut c = Vec::with_capacity(46 + r.name.len());
+			c.extend(0x0201_4b50u32.to_le_bytes());
+			c.extend(20u16.to_le_bytes());
+			c.extend(20u16.to_le_bytes());
+			c.extend(ZIP_FLAGS.to_le_bytes());
+			c.extend(0u16.to_le_bytes());
+			c.extend(self.time.1.to_le_bytes());
+			c.extend(self.time.0.to_le_bytes());
+			c.extend(r.crc.to_le_bytes());
+			c.extend((r.size + 12).to_le_bytes());
+			c.extend(r.size.to_le_bytes());
+			c.extend((r.name.len() as u16).to_le_bytes());
+			c.extend([0u8; 12]); // extra, comment, disk, internal and external attributes
+			c.extend(r.offset.to_le_bytes());
+			c.extend(r.name.as_bytes());
+			self.put(&c)?;
+		}
+		let cd_size = u32::try_from(self.offset).context("Export archive too large")? - cd_start;
+		let mut e = Vec::with_capacity(22);
+		e.extend(0x0605_4b50u32.to_le_bytes());
+		e.extend([0u8; 4]);
+		e.extend((records.len() as u16).to_le_bytes());
+		e.extend((records.len() as u16).to_le_bytes());
+		e.extend(cd_size.to_le_bytes());
+		e.extend(cd_start.to_le_bytes());
+		e.extend(0u16.to_le_bytes());
+		self.put(&e)?;
+		self.out.flush()?;
+		Ok(self.out)
+	}
+}
+
+impl<W: Write> Write for ZipCryptoWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let keys = self.keys.as_mut().ok_or_else(|| io::Error::other("no ZIP member open"))?;
+		let enc: Vec<u8> = buf.iter().map(|b| keys.encrypt(*b)).collect();
+		self.put(&enc)?;
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.out.flush()
+	}
+}
+
+/// (date, time) in MS-DOS format, UTC.
+fn dos_datetime(t: SystemTime) -> (u16, u16) {
+	let secs = t.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+	let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+	// civil-from-days (Howard Hinnant)
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z - era * 146_097;
+	let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = (yoe + era * 400 + i64::from(month <= 2)).clamp(1980, 2107);
+	let date = (((year - 1980) as u16) << 9) | ((month as u16) << 5) | day as u16;
+	let time = (((rem / 3600) as u16) << 11) | ((((rem % 3600) / 60) as u16) << 5) | ((rem % 60) / 2) as u16;
+	(date, time)
+}
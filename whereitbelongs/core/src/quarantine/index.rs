@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::scan::Detection;
+
+pub(crate) const INDEX_FILE: &str = "index.json";
+
+/// One record in the quarantine index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+	pub id: String,
+	pub original_path: PathBuf,
+	pub sha256: String,
+	pub size: u64,
+	pub detection: Option<Detection>,
+	pub quarantined_by: String,
+	pub quarantined_at: SystemTime,
+	/// The copy is safe but the original could not be deleted; see `Quarantine::remove_original`.
+	#[serde(default)]
+	pub pending_removal: bool,
+	/// Under investigation; exempt from retention purges.
+	#[serde(default)]
+	pub pinned: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Index {
+	pub entries: Vec<QuarantineEntry>,
+}
+
+impl Index {
+	pub fn load(dir: &Path) -> Result<Self> {
+		let path = dir.join(INDEX_FILE);
+		match fs::read(&path) {
+			Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("Corrupt quarantine index: {}", path.display())),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+			Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+		}
+	}
+
+	/// Writes to a temp file and renames it over the index so a crash never leaves it half-written.
+	pub fn save(&self, dir: &Path) -> Result<()> {
+		let path = dir.join(INDEX_FILE);
+		let tmp = dir.join(format!("{}.tmp", INDEX_FILE));
+		let mut f = fs::File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?;
+		f.write_all(&serde_json::to_vec_pretty(self)?)?;
+		f.sync_all()?;
+		fs::rename(&tmp, &path).with_context(|| format!("Failed to replace {}", path.display()))
+	}
+
+	pub fn get(&self, id: &str) -> Option<&QuarantineEntry> {
+		self.entries.iter().find(|e| e.id == id)
+	}
+
+	pub fn get_mut(&mut self, id: &str) -> Option<&mut QuarantineEntry> {
+		self.entries.iter_mut().find(|e| e.id == id)
+	}
+
+	pub fn remove(&mut self, id: &str) -> Option<QuarantineEntry> {
+		let pos = self.entries.iter().position(|e| e.id == id)?;
+		Some(self.entries.remove(pos))
+	}
+}
@@ -0,0 +1,492 @@
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::allowlist::Allowlist;
+use crate::scan::{Detection, DetectionKind, ScanOptions, Scanner};
+
+mod container;
+mod export;
+mod index;
+mod retention;
+
+pub use container::QuarantineHeader;
+pub use export::{sidecar_path, ExportManifest, ExportOptions, ExportedSample, DEFAULT_EXPORT_PASSWORD};
+pub use index::QuarantineEntry;
+pub use retention::{PurgeReport, RetentionPolicy};
+
+use index::Index;
+
+/// `WIB_DATA_DIR`, or `whereitbelongs` under the platform data directory.
+pub fn app_data_dir() -> PathBuf {
+	if let Ok(p) = std::env::var("WIB_DATA_DIR") {
+		return PathBuf::from(p);
+	}
+	let base = dirs::data_dir().unwrap_or(std::env::temp_dir());
+	base.join("whereitbelongs")
+}
+
+fn ensure_dir(dir: &Path) -> Result<()> {
+	fs::create_dir_all(dir).with_context(|| format!("Failed to create dir: {}", dir.display()))
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+	let mut f = BufReader::new(fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?);
+	let mut h = Sha256::new();
+	let mut buf = [0u8; 64 * 1024];
+	loop {
+		let n = f.read(&mut buf)?;
+		if n == 0 { break; }
+		h.update(&buf[..n]);
+	}
+	Ok(format!("{:x}", h.finalize()))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum QuarantineError {
+	#[error("{path} changed while it was being quarantined")]
+	SourceChanged { path: PathBuf },
+	#[error("quarantined copy of {path} failed verification (expected {expected}, got {actual})")]
+	VerificationFailed { path: PathBuf, expected: String, actual: String },
+	/// The encrypted copy and index entry exist; the original is still on disk.
+	#[error("quarantined {path} as {id} but could not remove the original: {source}")]
+	RemovalFailed {
+		id: String,
+		path: PathBuf,
+		#[source]
+		source: io::Error,
+	},
+	#[error("{path} is allowlisted ({sha256}); not quarantining")]
+	Allowlisted { path: PathBuf, sha256: String },
+	/// Removing a link would leave its target in place, so links are refused outright.
+	#[error("{path} is a symbolic link; quarantine its target instead")]
+	Symlink { path: PathBuf },
+}
+
+/// Approval to restore a file the current signatures still detect.
+#[derive(Debug, Clone)]
+pub struct FalsePositive {
+	pub approved_by: String,
+	pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum RestoreOutcome {
+	/// Clean on re-scan, or restored as an approved false positive (`overridden` holds the detection).
+	Restored { path: PathBuf, overridden: Option<Detection> },
+	/// Still detected and not approved; the entry stays in quarantine.
+	StillDetected(Detection),
+}
+
+struct HashingReader<R> {
+	inner: R,
+	hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		self.hasher.update(&buf[..n]);
+		Ok(n)
+	}
+}
+
+fn sync_dir(dir: &Path) -> Result<()> {
+	#[cfg(unix)]
+	fs::File::open(dir).and_then(|d| d.sync_all()).with_context(|| format!("Failed to sync {}", dir.display()))?;
+	#[cfg(not(unix))]
+	let _ = dir;
+	Ok(())
+}
+
+/// Name recorded as `quarantined_by` when the caller does not supply one.
+pub fn current_user() -> String {
+	std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "system".into())
+}
+
+pub fn quarantine_dir() -> PathBuf {
+	app_data_dir().join("quarantine")
+}
+
+/// What `restore` does when the destination already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnConflict {
+	#[default]
+	Fail,
+	/// Restore next to it as `name (1).ext`, `name (2).ext`, ...
+	Rename,
+	Overwrite,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+	/// Defaults to the entry's original path.
+	pub destination: Option<PathBuf>,
+	pub on_conflict: OnConflict,
+}
+
+/// An indexed quarantine directory. Containers are stored as `<id>.qf` next to `index.json`.
+pub struct Quarantine {
+	dir: PathBuf,
+	key: [u8; 32],
+	retention: RetentionPolicy,
+	allowlist: Mutex<Allowlist>,
+	// serializes index read-modify-write within this process
+	lock: Mutex<()>,
+}
+
+impl Quarantine {
+	/// Opens (creating if needed) the quarantine in `dir`, with the allowlist kept in its `allowlist.json`.
+	pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+		let dir = dir.as_ref().to_path_buf();
+		ensure_dir(&dir)?;
+		let key = container::load_or_create_key(&dir)?;
+		let allowlist = Mutex::new(Allowlist::load(dir.join("allowlist.json"))?);
+		Ok(Self { dir, key, retention: RetentionPolicy::default(), allowlist, lock: Mutex::new(()) })
+	}
+
+	/// Opens the default quarantine with the default allowlist, applying `retention.json` from
+	/// the data directory if present.
+	pub fn open_default() -> Result<Self> {
+		let mut q = Self::open(quarantine_dir())?;
+		q.set_allowlist(Allowlist::load_default()?);
+		let policy = app_data_dir().join("retention.json");
+		if policy.exists() {
+			q.set_retention(RetentionPolicy::load(policy)?);
+		}
+		Ok(q)
+	}
+
+	/// Policy enforced automatically after every quarantine.
+	pub fn set_retention(&mut self, policy: RetentionPolicy) {
+		self.retention = policy;
+	}
+
+	pub fn retention(&self) -> &RetentionPolicy {
+		&self.retention
+	}
+
+	/// Hashes on this list are refused by `quarantine`; false positives approved on restore are added to it.
+	pub fn set_allowlist(&mut self, list: Allowlist) {
+		self.allowlist = Mutex::new(list);
+	}
+
+	pub fn allowlist(&self) -> Allowlist {
+		self.allowlist.lock().unwrap().clone()
+	}
+
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	pub fn container_path(&self, id: &str) -> PathBuf {
+		self.dir.join(format!("{}.qf", id))
+	}
+
+	pub fn entries(&self) -> Result<Vec<QuarantineEntry>> {
+		Ok(Index::load(&self.dir)?.entries)
+	}
+
+	pub fn get(&self, id: &str) -> Result<Option<QuarantineEntry>> {
+		Ok(Index::load(&self.dir)?.get(id).cloned())
+	}
+
+	/// Moves `path` into an encrypted container and records it in the index. The original is
+	/// only deleted once the copy is synced and decrypts back to the same SHA-256; if deletion
+	/// fails the entry stays indexed as `pending_removal` and [`QuarantineError::RemovalFailed`]
+	/// is returned.
+	pub fn quarantine<P: AsRef<Path>>(&self, path: P, detection: Option<&Detection>, by: &str) -> Result<QuarantineEntry> {
+		let path = path.as_ref();
+		let meta = fs::symlink_metadata(path).with_context(|| format!("Failed to stat {}", path.display()))?;
+		if meta.file_type().is_symlink() { return Err(QuarantineError::Symlink { path: path.to_path_buf() }.into()); }
+		let sha = sha256_file(path)?;
+		if self.allowlist.lock().unwrap().contains(&sha) {
+			return Err(QuarantineError::Allowlisted { path: path.to_path_buf(), sha256: sha }.into());
+		}
+		let header = QuarantineHeader::capture(path, &meta, sha, detection, by);
+		let id = uuid::Uuid::new_v4().to_string();
+		let qpath = self.container_path(&id);
+		let tmp = self.dir.join(format!("{}.qf.tmp", id));
+		if let Err(e) = self.write_verified(path, &header, &tmp) {
+			fs::remove_file(&tmp).ok();
+			return Err(e);
+		}
+		fs::rename(&tmp, &qpath).with_context(|| format!("Failed to move {} into place", tmp.display()))?;
+		sync_dir(&self.dir)?;
+		let mut entry = QuarantineEntry {
+			id,
+			original_path: header.original_path,
+			sha256: header.sha256,
+			size: header.size,
+			detection: header.detection,
+			quarantined_by: header.quarantined_by,
+			quarantined_at: header.quarantined_at,
+			pending_removal: false,
+			pinned: false,
+		};
+		let guard = self.lock.lock().unwrap();
+		let mut index = Index::load(&self.dir)?;
+		index.entries.push(entry.clone());
+		if let Err(e) = index.save(&self.dir) {
+			fs::remove_file(&qpath).ok();
+			return Err(e);
+		}
+		if let Err(source) = fs::remove_file(path) {
+			log::error!("Quarantined {} as {} but could not remove it: {}", path.display(), entry.id, source);
+			entry.pending_removal = true;
+			if let Some(e) = index.get_mut(&entry.id) { e.pending_removal = true; }
+			index.save(&self.dir)?;
+			return Err(QuarantineError::RemovalFailed { id: entry.id, path: path.to_path_buf(), source }.into());
+		}
+		drop(guard);
+		log::info!("Quarantined {} as {}", entry.original_path.display(), entry.id);
+		if !self.retention.is_unlimited() {
+			if let Err(e) = self.purge() { log::warn!("Quarantine purge failed: {:#}", e); }
+		}
+		Ok(entry)
+	}
+
+	// Streams `src` into `tmp`, fsyncs it, then decrypts it back and compares hashes.
+	fn write_verified(&self, src: &Path, header: &QuarantineHeader, tmp: &Path) -> Result<()> {
+		let file = fs::File::open(src).with_context(|| format!("Failed to open {}", src.display()))?;
+		let mut reader = HashingReader { inner: BufReader::new(file), hasher: Sha256::new() };
+		let out = fs::File::create(tmp).with_context(|| format!("Failed to create {}", tmp.display()))?;
+		let mut out = BufWriter::new(out);
+		container::write_container(&self.key, header, &mut reader, &mut out)?;
+		out.into_inner().map_err(|e| e.into_error())?.sync_all().with_context(|| format!("Failed to sync {}", tmp.display()))?;
+		if format!("{:x}", reader.hasher.finalize()) != header.sha256 {
+			return Err(QuarantineError::SourceChanged { path: src.to_path_buf() }.into());
+		}
+		let mut r = BufReader::new(fs::File::open(tmp)?);
+		let (_, prefix) = container::read_header(&self.key, &mut r)?;
+		let mut hasher = Sha256::new();
+		container::read_payload(&self.key, &prefix, &mut r, &mut hasher)?;
+		let actual = format!("{:x}", hasher.finalize());
+		if actual != header.sha256 {
+			return Err(QuarantineError::VerificationFailed { path: src.to_path_buf(), expected: header.sha256.clone(), actual }.into());
+		}
+		Ok(())
+	}
+
+	/// Retries deleting the original of an entry left `pending_removal`. Refuses if the file
+	/// at the original path no longer has the quarantined hash.
+	pub fn remove_original(&self, id: &str) -> Result<()> {
+		let _guard = self.lock.lock().unwrap();
+		let mut index = Index::load(&self.dir)?;
+		let entry = index.get_mut(id).with_context(|| format!("No quarantine entry {}", id))?;
+		if !entry.pending_removal { return Ok(()); }
+		let path = entry.original_path.clone();
+		if path.exists() {
+			if sha256_file(&path)? != entry.sha256 { bail!("{} has changed since it was quarantined; not removing", path.display()); }
+			fs::remove_file(&path).map_err(|source| QuarantineError::RemovalFailed { id: id.to_string(), path: path.clone(), source })?;
+		}
+		entry.pending_removal = false;
+		index.save(&self.dir)?;
+		log::info!("Removed original {} of quarantine entry {}", path.display(), id);
+		Ok(())
+	}
+
+	pub fn read_header(&self, id: &str) -> Result<QuarantineHeader> {
+		read_quarantine_header(self.container_path(id))
+	}
+
+	/// Restores an entry (to its original path unless overridden) and drops it from quarantine.
+	/// Returns the path actually written.
+	pub fn restore(&self, id: &str, opts: &RestoreOptions) -> Result<PathBuf> {
+		let _guard = self.lock.lock().unwrap();
+		let mut index = Index::load(&self.dir)?;
+		let entry = index.get(id).with_context(|| format!("No quarantine entry {}", id))?.clone();
+		let dest = opts.destination.clone().unwrap_or_else(|| entry.original_path.clone());
+		if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+			ensure_dir(parent)?;
+		}
+		let name = dest.file_name().with_context(|| format!("Invalid restore path {}", dest.display()))?;
+		let tmp = dest.with_file_name(format!(".{}.{}.restoring", name.to_string_lossy(), id));
+		let out = fs::File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?;
+		let dest = match decrypt_into(&self.key, &self.container_path(id), out, &tmp).and_then(|_| place_restored(&tmp, &dest, opts.on_conflict)) {
+			Ok(dest) => dest,
+			Err(e) => {
+				fs::remove_file(&tmp).ok();
+				return Err(e);
+			}
+		};
+		index.remove(id);
+		index.save(&self.dir)?;
+		fs::remove_file(self.container_path(id)).ok();
+		log::info!("Restored quarantine entry {} to {}", id, dest.display());
+		Ok(dest)
+	}
+
+	fn decrypt_payload<W: io::Write>(&self, id: &str, out: W) -> Result<u64> {
+		let qpath = self.container_path(id);
+		let mut r = BufReader::new(fs::File::open(&qpath).with_context(|| format!("Failed to open {}", qpath.display()))?);
+		let (_, prefix) = container::read_header(&self.key, &mut r)?;
+		container::read_payload(&self.key, &prefix, &mut r, out)
+	}
+
+	/// Scans a quarantined entry with `scanner` (size and extension filters off); the detection,
+	/// if any, is reported against the original path.
+	pub fn rescan(&self, id: &str, scanner: &Scanner) -> Result<Option<Detection>> {
+		let entry = self.get(id)?.with_context(|| format!("No quarantine entry {}", id))?;
+		let mut buf = Vec::new();
+		self.decrypt_payload(id, &mut buf)?;
+		let opts = ScanOptions { include_extensions: None, max_file_size_bytes: u64::MAX, ..Default::default() };
+		let name = entry.original_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+		Ok(scanner.scan_bytes(&buf, &name, &opts).into_iter().next().map(|mut d| {
+			d.path = entry.original_path.clone();
+			d
+		}))
+	}
+
+	/// Re-scans an entry with the current signatures before restoring it. A file that is still
+	/// detected is only restored when `false_positive` is given, which also allowlists its hash.
+	pub fn restore_rescanned(&self, id: &str, opts: &RestoreOptions, scanner: &Scanner, false_positive: Option<&FalsePositive>) -> Result<RestoreOutcome> {
+		let Some(detection) = self.rescan(id, scanner)? else {
+			return Ok(RestoreOutcome::Restored { path: self.restore(id, opts)?, overridden: None });
+		};
+		let Some(fp) = false_positive else {
+			log::info!("Quarantine entry {} is still detected; not restoring", id);
+			return Ok(RestoreOutcome::StillDetected(detection));
+		};
+		let entry = self.get(id)?.with_context(|| format!("No quarantine entry {}", id))?;
+		let name = match &detection.kind {
+			DetectionKind::Signature { name, .. } => name.clone(),
+			DetectionKind::Heuristic { description } => description.clone(),
+		};
+		self.allowlist.lock().unwrap().add(&entry.sha256, &fp.approved_by, &fp.reason, Some(name))?;
+		Ok(RestoreOutcome::Restored { path: self.restore(id, opts)?, overridden: Some(detection) })
+	}
+
+	/// Pins or unpins an entry; pinned entries survive retention purges.
+	pub fn set_pinned(&self, id: &str, pinned: bool) -> Result<()> {
+		let _guard = self.lock.lock().unwrap();
+		let mut index = Index::load(&self.dir)?;
+		index.get_mut(id).with_context(|| format!("No quarantine entry {}", id))?.pinned = pinned;
+		index.save(&self.dir)
+	}
+
+	/// Applies the configured retention policy.
+	pub fn purge(&self) -> Result<PurgeReport> {
+		self.purge_with(&self.retention)
+	}
+
+	pub fn purge_with(&self, policy: &RetentionPolicy) -> Result<PurgeReport> {
+		let _guard = self.lock.lock().unwrap();
+		let mut index = Index::load(&self.dir)?;
+		let sized: Vec<(QuarantineEntry, u64)> = index
+			.entries
+			.iter()
+			.map(|e| (e.clone(), fs::metadata(self.container_path(&e.id)).map(|m| m.len()).unwrap_or(e.size)))
+			.collect();
+		let mut report = PurgeReport::default();
+		for id in policy.select(&sized, std::time::SystemTime::now()) {
+			let qpath = self.container_path(&id);
+			let size = sized.iter().find(|(e, _)| e.id == id).map(|(_, s)| *s).unwrap_or(0);
+			if qpath.exists() {
+				if let Err(e) = fs::remove_file(&qpath) {
+					log::warn!("Failed to purge quarantine entry {}: {}", id, e);
+					continue;
+				}
+			}
+			let Some(entry) = index.remove(&id) else { continue };
+			log::info!("Purged quarantine entry {} ({}, {} bytes)", id, entry.original_path.display(), size);
+			report.freed_bytes += size;
+			report.purged.push(entry);
+		}
+		if !report.purged.is_empty() {
+			index.save(&self.dir)?;
+		}
+		report.remaining = index.entries.len();
+		report.remaining_bytes = sized.iter().filter(|(e, _)| index.get(&e.id).is_some()).map(|(_, s)| s).sum();
+		log::info!(
+			"Quarantine purge removed {} entries ({} bytes); {} entries ({} bytes) remain",
+			report.purged.len(),
+			report.freed_bytes,
+			report.remaining,
+			report.remaining_bytes
+		);
+		Ok(report)
+	}
+
+	/// Permanently removes an entry and its container.
+	pub fn delete(&self, id: &str) -> Result<()> {
+		let _guard = self.lock.lock().unwrap();
+		let mut index = Index::load(&self.dir)?;
+		if index.remove(id).is_none() { bail!("No quarantine entry {}", id); }
+		let qpath = self.container_path(id);
+		if qpath.exists() {
+			fs::remove_file(&qpath).with_context(|| format!("Failed to delete {}", qpath.display()))?;
+		}
+		index.save(&self.dir)?;
+		log::info!("Deleted quarantine entry {}", id);
+		Ok(())
+	}
+}
+
+/// Moves a fully restored temp file into place without clobbering unless asked to.
+fn place_restored(tmp: &Path, dest: &Path, on_conflict: OnConflict) -> Result<PathBuf> {
+	if on_conflict == OnConflict::Overwrite {
+		fs::rename(tmp, dest).with_context(|| format!("Failed to replace {}", dest.display()))?;
+		return Ok(dest.to_path_buf());
+	}
+	let stem = dest.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+	let ext = dest.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+	for n in 0..10_000 {
+		let candidate = if n == 0 { dest.to_path_buf() } else { dest.with_file_name(format!("{} ({}){}", stem, n, ext)) };
+		// hard_link fails atomically if the target exists, unlike rename
+		match fs::hard_link(tmp, &candidate) {
+			Ok(()) => {
+				fs::remove_file(tmp).ok();
+				return Ok(candidate);
+			}
+			Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && on_conflict == OnConflict::Rename => continue,
+			Err(e) => return Err(e).with_context(|| format!("Failed to restore to {}", candidate.display())),
+		}
+	}
+	bail!("No free name to restore {}", dest.display())
+}
+
+fn decrypt_into(key: &[u8; 32], qpath: &Path, out: fs::File, dest: &Path) -> Result<QuarantineHeader> {
+	let mut r = BufReader::new(fs::File::open(qpath).with_context(|| format!("Failed to open {}", qpath.display()))?);
+	let (header, prefix) = container::read_header(key, &mut r)?;
+	container::read_payload(key, &prefix, &mut r, BufWriter::new(out))?;
+	header.apply_metadata(dest)?;
+	Ok(header)
+}
+
+pub fn quarantine_file<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+	quarantine_file_with(path, None)
+}
+
+/// Quarantines into the default directory as the current user; returns the container path.
+pub fn quarantine_file_with<P: AsRef<Path>>(path: P, detection: Option<&Detection>) -> Result<PathBuf> {
+	let q = Quarantine::open_default()?;
+	let entry = q.quarantine(path, detection, &current_user())?;
+	Ok(q.container_path(&entry.id))
+}
+
+/// Decrypts only the metadata header of a quarantined file.
+pub fn read_quarantine_header<P: AsRef<Path>>(quarantined_path: P) -> Result<QuarantineHeader> {
+	let quarantined_path = quarantined_path.as_ref();
+	let key = container::load_key(quarantined_path.parent().unwrap_or(Path::new(".")))?;
+	let f = fs::File::open(quarantined_path).with_context(|| format!("Failed to open {}", quarantined_path.display()))?;
+	Ok(container::read_header(&key, &mut BufReader::new(f))?.0)
+}
+
+/// Decrypts a quarantined file to `restore_to` and re-applies its original owner, mode and timestamps.
+/// Leaves the container and index untouched; use [`Quarantine::restore`] to restore by id.
+pub fn restore_from_quarantine<P: AsRef<Path>>(quarantined_path: P, restore_to: P) -> Result<QuarantineHeader> {
+	let quarantined_path = quarantined_path.as_ref();
+	let restore_to = restore_to.as_ref();
+	let key = container::load_key(quarantined_path.parent().unwrap_or(Path::new(".")))?;
+	let out = fs::File::create(restore_to).with_context(|| format!("Failed to create {}", restore_to.display()))?;
+	decrypt_into(&key, quarantined_path, out, restore_to)
+}
+
+pub fn list_quarantined() -> Result<Vec<QuarantineEntry>> {
+	if !quarantine_dir().exists() { return Ok(Vec::new()); }
+	Quarantine::open_default()?.entries()
+}
@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use super::index::QuarantineEntry;
+
+/// Limits enforced by [`super::Quarantine::purge`]. Pinned entries are never purged.
+/// Deployments configure this through `retention.json` in the data directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+	#[serde(default)]
+	pub max_age_days: Option<u64>,
+	/// Cap on the on-disk size of all containers; oldest unpinned entries go first.
+	#[serde(default)]
+	pub max_total_bytes: Option<u64>,
+	/// Keep at most this many unpinned entries, newest first.
+	#[serde(default)]
+	pub keep_newest: Option<usize>,
+}
+
+impl RetentionPolicy {
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref();
+		let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+		serde_json::from_str(&text).with_context(|| format!("Invalid retention policy in {}", path.display()))
+	}
+
+	pub fn is_unlimited(&self) -> bool {
+		self.max_age_days.is_none() && self.max_total_bytes.is_none() && self.keep_newest.is_none()
+	}
+
+	/// Ids to purge from `entries`, each paired with its on-disk size.
+	pub(crate) fn select(&self, entries: &[(QuarantineEntry, u64)], now: SystemTime) -> Vec<String> {
+		let mut order: Vec<&(QuarantineEntry, u64)> = entries.iter().collect();
+		order.sort_by_key(|e| std::cmp::Reverse(e.0.quarantined_at));
+		let max_age = self.max_age_days.map(|d| Duration::from_secs(d * 24 * 60 * 60));
+		let mut purge = Vec::new();
+		let mut kept = Vec::new();
+		let mut unpinned = 0;
+		for (entry, size) in order {
+			if entry.pinned {
+				kept.push((entry, *size));
+				continue;
+			}
+			let too_old = max_age.is_some_and(|max| now.duration_since(entry.quarantined_at).unwrap_or_default() > max);
+			let too_many = self.keep_newest.is_some_and(|n| unpinned >= n);
+			if too_old || too_many {
+				purge.push(entry.id.clone());
+			} else {
+				unpinned += 1;
+				kept.push((entry, *size));
+			}
+		}
+		if let Some(max_bytes) = self.max_total_bytes {
+			let mut total: u64 = kept.iter().map(|(_, s)| s).sum();
+			// kept is newest first, so walk it backwards
+			for (entry, size) in kept.iter().rev() {
+				if total <= max_bytes { break; }
+				if entry.pinned { continue; }
+				purge.push(entry.id.clone());
+				total -= size;
+			}
+		}
+		purge
+	}
+}
+
+/// Outcome of one purge run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PurgeReport {
+	pub purged: Vec<QuarantineEntry>,
+	pub freed_bytes: u64,
+	pub remaining: usize,
+	pub remaining_bytes: u64,
+}
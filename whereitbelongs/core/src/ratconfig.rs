@@ -0,0 +1,371 @@
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::pe;
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+// Salt shared by AsyncRAT and Quasar (and most of their forks) for PBKDF2 key derivation
+const DOTNET_AES_SALT: [u8; 32] = [
+	0xBF, 0xEB, 0x1E, 0x56, 0xFB, 0xCD, 0x97, 0x3B, 0xB2, 0x19, 0x02, 0x24, 0x30, 0xA5, 0x78, 0x43,
+	0x00, 0x3D, 0x56, 0x44, 0xD2, 0x1E, 0x62, 0xB9, 0xD4, 0xF1, 0x80, 0xE7, 0xE6, 0xC3, 0x39, 0x41,
+];
+const DOTNET_AES_ITERATIONS: u32 = 50_000;
+const MAX_KEY_CANDIDATES: usize = 16;
+const NJRAT_SPLITTER: &str = "|'|'|";
+const REMCOS_FIELD_SEPARATOR: &[u8] = b"|\x1e\x1e\x1f|";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct C2Endpoint {
+	pub host: String,
+	pub port: Option<u16>,
+}
+
+impl C2Endpoint {
+	pub fn ip(&self) -> Option<IpAddr> {
+		self.host.parse().ok()
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RatConfig {
+	pub family: String,
+	pub c2: Vec<C2Endpoint>,
+	pub mutex: Option<String>,
+	pub install_path: Option<String>,
+	pub campaign_id: Option<String>,
+	pub encryption_key: Option<String>,
+	pub version: Option<String>,
+	pub extra: BTreeMap<String, String>,
+}
+
+impl RatConfig {
+	fn new(family: &str) -> Self {
+		Self { family: family.to_string(), ..Default::default() }
+	}
+
+	fn is_empty(&self) -> bool {
+		self.c2.is_empty() && self.mutex.is_none() && self.install_path.is_none() && self.campaign_id.is_none()
+	}
+
+	fn push_c2(&mut self, host: &str, port: Option<u16>) {
+		let host = host.trim().to_string();
+		if host.is_empty() || self.c2.iter().any(|c| c.host == host && c.port == port) { return; }
+		self.c2.push(C2Endpoint { host, port });
+	}
+}
+
+/// Tries the extractor for `family` against the raw sample bytes. `family` is a signatures.json family or
+/// the family part of a ClamAV signature name (`Win.Trojan.Remcos-123` -> `Remcos`); case is ignored.
+pub fn extract_config(family: &str, data: &[u8]) -> Option<RatConfig> {
+	match family.to_lowercase().as_str() {
+		"njrat" => extract_njrat(data),
+		"remcos" => extract_remcos(data),
+		"asyncrat" => extract_dotnet_aes(data, "AsyncRAT", true),
+		"quasar" => extract_dotnet_aes(data, "Quasar", false),
+		_ => None,
+	}
+}
+
+fn ascii_strings(data: &[u8], min_len: usize) -> Vec<String> {
+	let mut out = Vec::new();
+	let mut cur = String::new();
+	for &b in data {
+		if (0x20..0x7f).contains(&b) {
+			cur.push(b as char);
+		} else {
+			if cur.len() >= min_len { out.push(std::mem::take(&mut cur)); }
+			cur.clear();
+		}
+	}
+	if cur.len() >= min_len { out.push(cur); }
+	out
+}
+
+/// UTF-16LE strings, which is how .NET stores literals in the #US heap.
+pub(crate) fn utf16_strings(data: &[u8], min_len: usize) -> Vec<String> {
+	let mut out = Vec::new();
+	for start in 0..2 {
+		let mut cur = String::new();
+		for pair in data.get(start..).unwrap_or_default().chunks_exact(2) {
+			if pair[1] == 0 && (0x20..0x7f).contains(&pair[0]) {
+				cur.push(pair[0] as char);
+			} else {
+				if cur.len() >= min_len { out.push(std::mem::take(&mut cur)); }
+				cur.clear();
+			}
+		}
+		if cur.len() >= min_len { out.push(cur); }
+	}
+	out
+}
+
+fn is_hostname(s: &str) -> bool {
+	if s.parse::<IpAddr>().is_ok() { return true; }
+	let lower = s.to_lowercase();
+	if !lower.contains('.') || lower.len() > 253 { return false; }
+	if [".exe", ".dll", ".bat", ".vbs", ".ps1", ".lnk"].iter().any(|e| lower.ends_with(e)) { return false; }
+	let labels: Vec<&str> = lower.split('.').collect();
+	let tld = labels.last().copied().unwrap_or_default();
+	labels.iter().all(|l| !l.is_empty() && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+		&& tld.len() >= 2
+		&& (tld.chars().all(|c| c.is_ascii_alphabetic()) || s.parse::<IpAddr>().is_ok())
+}
+
+fn parse_port(s: &str) -> Option<u16> {
+	if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) { return None; }
+	s.parse::<u16>().ok().filter(|p| *p > 0)
+}
+
+fn is_version(s: &str) -> bool {
+	let mut parts = s.split('.');
+	let major = parts.next().unwrap_or_default();
+	!major.is_empty()
+		&& major.chars().all(|c| c.is_ascii_digit())
+		&& parts.clone().count() >= 1
+		&& parts.all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn decode_printable_b64(s: &str) -> Option<String> {
+	if s.len() < 4 || !s.len().is_multiple_of(4) { return None; }
+	let raw = general_purpose::STANDARD.decode(s).ok()?;
+	let text = String::from_utf8(raw).ok()?;
+	if !text.is_empty() && text.chars().all(|c| !c.is_control()) { Some(text) } else { None }
+}
+
+/// Parses "host:port" pairs separated by any of `;`, `,` or whitespace.
+fn parse_host_port_list(s: &str) -> Vec<(String, Option<u16>)> {
+	s.split(|c: char| c == ';' || c == ',' || c.is_whitespace())
+		.filter(|p| !p.is_empty())
+		.filter_map(|p| {
+			let mut it = p.split(':');
+			let host = it.next()?;
+			let port = it.next().and_then(parse_port);
+			if is_hostname(host) { Some((host.to_string(), port)) } else { None }
+		})
+		.collect()
+}
+
+const INSTALL_DIRS: [&str; 7] = ["temp", "appdata", "userprofile", "programdata", "windir", "localappdata", "startup"];
+
+// njRAT keeps its settings as plain string literals next to the `|'|'|` field splitter
+fn extract_njrat(data: &[u8]) -> Option<RatConfig> {
+	let mut strings = utf16_strings(data, 2);
+	strings.extend(ascii_strings(data, 2));
+	let has_splitter = strings.iter().any(|s| s.contains(NJRAT_SPLITTER));
+	let mut cfg = RatConfig::new("njRAT");
+	let mut exe = None;
+	let mut dir = None;
+	for (i, s) in strings.iter().enumerate() {
+		let s = s.trim();
+		if is_hostname(s) && cfg.c2.is_empty() {
+			let port = strings.iter().skip(i + 1).take(3).find_map(|n| parse_port(n.trim()));
+			cfg.push_c2(s, port);
+		} else if s.to_lowercase().ends_with(".exe") && exe.is_none() {
+			exe = Some(s.to_string());
+		} else if INSTALL_DIRS.contains(&s.to_lowercase().as_str()) && dir.is_none() {
+			dir = Some(s.to_string());
+		} else if s.len() == 32 && s.chars().all(|c| c.is_ascii_hexdigit()) && cfg.mutex.is_none() {
+			cfg.mutex = Some(s.to_string());
+		} else if is_version(s) && s.len() <= 12 && cfg.version.is_none() && !is_hostname(s) {
+			cfg.version = Some(s.to_string());
+		} else if let Some(name) = decode_printable_b64(s).filter(|_| cfg.campaign_id.is_none()) {
+			cfg.campaign_id = Some(name);
+		}
+	}
+	cfg.install_path = match (dir, exe) {
+		(Some(d), Some(e)) => Some(format!("%{}%\\{}", d, e)),
+		(None, Some(e)) => Some(e),
+		_ => None,
+	};
+	if has_splitter { cfg.extra.insert("splitter".into(), NJRAT_SPLITTER.into()); }
+	if (has_splitter || !cfg.c2.is_empty()) && !cfg.is_empty() { Some(cfg) } else { None }
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+	let mut s: Vec<u8> = (0..=255).collect();
+	let mut j: u8 = 0;
+	for i in 0..256 {
+		j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+		s.swap(i, j as usize);
+	}
+	let (mut i, mut j) = (0u8, 0u8);
+	data.iter()
+		.map(|b| {
+			i = i.wrapping_add(1);
+			j = j.wrapping_add(s[i as usize]);
+			s.swap(i as usize, j as usize);
+			b ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+		})
+		.collect()
+}
+
+// Remcos stores an RC4-encrypted settings blob in the SETTINGS resource: [key_len][key][ciphertext]
+fn extract_remcos(data: &[u8]) -> Option<RatConfig> {
+	let blob = pe::rcdata(data, "SETTINGS")?;
+	let key_len = *blob.first()? as usize;
+	if key_len == 0 || blob.len() <= key_len + 1 { return None; }
+	let key = &blob[1..=key_len];
+	let plain = rc4(key, &blob[key_len + 1..]);
+	let fields = split_bytes(&plain, REMCOS_FIELD_SEPARATOR);
+	if fields.len() < 2 { return None; }
+	let mut cfg = RatConfig::new("Remcos");
+	cfg.encryption_key = Some(hex(key));
+	// first field: "host:port:tls" entries separated by \x1e (older builds use '|')
+	for hp in fields[0].split(|b| *b == 0x1e || *b == b'|') {
+		let text = String::from_utf8_lossy(hp);
+		let mut it = text.split(':');
+		if let Some(host) = it.next().filter(|h| is_hostname(h)) {
+			cfg.push_c2(host, it.next().and_then(parse_port));
+		}
+	}
+	let campaign = String::from_utf8_lossy(fields[1]).trim().to_string();
+	if !campaign.is_empty() { cfg.campaign_id = Some(campaign); }
+	for (i, f) in fields.iter().enumerate().skip(2) {
+		let text = String::from_utf8_lossy(f).trim_matches('\0').to_string();
+		if text.is_empty() { continue; }
+		if text.starts_with("Rmc-") && cfg.mutex.is_none() {
+			cfg.mutex = Some(text);
+		} else if text.to_lowercase().ends_with(".exe") && cfg.install_path.is_none() {
+			cfg.install_path = Some(text);
+		} else {
+			cfg.extra.insert(format!("field_{}", i), text);
+		}
+	}
+	if cfg.c2.is_empty() { None } else { Some(cfg) }
+}
+
+fn split_bytes<'a>(data: &'a [u8], sep: &[u8]) -> Vec<&'a [u8]> {
+	let mut out = Vec::new();
+	let mut start = 0;
+	let mut i = 0;
+	while i + sep.len() <= data.len() {
+		if &data[i..i + sep.len()] == sep {
+			out.push(&data[start..i]);
+			i += sep.len();
+			start = i;
+		} else {
+			i += 1;
+		}
+	}
+	out.push(&data[start..]);
+	out
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+struct DotnetAesKey {
+	key: [u8; 32],
+	auth_key: [u8; 64],
+}
+
+impl DotnetAesKey {
+	fn derive(master: &str) -> Self {
+		// Rfc2898DeriveBytes: GetBytes(32) for the AES key, then GetBytes(64) for the HMAC key
+		let mut out = [0u8; 96];
+		pbkdf2::pbkdf2_hmac::<Sha1>(master.as_bytes(), &DOTNET_AES_SALT, DOTNET_AES_ITERATIONS, &mut out);
+		let mut key = [0u8; 32];
+		let mut auth_key = [0u8; 64];
+		key.copy_from_slice(&out[..32]);
+		auth_key.copy_from_slice(&out[32..]);
+		Self { key, auth_key }
+	}
+
+	/// Input layout: HMAC-SHA256(iv || ciphertext) [32] || IV [16] || AES-256-CBC ciphertext
+	fn decrypt(&self, blob: &[u8]) -> Option<String> {
+		if blob.len() < 64 || !(blob.len() - 48).is_multiple_of(16) { return None; }
+		let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.auth_key).ok()?;
+		mac.update(&blob[32..]);
+		mac.verify_slice(&blob[..32]).ok()?;
+		let mut buf = blob[48..].to_vec();
+		let plain = Aes256CbcDec::new(&self.key.into(), blob[32..48].into())
+			.decrypt_padded_mut::<Pkcs7>(&mut buf)
+			.ok()?;
+		String::from_utf8(plain.to_vec()).ok()
+	}
+}
+
+fn encrypted_blob(s: &str) -> Option<Vec<u8>> {
+	if s.len() < 88 { return None; }
+	let raw = general_purpose::STANDARD.decode(s).ok()?;
+	if raw.len() >= 64 && (raw.len() - 48).is_multiple_of(16) { Some(raw) } else { None }
+}
+
+// AsyncRAT and Quasar encrypt every setting with AES-256-CBC + HMAC-SHA256 using a key derived from a
+// master string that ships in the same binary (base64-encoded for AsyncRAT, plain for Quasar).
+fn extract_dotnet_aes(data: &[u8], family: &str, b64_master: bool) -> Option<RatConfig> {
+	let strings = utf16_strings(data, 8);
+	let blobs: Vec<Vec<u8>> = strings.iter().filter_map(|s| encrypted_blob(s)).collect();
+	if blobs.is_empty() { return None; }
+	let candidates = strings
+		.iter()
+		.filter(|s| encrypted_blob(s).is_none())
+		.filter_map(|s| {
+			if b64_master {
+				decode_printable_b64(s).filter(|m| m.len() >= 16)
+			} else if (16..=64).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()) {
+				Some(s.clone())
+			} else {
+				None
+			}
+		})
+		.take(MAX_KEY_CANDIDATES);
+	for master in candidates {
+		let key = DotnetAesKey::derive(&master);
+		let values: Vec<String> = blobs.iter().filter_map(|b| key.decrypt(b)).collect();
+		if values.is_empty() { continue; }
+		let mut cfg = RatConfig::new(family);
+		cfg.encryption_key = Some(master);
+		let mut ports = Vec::new();
+		let mut hosts = Vec::new();
+		for v in values {
+			classify_dotnet_value(&mut cfg, &v, &mut hosts, &mut ports);
+		}
+		for h in hosts {
+			if ports.is_empty() { cfg.push_c2(&h, None); }
+			for p in &ports { cfg.push_c2(&h, Some(*p)); }
+		}
+		return Some(cfg);
+	}
+	None
+}
+
+fn classify_dotnet_value(cfg: &mut RatConfig, v: &str, hosts: &mut Vec<String>, ports: &mut Vec<u16>) {
+	let v = v.trim();
+	let lower = v.to_lowercase();
+	let list: Vec<&str> = v.split(',').map(str::trim).filter(|p| !p.is_empty()).collect();
+	if v.contains(':') && !parse_host_port_list(v).is_empty() {
+		// Quasar: "host:port;host:port;"
+		for (h, p) in parse_host_port_list(v) { cfg.push_c2(&h, p); }
+	} else if !list.is_empty() && list.iter().all(|p| parse_port(p).is_some()) {
+		ports.extend(list.iter().filter_map(|p| parse_port(p)));
+	} else if !list.is_empty() && list.iter().all(|p| is_hostname(p)) {
+		hosts.extend(list.iter().map(|p| p.to_string()));
+	} else if lower.contains("mutex") || lower.starts_with("asyncmutex") {
+		cfg.mutex = Some(v.to_string());
+	} else if lower.starts_with('%') && cfg.install_path.is_none() {
+		cfg.install_path = Some(v.to_string());
+	} else if lower.ends_with(".exe") {
+		cfg.install_path = Some(match cfg.install_path.take() {
+			Some(dir) => format!("{}\\{}", dir.trim_end_matches('\\'), v),
+			None => v.to_string(),
+		});
+	} else if is_version(v) && cfg.version.is_none() {
+		cfg.version = Some(v.to_string());
+	} else if lower == "true" || lower == "false" || v.len() > 128 {
+		cfg.extra.insert(format!("value_{}", cfg.extra.len()), v.to_string());
+	} else if cfg.campaign_id.is_none() {
+		// AsyncRAT "Group" / Quasar "TAG"
+		cfg.campaign_id = Some(v.to_string());
+	} else {
+		cfg.extra.insert(format!("value_{}", cfg.extra.len()), v.to_string());
+	}
+}
@@ -1,9 +1,17 @@
+use anyhow::Context;
 use rayon::prelude::*;
 use regex::Regex;
+use crate::allowlist::Allowlist;
+use crate::clamav::{self, ClamFormat, ImportReport};
+use crate::ioc::{extract_iocs_from_bytes, Ioc, IocOptions};
+use crate::mail;
+use crate::ratconfig::{extract_config, RatConfig};
+use crate::signature::SignatureSet;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::fs;
-use std::io::Read;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
@@ -19,6 +27,13 @@ pub struct Detection {
 	pub kind: DetectionKind,
 	pub severity: u8,
 	pub sha256: Option<String>,
+	#[serde(default)]
+	pub config: Option<RatConfig>,
+	#[serde(default)]
+	pub iocs: Vec<Ioc>,
+	/// Inner item of a container file that matched, e.g. a mail attachment name.
+	#[serde(default)]
+	pub member: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +41,11 @@ pub struct ScanOptions {
 	pub include_extensions: Option<Vec<String>>, // lowercase without dot, e.g., ["exe","dll","js"]
 	pub enable_heuristics: bool,
 	pub max_file_size_bytes: u64,
+	#[serde(default)]
+	pub ioc_options: IocOptions,
+	/// Files or directories never scanned, e.g. canary files.
+	#[serde(default)]
+	pub exclude_paths: Vec<PathBuf>,
 }
 
 impl Default for ScanOptions {
@@ -34,6 +54,8 @@ impl Default for ScanOptions {
 			include_extensions: None,
 			enable_heuristics: true,
 			max_file_size_bytes: 16 * 1024 * 1024, // 16 MiB
+			ioc_options: IocOptions::default(),
+			exclude_paths: Vec::new(),
 		}
 	}
 }
@@ -52,12 +74,17 @@ struct SignatureDbRaw {
 
 struct SignatureDb {
 	patterns: Vec<(String, String, Regex)>,
+	compiled: SignatureSet,
 }
 
 impl SignatureDb {
 	fn load() -> Self {
 		let raw_json = include_str!("../assets/signatures.json");
-		let raw: SignatureDbRaw = serde_json::from_str(raw_json).expect("invalid signatures.json");
+		Self::from_json(raw_json).expect("invalid signatures.json")
+	}
+
+	fn from_json(raw_json: &str) -> anyhow::Result<Self> {
+		let raw: SignatureDbRaw = serde_json::from_str(raw_json)?;
 		let patterns = raw
 			.signatures
 			.into_iter()
@@ -65,7 +92,7 @@ impl SignatureDb {
 				Regex::new(&format!("(?i){}", s.pattern)).ok().map(|re| (s.name, s.family, re))
 			})
 			.collect();
-		Self { patterns }
+		Ok(Self { patterns, compiled: SignatureSet::default() })
 	}
 }
 
@@ -80,6 +107,7 @@ fn compute_sha256(bytes: &[u8]) -> String {
 }
 
 fn should_scan(path: &Path, options: &ScanOptions) -> bool {
+	if options.exclude_paths.iter().any(|e| path.starts_with(e)) { return false; }
 	if let Some(exts) = &options.include_extensions {
 		if let Some(ext) = file_extension_lowercase(path) {
 			return exts.iter().any(|e| e == &ext);
@@ -119,58 +147,194 @@ fn run_heuristics(path: &Path, content: &str) -> Vec<Detection> {
 			},
 			severity: 6,
 			sha256: None,
+			config: None,
+			iocs: Vec::new(),
+			member: None,
 		});
 	}
 	detections
 }
 
-pub fn scan_paths<P: AsRef<Path> + Send + Sync>(paths: &[P], options: ScanOptions) -> Vec<Detection> {
-	let sigdb = SignatureDb::load();
-	let candidates: Vec<PathBuf> = paths
-		.par_iter()
-		.flat_map_iter(|p| {
-			WalkDir::new(p)
-				.follow_links(false)
-				.into_iter()
-				.filter_map(Result::ok)
-				.filter(|e| e.file_type().is_file())
-				.map(|e| e.into_path())
-				.collect::<Vec<_>>()
-		})
-		.filter(|p| should_scan(p, &options))
-		.collect();
-
-	candidates
-		.par_iter()
-		.filter_map(|path| {
-			let meta = fs::metadata(path).ok()?;
-			if meta.len() > options.max_file_size_bytes { return None; }
-			let mut file = fs::File::open(path).ok()?;
-			let mut buf = Vec::with_capacity(meta.len() as usize);
-			if file.read_to_end(&mut buf).is_err() { return None; }
-
-			// Attempt to read as text for regex matching. If not UTF-8, fall back to lossy
-			let content = String::from_utf8_lossy(&buf);
-
-			// Signature matches
-			for (name, family, re) in &sigdb.patterns {
-				if re.is_match(&content) {
-					let sha = compute_sha256(&buf);
-					return Some(Detection {
-						path: path.clone(),
-						kind: DetectionKind::Signature { name: name.clone(), family: family.clone() },
-						severity: 8,
-						sha256: Some(sha),
-					});
+/// Path given to detections from in-memory scans, e.g. `mem://invoice.pdf`.
+pub fn virtual_path(name_hint: &str) -> PathBuf {
+	PathBuf::from(format!("mem://{}", name_hint))
+}
+
+/// Scanning engine holding a loaded signature database; cheap to share between threads.
+pub struct Scanner {
+	sigdb: SignatureDb,
+	allowlist: HashSet<String>,
+}
+
+impl Default for Scanner {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Scanner {
+	pub fn new() -> Self {
+		Self { sigdb: SignatureDb::load(), allowlist: HashSet::new() }
+	}
+
+	/// Files whose SHA-256 is on `list` are never reported.
+	pub fn set_allowlist(&mut self, list: &Allowlist) {
+		self.allowlist = list.entries().iter().map(|e| e.sha256.to_lowercase()).collect();
+	}
+
+	/// Built-in signatures plus those from an extra file: signatures.json format, or a ClamAV
+	/// .hdb/.hsb/.ndb/.ldb file (lines that cannot be imported are logged).
+	pub fn with_signature_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+		let path = path.as_ref();
+		let mut scanner = Self::new();
+		if ClamFormat::from_path(path).is_some() {
+			let report = scanner.load_clamav(path)?;
+			for issue in &report.issues {
+				log::warn!("{}:{}: {}", path.display(), issue.line, issue.message);
+			}
+			return Ok(scanner);
+		}
+		let raw = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+		let extra = SignatureDb::from_json(&raw).with_context(|| format!("Invalid signature file {}", path.display()))?;
+		scanner.sigdb.patterns.extend(extra.patterns);
+		Ok(scanner)
+	}
+
+	/// Imports a ClamAV signature file; unsupported lines are listed in the report, not fatal.
+	pub fn load_clamav<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<ImportReport> {
+		let (set, report) = clamav::import_file(path)?;
+		self.sigdb.compiled.extend(set);
+		Ok(report)
+	}
+
+	pub fn signature_count(&self) -> usize {
+		self.sigdb.patterns.len() + self.sigdb.compiled.len()
+	}
+
+	pub fn scan_paths<P: AsRef<Path> + Send + Sync>(&self, paths: &[P], options: &ScanOptions) -> Vec<Detection> {
+		let candidates: Vec<PathBuf> = paths
+			.par_iter()
+			.flat_map_iter(|p| {
+				WalkDir::new(p)
+					.follow_links(false)
+					.into_iter()
+					.filter_entry(|e| !options.exclude_paths.iter().any(|x| e.path() == x))
+					.filter_map(Result::ok)
+					.filter(|e| e.file_type().is_file())
+					.map(|e| e.into_path())
+					.collect::<Vec<_>>()
+			})
+			.filter(|p| should_scan(p, options))
+			.collect();
+
+		candidates
+			.par_iter()
+			.filter_map(|path| {
+				let meta = fs::metadata(path).ok()?;
+				if meta.len() > options.max_file_size_bytes { return None; }
+				let mut file = fs::File::open(path).ok()?;
+				let mut buf = Vec::with_capacity(meta.len() as usize);
+				if file.read_to_end(&mut buf).is_err() { return None; }
+				if mail::looks_like_mail(path, &buf) {
+					return self.scan_mail(&buf, path, options).into_iter().next();
+				}
+				self.scan_buffer(path, &buf, options)
+			})
+			.collect()
+	}
+
+	/// Scans an in-memory buffer; detections carry `virtual_path(name_hint)` as their path.
+	pub fn scan_bytes(&self, bytes: &[u8], name_hint: &str, options: &ScanOptions) -> Vec<Detection> {
+		let path = virtual_path(name_hint);
+		if !should_scan(Path::new(name_hint), options) || bytes.len() as u64 > options.max_file_size_bytes {
+			return Vec::new();
+		}
+		self.scan_buffer(&path, bytes, options).into_iter().collect()
+	}
+
+	/// Buffers at most `max_file_size_bytes` from `reader`; larger streams are skipped like large files.
+	pub fn scan_reader<R: Read>(&self, reader: R, name_hint: &str, options: &ScanOptions) -> io::Result<Vec<Detection>> {
+		let mut buf = Vec::new();
+		reader.take(options.max_file_size_bytes.saturating_add(1)).read_to_end(&mut buf)?;
+		Ok(self.scan_bytes(&buf, name_hint, options))
+	}
+
+	/// Scans every decoded part of a message or mbox; detections name the part in `member`.
+	/// Falls back to the raw message when no part matches.
+	pub fn scan_mail(&self, raw: &[u8], path: &Path, options: &ScanOptions) -> Vec<Detection> {
+		let messages = mail::split_mbox(raw);
+		let mut detections = Vec::new();
+		for (i, msg) in messages.iter().enumerate() {
+			for part in mail::parse_message(msg) {
+				if part.data.len() as u64 > options.max_file_size_bytes { continue; }
+				if let Some(mut d) = self.scan_buffer(path, &part.data, options) {
+					d.member = Some(if messages.len() > 1 { format!("message {}/{}", i + 1, part.name) } else { part.name });
+					detections.push(d);
 				}
 			}
+		}
+		if detections.is_empty() {
+			detections.extend(self.scan_buffer(path, raw, options));
+		}
+		detections
+	}
+
+	fn scan_buffer(&self, path: &Path, buf: &[u8], options: &ScanOptions) -> Option<Detection> {
+		if !self.allowlist.is_empty() && self.allowlist.contains(&compute_sha256(buf)) { return None; }
 
-			// Heuristics
-			if options.enable_heuristics {
-				let mut hs = run_heuristics(path, &content);
-				if !hs.is_empty() { return hs.pop(); }
+		// Attempt to read as text for regex matching. If not UTF-8, fall back to lossy
+		let content = String::from_utf8_lossy(buf);
+
+		// Signature matches
+		for (name, family, re) in &self.sigdb.patterns {
+			if re.is_match(&content) {
+				let sha = compute_sha256(buf);
+				return Some(Detection {
+					path: path.to_path_buf(),
+					kind: DetectionKind::Signature { name: name.clone(), family: family.clone() },
+					severity: 8,
+					sha256: Some(sha),
+					config: extract_config(family, buf),
+					iocs: extract_iocs_from_bytes(buf, &options.ioc_options),
+					member: None,
+				});
 			}
-			None
-		})
-		.collect()
-}
\ No newline at end of file
+		}
+
+		// Compiled binary signatures (ClamAV imports)
+		if let Some(name) = self.sigdb.compiled.match_buffer(buf) {
+			let family = clamav::family_from_name(name);
+			return Some(Detection {
+				path: path.to_path_buf(),
+				kind: DetectionKind::Signature { name: name.to_string(), family: family.clone() },
+				severity: 8,
+				sha256: Some(compute_sha256(buf)),
+				config: extract_config(&family, buf),
+				iocs: extract_iocs_from_bytes(buf, &options.ioc_options),
+				member: None,
+			});
+		}
+
+		// Heuristics
+		if options.enable_heuristics {
+			let mut hs = run_heuristics(path, &content);
+			if let Some(mut d) = hs.pop() {
+				d.iocs = extract_iocs_from_bytes(buf, &options.ioc_options);
+				return Some(d);
+			}
+		}
+		None
+	}
+}
+
+pub fn scan_paths<P: AsRef<Path> + Send + Sync>(paths: &[P], options: ScanOptions) -> Vec<Detection> {
+	Scanner::new().scan_paths(paths, &options)
+}
+
+pub fn scan_bytes(bytes: &[u8], name_hint: &str) -> Vec<Detection> {
+	Scanner::new().scan_bytes(bytes, name_hint, &ScanOptions::default())
+}
+
+pub fn scan_reader<R: Read>(reader: R, name_hint: &str) -> io::Result<Vec<Detection>> {
+	Scanner::new().scan_reader(reader, name_hint, &ScanOptions::default())
+}
@@ -0,0 +1,251 @@
+use md5::Md5;
+use regex::bytes::Regex;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{mail, pe};
+
+// Binary signature model; external formats (ClamAV .hdb/.hsb/.ndb/.ldb) compile into these types.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashKind {
+	Md5,
+	Sha1,
+	Sha256,
+}
+
+impl HashKind {
+	pub fn from_hex_len(len: usize) -> Option<Self> {
+		match len {
+			32 => Some(HashKind::Md5),
+			40 => Some(HashKind::Sha1),
+			64 => Some(HashKind::Sha256),
+			_ => None,
+		}
+	}
+
+	fn digest(self, data: &[u8]) -> String {
+		match self {
+			HashKind::Md5 => format!("{:x}", Md5::digest(data)),
+			HashKind::Sha1 => format!("{:x}", Sha1::digest(data)),
+			HashKind::Sha256 => format!("{:x}", Sha256::digest(data)),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct HashSignature {
+	pub name: String,
+	pub kind: HashKind,
+	/// Lowercase hex digest.
+	pub digest: String,
+	/// `None` matches any size (ClamAV `*`).
+	pub size: Option<u64>,
+}
+
+/// File types a body signature applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetType {
+	Any,
+	Pe,
+	Html,
+	Mail,
+	Elf,
+	Text,
+	MachO,
+	Pdf,
+}
+
+impl TargetType {
+	fn matches(self, data: &[u8]) -> bool {
+		match self {
+			TargetType::Any => true,
+			TargetType::Pe => pe::is_pe(data),
+			TargetType::Elf => data.starts_with(b"\x7fELF"),
+			TargetType::MachO => {
+				[&[0xfe, 0xed, 0xfa, 0xce], &[0xfe, 0xed, 0xfa, 0xcf], &[0xce, 0xfa, 0xed, 0xfe], &[0xcf, 0xfa, 0xed, 0xfe], &[0xca, 0xfe, 0xba, 0xbe]]
+					.iter()
+					.any(|m| data.starts_with(*m))
+			}
+			TargetType::Pdf => data[..data.len().min(1024)].windows(5).any(|w| w == b"%PDF-"),
+			TargetType::Mail => mail::looks_like_mail(Path::new(""), data),
+			TargetType::Text | TargetType::Html => !data[..data.len().min(4096)].contains(&0),
+		}
+	}
+
+	// ClamAV matches these against normalized (lowercased) text; lowercasing is our approximation
+	fn normalized(self) -> bool {
+		matches!(self, TargetType::Text | TargetType::Html)
+	}
+}
+
+/// Where a body pattern may start. `shift` allows the match to begin up to that many bytes later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Offset {
+	Any,
+	Absolute { offset: u64, shift: u64 },
+	FromEof { offset: u64, shift: u64 },
+	EntryPoint { delta: i64, shift: u64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct BodyPattern {
+	pub offset: Offset,
+	regex: Regex,
+}
+
+impl BodyPattern {
+	/// `regex` must already be anchored (`\A`) unless `offset` is `Any`.
+	pub(crate) fn new(offset: Offset, regex: Regex) -> Self {
+		Self { offset, regex }
+	}
+
+	fn count(&self, data: &[u8], ep: Option<usize>) -> usize {
+		let start = match self.offset {
+			Offset::Any => return self.regex.find_iter(data).count(),
+			Offset::Absolute { offset, .. } => Some(offset as usize),
+			Offset::FromEof { offset, .. } => data.len().checked_sub(offset as usize),
+			Offset::EntryPoint { delta, .. } => ep.and_then(|ep| usize::try_from(ep as i64 + delta).ok()),
+		};
+		match start.and_then(|s| data.get(s..)) {
+			Some(slice) if self.regex.is_match(slice) => 1,
+			_ => 0,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct BodySignature {
+	pub name: String,
+	pub target: TargetType,
+	pub pattern: BodyPattern,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountOp {
+	Eq,
+	Gt,
+	Lt,
+}
+
+/// Logical signature expression over subsignature indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogicExpr {
+	Sub(usize),
+	And(Vec<LogicExpr>),
+	Or(Vec<LogicExpr>),
+	/// `expr=n`, `expr>n`, `expr<n`, optionally `,m` = at least m distinct subsignatures.
+	Count { expr: Box<LogicExpr>, op: CountOp, n: usize, distinct: Option<usize> },
+}
+
+impl LogicExpr {
+	/// Returns (truth, total match count, distinct subsignatures matched).
+	fn eval(&self, counts: &[usize]) -> (bool, usize, usize) {
+		match self {
+			LogicExpr::Sub(i) => {
+				let c = counts.get(*i).copied().unwrap_or(0);
+				(c > 0, c, usize::from(c > 0))
+			}
+			LogicExpr::And(items) | LogicExpr::Or(items) => {
+				let evals: Vec<_> = items.iter().map(|e| e.eval(counts)).collect();
+				let truth = if matches!(self, LogicExpr::And(_)) { evals.iter().all(|e| e.0) } else { evals.iter().any(|e| e.0) };
+				(truth, evals.iter().map(|e| e.1).sum(), evals.iter().map(|e| e.2).sum())
+			}
+			LogicExpr::Count { expr, op, n, distinct } => {
+				let (_, count, uniq) = expr.eval(counts);
+				let ok = match op {
+					CountOp::Eq => count == *n,
+					CountOp::Gt => count > *n,
+					CountOp::Lt => count < *n,
+				} && distinct.is_none_or(|d| uniq >= d);
+				(ok, count, uniq)
+			}
+		}
+	}
+
+	pub(crate) fn max_index(&self) -> Option<usize> {
+		match self {
+			LogicExpr::Sub(i) => Some(*i),
+			LogicExpr::And(items) | LogicExpr::Or(items) => items.iter().filter_map(LogicExpr::max_index).max(),
+			LogicExpr::Count { expr, .. } => expr.max_index(),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct LogicalSignature {
+	pub name: String,
+	pub target: TargetType,
+	/// Inclusive file size range.
+	pub file_size: Option<(u64, u64)>,
+	pub subsigs: Vec<BodyPattern>,
+	pub expr: LogicExpr,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SignatureSet {
+	hashes: HashMap<HashKind, HashMap<String, Vec<HashSignature>>>,
+	bodies: Vec<BodySignature>,
+	logical: Vec<LogicalSignature>,
+}
+
+impl SignatureSet {
+	pub fn len(&self) -> usize {
+		self.hashes.values().flat_map(|m| m.values()).map(Vec::len).sum::<usize>() + self.bodies.len() + self.logical.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn add_hash(&mut self, sig: HashSignature) {
+		self.hashes.entry(sig.kind).or_default().entry(sig.digest.clone()).or_default().push(sig);
+	}
+
+	pub fn add_body(&mut self, sig: BodySignature) {
+		self.bodies.push(sig);
+	}
+
+	pub fn add_logical(&mut self, sig: LogicalSignature) {
+		self.logical.push(sig);
+	}
+
+	pub fn extend(&mut self, other: SignatureSet) {
+		for sig in other.hashes.into_values().flat_map(|m| m.into_values()).flatten() {
+			self.add_hash(sig);
+		}
+		self.bodies.extend(other.bodies);
+		self.logical.extend(other.logical);
+	}
+
+	/// Name of the first signature matching `data`.
+	pub fn match_buffer(&self, data: &[u8]) -> Option<&str> {
+		for (kind, by_digest) in &self.hashes {
+			if let Some(sigs) = by_digest.get(&kind.digest(data)) {
+				if let Some(s) = sigs.iter().find(|s| s.size.is_none_or(|size| size == data.len() as u64)) {
+					return Some(&s.name);
+				}
+			}
+		}
+		if self.bodies.is_empty() && self.logical.is_empty() { return None; }
+		let ep = pe::entry_point_offset(data);
+		let needs_lowered = self.bodies.iter().any(|s| s.target.normalized()) || self.logical.iter().any(|s| s.target.normalized());
+		let lowered = if needs_lowered { data.to_ascii_lowercase() } else { Vec::new() };
+		let view = |target: TargetType| if target.normalized() { &lowered[..] } else { data };
+		for sig in &self.bodies {
+			if !sig.target.matches(data) { continue; }
+			if sig.pattern.count(view(sig.target), ep) > 0 { return Some(&sig.name); }
+		}
+		for sig in &self.logical {
+			if let Some((min, max)) = sig.file_size {
+				if !(min..=max).contains(&(data.len() as u64)) { continue; }
+			}
+			if !sig.target.matches(data) { continue; }
+			let counts: Vec<usize> = sig.subsigs.iter().map(|p| p.count(view(sig.target), ep)).collect();
+			if sig.expr.eval(&counts).0 { return Some(&sig.name); }
+		}
+		None
+	}
+}
@@ -8,6 +8,32 @@ pub enum Role {
 	User,
 }
 
+impl Role {
+	fn rank(&self) -> u8 {
+		match self {
+			Role::SuperAdmin => 3,
+			Role::Admin => 2,
+			Role::PowerUser => 1,
+			Role::User => 0,
+		}
+	}
+
+	/// True if this role has at least the privileges of `min`.
+	pub fn at_least(&self, min: &Role) -> bool {
+		self.rank() >= min.rank()
+	}
+
+	/// Exporting live malware samples out of quarantine.
+	pub fn can_export_samples(&self) -> bool {
+		self.at_least(&Role::Admin)
+	}
+
+	/// Restoring a file that still matches a signature as a false positive.
+	pub fn can_approve_false_positives(&self) -> bool {
+		self.at_least(&Role::Admin)
+	}
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserAccount {
 	pub id: i64,
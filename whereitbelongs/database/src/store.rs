@@ -69,7 +69,7 @@ fn decrypt_email(key: &[u8; 32], enc_b64: &str, nonce_b64: &str) -> Result<Strin
 	let nonce = Nonce::from_slice(&nonce_raw);
 	let ct = general_purpose::STANDARD.decode(enc_b64)?;
 	let pt = cipher.decrypt(nonce, ct.as_ref()).map_err(|_| anyhow!("decrypt"))?;
-	Ok(String::from_utf8(pt).map_err(|_| anyhow!("utf8"))?)
+	String::from_utf8(pt).map_err(|_| anyhow!("utf8"))
 }
 
 pub fn create_user_if_missing(conn: &Connection, cfg: &DbConfig, email: &str, password: &str, role: Role) -> Result<()> {
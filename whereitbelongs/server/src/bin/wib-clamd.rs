@@ -0,0 +1,41 @@
+use anyhow::{anyhow, Context, Result};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use wib_server::clamd::{ClamdConfig, ClamdServer, DEFAULT_PORT};
+
+const USAGE: &str = "usage: wib-clamd [--tcp ADDR] [--unix PATH] [--signatures FILE] [--max-stream BYTES]";
+
+fn main() -> Result<()> {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	let mut config = ClamdConfig::default();
+	let mut tcp = None;
+	let mut unix: Option<PathBuf> = None;
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value\n{}", arg, USAGE));
+		match arg.as_str() {
+			"--tcp" => tcp = Some(value()?),
+			"--unix" => unix = Some(PathBuf::from(value()?)),
+			"--signatures" => config.signature_file = Some(PathBuf::from(value()?)),
+			"--max-stream" => config.max_stream_bytes = value()?.parse().context("--max-stream")?,
+			_ => return Err(anyhow!("unknown argument {}\n{}", arg, USAGE)),
+		}
+	}
+	let server = Arc::new(ClamdServer::new(config)?);
+
+	if let Some(path) = unix {
+		#[cfg(unix)]
+		{
+			let _ = std::fs::remove_file(&path);
+			let listener = std::os::unix::net::UnixListener::bind(&path)
+				.with_context(|| format!("bind {}", path.display()))?;
+			return server.serve_unix(listener);
+		}
+		#[cfg(not(unix))]
+		return Err(anyhow!("unix sockets are not supported on this platform: {}", path.display()));
+	}
+	let addr = tcp.unwrap_or_else(|| format!("127.0.0.1:{}", DEFAULT_PORT));
+	let listener = TcpListener::bind(&addr).with_context(|| format!("bind {}", addr))?;
+	server.serve_tcp(listener)
+}
@@ -0,0 +1,28 @@
+use anyhow::{anyhow, Context, Result};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use wib_server::icap::{IcapConfig, IcapServer, DEFAULT_PORT};
+
+const USAGE: &str = "usage: wib-icap [--listen ADDR] [--signatures FILE] [--max-body BYTES] [--preview BYTES] [--block-oversized]";
+
+fn main() -> Result<()> {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	let mut config = IcapConfig::default();
+	let mut listen = format!("127.0.0.1:{}", DEFAULT_PORT);
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value\n{}", arg, USAGE));
+		match arg.as_str() {
+			"--listen" => listen = value()?,
+			"--signatures" => config.signature_file = Some(PathBuf::from(value()?)),
+			"--max-body" => config.max_body_bytes = value()?.parse().context("--max-body")?,
+			"--preview" => config.preview_bytes = value()?.parse().context("--preview")?,
+			"--block-oversized" => config.block_oversized = true,
+			_ => return Err(anyhow!("unknown argument {}\n{}", arg, USAGE)),
+		}
+	}
+	let server = Arc::new(IcapServer::new(config)?);
+	let listener = TcpListener::bind(&listen).with_context(|| format!("bind {}", listen))?;
+	server.serve_tcp(listener)
+}
@@ -0,0 +1,48 @@
+use anyhow::{anyhow, Context, Result};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use wib_server::milter::{MilterAction, MilterConfig, MilterServer, DEFAULT_PORT};
+
+const USAGE: &str = "usage: wib-milter [--listen ADDR] [--unix PATH] [--signatures FILE] [--action reject|quarantine|tag] [--max-message BYTES]";
+
+fn main() -> Result<()> {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	let mut config = MilterConfig::default();
+	let mut listen = format!("127.0.0.1:{}", DEFAULT_PORT);
+	let mut unix: Option<PathBuf> = None;
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value\n{}", arg, USAGE));
+		match arg.as_str() {
+			"--listen" => listen = value()?,
+			"--unix" => unix = Some(PathBuf::from(value()?)),
+			"--signatures" => config.signature_file = Some(PathBuf::from(value()?)),
+			"--max-message" => config.max_message_bytes = value()?.parse().context("--max-message")?,
+			"--action" => {
+				config.action = match value()?.as_str() {
+					"reject" => MilterAction::Reject,
+					"quarantine" => MilterAction::Quarantine,
+					"tag" => MilterAction::Tag,
+					other => return Err(anyhow!("unknown action {}\n{}", other, USAGE)),
+				}
+			}
+			_ => return Err(anyhow!("unknown argument {}\n{}", arg, USAGE)),
+		}
+	}
+	let server = Arc::new(MilterServer::new(config)?);
+
+	if let Some(path) = unix {
+		#[cfg(unix)]
+		{
+			let _ = std::fs::remove_file(&path);
+			let listener = std::os::unix::net::UnixListener::bind(&path)
+				.with_context(|| format!("bind {}", path.display()))?;
+			return server.serve_unix(listener);
+		}
+		#[cfg(not(unix))]
+		return Err(anyhow!("unix sockets are not supported on this platform: {}", path.display()));
+	}
+	let listener = TcpListener::bind(&listen).with_context(|| format!("bind {}", listen))?;
+	server.serve_tcp(listener)
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use wib_core::{Detection, DetectionKind, ScanOptions, Scanner};
+
+use crate::{accept_failed, load_scanner};
+
+pub const DEFAULT_PORT: u16 = 3310;
+const MAX_COMMAND_LEN: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub struct ClamdConfig {
+	/// Extra signatures re-read on every RELOAD: signatures.json format, or a ClamAV .hdb/.hsb/.ndb/.ldb file.
+	pub signature_file: Option<PathBuf>,
+	/// Same meaning as clamd's StreamMaxLength.
+	pub max_stream_bytes: u64,
+	pub scan_options: ScanOptions,
+}
+
+impl Default for ClamdConfig {
+	fn default() -> Self {
+		Self {
+			signature_file: None,
+			max_stream_bytes: 25 * 1024 * 1024, // 25 MiB, clamd's default
+			scan_options: ScanOptions::default(),
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+enum Delimiter {
+	Null,
+	Newline,
+}
+
+impl Delimiter {
+	fn byte(self) -> u8 {
+		match self {
+			Delimiter::Null => b'\0',
+			Delimiter::Newline => b'\n',
+		}
+	}
+}
+
+/// clamd wire protocol on top of a shared [`Scanner`].
+pub struct ClamdServer {
+	config: ClamdConfig,
+	scanner: RwLock<Arc<Scanner>>,
+}
+
+pub fn detection_name(d: &Detection) -> String {
+	match &d.kind {
+		DetectionKind::Signature { name, .. } => name.clone(),
+		DetectionKind::Heuristic { .. } => "WIB.Heuristic.Suspicious".to_string(),
+	}
+}
+
+impl ClamdServer {
+	pub fn new(config: ClamdConfig) -> Result<Self> {
+		let scanner = load_scanner(config.signature_file.as_deref())?;
+		Ok(Self { config, scanner: RwLock::new(Arc::new(scanner)) })
+	}
+
+	fn scanner(&self) -> Arc<Scanner> {
+		self.scanner.read().expect("scanner lock poisoned").clone()
+	}
+
+	/// Reloads signatures; in-flight scans keep using the previous scanner.
+	pub fn reload(&self) -> Result<()> {
+		let scanner = load_scanner(self.config.signature_file.as_deref())?;
+		*self.scanner.write().expect("scanner lock poisoned") = Arc::new(scanner);
+		Ok(())
+	}
+
+	pub fn serve_tcp(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+		log::info!("clamd listening on {}", listener.local_addr()?);
+		for stream in listener.incoming() {
+			let stream = match stream {
+				Ok(s) => s,
+				Err(e) => { accept_failed("clamd", e); continue; }
+			};
+			let server = self.clone();
+			thread::spawn(move || {
+				if let Err(e) = server.handle(stream) { log::warn!("clamd connection error: {}", e); }
+			});
+		}
+		Ok(())
+	}
+
+	#[cfg(unix)]
+	pub fn serve_unix(self: Arc<Self>, listener: std::os::unix::net::UnixListener) -> Result<()> {
+		log::info!("clamd listening on {:?}", listener.local_addr()?);
+		for stream in listener.incoming() {
+			let stream = match stream {
+				Ok(s) => s,
+				Err(e) => { accept_failed("clamd", e); continue; }
+			};
+			let server = self.clone();
+			thread::spawn(move || {
+				if let Err(e) = server.handle(stream) { log::warn!("clamd connection error: {}", e); }
+			});
+		}
+		Ok(())
+	}
+
+	/// Serves a single command on `stream`, as clamd does outside of IDSESSION.
+	pub fn handle<S: Read + Write>(&self, mut stream: S) -> io::Result<()> {
+		let Some((line, delim)) = read_command(&mut stream)? else { return Ok(()) };
+		let (cmd, arg) = match line.split_once(' ') {
+			Some((c, a)) => (c.to_string(), a.to_string()),
+			None => (line.clone(), String::new()),
+		};
+		let replies = match cmd.as_str() {
+			"PING" => vec!["PONG".to_string()],
+			"VERSION" => vec![format!("WIB {}/{}", env!("CARGO_PKG_VERSION"), self.scanner().signature_count())],
+			"RELOAD" => {
+				if let Err(e) = self.reload() { log::error!("clamd reload failed: {:#}", e); }
+				vec!["RELOADING".to_string()]
+			}
+			"SCAN" => self.scan_path(&arg, true),
+			"CONTSCAN" | "MULTISCAN" => self.scan_path(&arg, false),
+			"INSTREAM" => vec![self.instream(&mut stream)?],
+			_ => vec!["UNKNOWN COMMAND".to_string()],
+		};
+		log::info!("clamd {} {} -> {}", cmd, arg, replies.join(" | "));
+		for r in replies {
+			stream.write_all(r.as_bytes())?;
+			stream.write_all(&[delim.byte()])?;
+		}
+		stream.flush()
+	}
+
+	fn scan_path(&self, arg: &str, stop_on_first: bool) -> Vec<String> {
+		let path = Path::new(arg);
+		if arg.is_empty() || !path.exists() {
+			return vec![format!("{}: lstat() failed: No such file or directory. ERROR", arg)];
+		}
+		let mut detections = self.scanner().scan_paths(&[path], &self.config.scan_options);
+		detections.sort_by(|a, b| a.path.cmp(&b.path));
+		if stop_on_first { detections.truncate(1); }
+		if detections.is_empty() {
+			return vec![format!("{}: OK", arg)];
+		}
+		detections.iter().map(|d| format!("{}: {} FOUND", d.path.display(), detection_name(d))).collect()
+	}
+
+	// INSTREAM payload: <u32 big-endian length><data> chunks terminated by a zero-length chunk
+	fn instream<S: Read>(&self, stream: &mut S) -> io::Result<String> {
+		let mut buf = Vec::new();
+		loop {
+			let mut len = [0u8; 4];
+			stream.read_exact(&mut len)?;
+			let len = u32::from_be_bytes(len) as u64;
+			if len == 0 { break; }
+			if buf.len() as u64 + len > self.config.max_stream_bytes {
+				return Ok("INSTREAM size limit exceeded. ERROR".to_string());
+			}
+			let start = buf.len();
+			buf.resize(start + len as usize, 0);
+			stream.read_exact(&mut buf[start..])?;
+		}
+		let mut opts = self.config.scan_options.clone();
+		opts.max_file_size_bytes = opts.max_file_size_bytes.max(self.config.max_stream_bytes);
+		Ok(match self.scanner().scan_bytes(&buf, "stream", &opts).first() {
+			Some(d) => format!("stream: {} FOUND", detection_name(d)),
+			None => "stream: OK".to_string(),
+		})
+	}
+}
+
+// Commands are `z<CMD>\0`, `n<CMD>\n` or the legacy bare `<CMD>\n`; replies use the same terminator.
+fn read_command<S: Read>(stream: &mut S) -> io::Result<Option<(String, Delimiter)>> {
+	let mut byte = [0u8; 1];
+	if stream.read(&mut byte)? == 0 { return Ok(None); }
+	let (delim, mut line) = match byte[0] {
+		b'z' => (Delimiter::Null, Vec::new()),
+		b'n' => (Delimiter::Newline, Vec::new()),
+		b => (Delimiter::Newline, vec![b]),
+	};
+	loop {
+		if stream.read(&mut byte)? == 0 || byte[0] == delim.byte() { break; }
+		line.push(byte[0]);
+		if line.len() > MAX_COMMAND_LEN {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "command too long"));
+		}
+	}
+	let line = String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string();
+	Ok(Some((line, delim)))
+}
@@ -0,0 +1,393 @@
+use anyhow::Result;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use wib_core::{ScanOptions, Scanner};
+
+use crate::clamd::detection_name;
+use crate::{accept_failed, load_scanner};
+
+pub const DEFAULT_PORT: u16 = 1344;
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct IcapConfig {
+	pub signature_file: Option<PathBuf>,
+	/// Bodies larger than this are not scanned.
+	pub max_body_bytes: u64,
+	/// Block (403) instead of passing through bodies over `max_body_bytes`.
+	pub block_oversized: bool,
+	/// Preview size advertised in OPTIONS.
+	pub preview_bytes: usize,
+	pub scan_options: ScanOptions,
+}
+
+impl Default for IcapConfig {
+	fn default() -> Self {
+		Self {
+			signature_file: None,
+			max_body_bytes: 32 * 1024 * 1024, // 32 MiB
+			block_oversized: false,
+			preview_bytes: 4096,
+			scan_options: ScanOptions::default(),
+		}
+	}
+}
+
+struct IcapRequest {
+	method: String,
+	uri: String,
+	headers: Vec<(String, String)>,
+}
+
+impl IcapRequest {
+	fn header(&self, name: &str) -> Option<&str> {
+		self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+	}
+
+	fn allows_204(&self) -> bool {
+		self.header("Allow").is_some_and(|v| v.split(',').any(|a| a.trim() == "204"))
+	}
+}
+
+/// Encapsulated message: raw HTTP header blocks in wire order plus the de-chunked body.
+struct Encapsulated {
+	sections: Vec<(String, Vec<u8>)>,
+	body_kind: Option<String>,
+	body: Vec<u8>,
+	body_len: u64,
+	/// Set once the body outgrew `max_body_bytes`: bytes of the current chunk still unread.
+	/// Buffering stops there and the rest is forwarded or drained straight from the connection.
+	pending: Option<u64>,
+}
+
+/// How a run of chunks ended.
+enum ChunksEnd {
+	/// The zero chunk; whether it carried the `ieof` extension.
+	Last { ieof: bool },
+	/// The buffer limit was reached; the next `pending` bytes are the unread current chunk.
+	Overflow { pending: u64 },
+}
+
+impl Encapsulated {
+	fn section(&self, name: &str) -> Option<&[u8]> {
+		self.sections.iter().find(|(n, _)| n == name).map(|(_, b)| b.as_slice())
+	}
+
+	/// Requested URL from the encapsulated HTTP request line, if any.
+	fn url(&self) -> Option<String> {
+		let hdr = self.section("req-hdr")?;
+		let line = String::from_utf8_lossy(hdr).lines().next()?.to_string();
+		line.split_whitespace().nth(1).map(str::to_string)
+	}
+}
+
+enum Verdict {
+	Clean,
+	Infected(String),
+	Oversized,
+}
+
+/// ICAP (RFC 3507) REQMOD/RESPMOD service backed by wib-core.
+pub struct IcapServer {
+	config: IcapConfig,
+	scanner: Arc<Scanner>,
+}
+
+impl IcapServer {
+	pub fn new(config: IcapConfig) -> Result<Self> {
+		let scanner = load_scanner(config.signature_file.as_deref())?;
+		Ok(Self { config, scanner: Arc::new(scanner) })
+	}
+
+	fn istag(&self) -> String {
+		format!("\"WIB-{}-{}\"", env!("CARGO_PKG_VERSION"), self.scanner.signature_count())
+	}
+
+	pub fn serve_tcp(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+		log::info!("icap listening on {}", listener.local_addr()?);
+		for stream in listener.incoming() {
+			let stream = match stream {
+				Ok(s) => s,
+				Err(e) => { accept_failed("icap", e); continue; }
+			};
+			let server = self.clone();
+			thread::spawn(move || {
+				if let Err(e) = server.handle_tcp(stream) { log::warn!("icap connection error: {}", e); }
+			});
+		}
+		Ok(())
+	}
+
+	fn handle_tcp(&self, stream: TcpStream) -> io::Result<()> {
+		let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+		let reader = BufReader::new(stream.try_clone()?);
+		self.handle(reader, stream, &peer)
+	}
+
+	/// Serves ICAP requests on a persistent connection until the client closes it.
+	pub fn handle<R: BufRead, W: Write>(&self, mut reader: R, mut writer: W, peer: &str) -> io::Result<()> {
+		while let Some(req) = read_request(&mut reader)? {
+			let started = Instant::now();
+			if req.method == "OPTIONS" {
+				self.write_options(&mut writer, &req)?;
+				log::info!("icap OPTIONS {} client={}", req.uri, peer);
+				continue;
+			}
+			if req.method != "REQMOD" && req.method != "RESPMOD" {
+				write!(writer, "ICAP/1.0 405 Method Not Allowed\r\nISTag: {}\r\nEncapsulated: null-body=0\r\n\r\n", self.istag())?;
+				writer.flush()?;
+				continue;
+			}
+			let mut msg = self.read_encapsulated(&mut reader, &mut writer, &req)?;
+			let url = msg.url().unwrap_or_else(|| "-".to_string());
+			let verdict = self.scan(&msg, &url);
+			let blocked = match verdict {
+				Verdict::Infected(_) => true,
+				Verdict::Oversized => self.config.block_oversized,
+				Verdict::Clean => false,
+			};
+			let echo = !blocked && !req.allows_204();
+			// the unread remainder of an oversized body is either streamed back or discarded
+			if let Some(pending) = msg.pending.filter(|_| !echo) { msg.body_len += forward_chunks(&mut reader, &mut io::sink(), pending)?; }
+			match &verdict {
+				Verdict::Infected(name) => self.write_block_page(&mut writer, &url, name)?,
+				Verdict::Oversized if self.config.block_oversized => self.write_block_page(&mut writer, &url, "Oversized content")?,
+				_ if req.allows_204() => {
+					write!(writer, "ICAP/1.0 204 No Content\r\nISTag: {}\r\nEncapsulated: null-body=0\r\n\r\n", self.istag())?;
+				}
+				_ => self.write_unmodified(&mut reader, &mut writer, &mut msg)?,
+			}
+			writer.flush()?;
+			let result = match &verdict {
+				Verdict::Clean => "clean".to_string(),
+				Verdict::Infected(name) => format!("blocked:{}", name),
+				Verdict::Oversized if self.config.block_oversized => "blocked:oversized".to_string(),
+				Verdict::Oversized => "passed:oversized".to_string(),
+			};
+			log::info!(
+				"icap {} url={} client={} http-client={} bytes={} result={} elapsed_ms={}",
+				req.method,
+				url,
+				peer,
+				req.header("X-Client-IP").unwrap_or("-"),
+				msg.body_len,
+				result,
+				started.elapsed().as_millis()
+			);
+		}
+		Ok(())
+	}
+
+	fn write_options<W: Write>(&self, w: &mut W, req: &IcapRequest) -> io::Result<()> {
+		let method = if req.uri.to_lowercase().contains("reqmod") { "REQMOD" } else { "RESPMOD" };
+		write!(
+			w,
+			"ICAP/1.0 200 OK\r\nMethods: {}\r\nService: WIB ICAP {}\r\nISTag: {}\r\nMax-Connections: 100\r\n\
+			Options-TTL: 3600\r\nAllow: 204\r\nPreview: {}\r\nTransfer-Preview: *\r\nEncapsulated: null-body=0\r\n\r\n",
+			method,
+			env!("CARGO_PKG_VERSION"),
+			self.istag(),
+			self.config.preview_bytes
+		)?;
+		w.flush()
+	}
+
+	fn read_encapsulated<R: BufRead, W: Write>(&self, r: &mut R, w: &mut W, req: &IcapRequest) -> io::Result<Encapsulated> {
+		let mut offsets: Vec<(String, usize)> = req
+			.header("Encapsulated")
+			.unwrap_or("null-body=0")
+			.split(',')
+			.filter_map(|part| {
+				let (name, off) = part.trim().split_once('=')?;
+				Some((name.trim().to_string(), off.trim().parse().ok()?))
+			})
+			.collect();
+		offsets.sort_by_key(|(_, off)| *off);
+		let mut msg = Encapsulated { sections: Vec::new(), body_kind: None, body: Vec::new(), body_len: 0, pending: None };
+		for (i, (name, off)) in offsets.iter().enumerate() {
+			if name.ends_with("-hdr") {
+				let end = offsets.get(i + 1).map(|(_, o)| *o).unwrap_or(*off);
+				if end < *off || end - off > MAX_HEADER_BYTES {
+					return Err(io::Error::new(io::ErrorKind::InvalidData, "bad Encapsulated header"));
+				}
+				let mut buf = vec![0u8; end - off];
+				r.read_exact(&mut buf)?;
+				msg.sections.push((name.clone(), buf));
+			} else if name != "null-body" {
+				msg.body_kind = Some(name.clone());
+			}
+		}
+		if msg.body_kind.is_none() { return Ok(msg); }
+
+		let limit = self.config.max_body_bytes;
+		if req.header("Preview").is_some() {
+			// the preview is bounded by what OPTIONS advertised, so it is always buffered whole
+			match read_chunks(r, &mut msg, limit.max(self.config.preview_bytes as u64))? {
+				ChunksEnd::Overflow { .. } => return Err(io::Error::new(io::ErrorKind::InvalidData, "preview larger than advertised")),
+				ChunksEnd::Last { ieof: true } => return Ok(msg),
+				ChunksEnd::Last { ieof: false } => {
+					w.write_all(b"ICAP/1.0 100 Continue\r\n\r\n")?;
+					w.flush()?;
+				}
+			}
+		}
+		if let ChunksEnd::Overflow { pending } = read_chunks(r, &mut msg, limit)? { msg.pending = Some(pending); }
+		Ok(msg)
+	}
+
+	fn scan(&self, msg: &Encapsulated, url: &str) -> Verdict {
+		if msg.pending.is_some() || msg.body_len > self.config.max_body_bytes { return Verdict::Oversized; }
+		if msg.body.is_empty() { return Verdict::Clean; }
+		let name = url.split(['?', '#']).next().unwrap_or_default().rsplit('/').next().filter(|n| !n.is_empty()).unwrap_or("body");
+		let mut opts = self.config.scan_options.clone();
+		opts.include_extensions = None;
+		opts.max_file_size_bytes = opts.max_file_size_bytes.max(self.config.max_body_bytes);
+		match self.scanner.scan_bytes(&msg.body, name, &opts).first() {
+			Some(d) => Verdict::Infected(detection_name(d)),
+			None => Verdict::Clean,
+		}
+	}
+
+	fn write_block_page<W: Write>(&self, w: &mut W, url: &str, reason: &str) -> io::Result<()> {
+		let html = format!(
+			"<html><head><title>Blocked by WhereItBelongs</title></head><body><h1>Download blocked</h1>\
+			<p>The content at <code>{}</code> was blocked: <b>{}</b>.</p></body></html>",
+			html_escape(url),
+			html_escape(reason)
+		);
+		let http = format!(
+			"HTTP/1.1 403 Forbidden\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\n\r\n",
+			html.len()
+		);
+		write!(
+			w,
+			"ICAP/1.0 200 OK\r\nISTag: {}\r\nX-Infection-Found: Type=0; Resolution=2; Threat={};\r\nEncapsulated: res-hdr=0, res-body={}\r\n\r\n",
+			self.istag(),
+			reason,
+			http.len()
+		)?;
+		w.write_all(http.as_bytes())?;
+		write_chunked(w, html.as_bytes())
+	}
+
+	// Clients that did not send `Allow: 204` expect the message back verbatim; an oversized body's
+	// unread remainder is streamed through from `r`
+	fn write_unmodified<R: BufRead, W: Write>(&self, r: &mut R, w: &mut W, msg: &mut Encapsulated) -> io::Result<()> {
+		let mut encapsulated = Vec::new();
+		let mut off = 0;
+		for (name, buf) in &msg.sections {
+			encapsulated.push(format!("{}={}", name, off));
+			off += buf.len();
+		}
+		encapsulated.push(format!("{}={}", msg.body_kind.as_deref().unwrap_or("null-body"), off));
+		write!(w, "ICAP/1.0 200 OK\r\nISTag: {}\r\nEncapsulated: {}\r\n\r\n", self.istag(), encapsulated.join(", "))?;
+		for (_, buf) in &msg.sections {
+			w.write_all(buf)?;
+		}
+		if msg.body_kind.is_none() { return Ok(()); }
+		write_chunks(w, &msg.body)?;
+		if let Some(pending) = msg.pending.take() { msg.body_len += forward_chunks(r, w, pending)?; }
+		w.write_all(b"0\r\n\r\n")
+	}
+}
+
+fn html_escape(s: &str) -> String {
+	s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn read_line<R: BufRead>(r: &mut R) -> io::Result<Option<String>> {
+	let mut buf = Vec::new();
+	if r.by_ref().take(MAX_HEADER_BYTES as u64).read_until(b'\n', &mut buf)? == 0 { return Ok(None); }
+	Ok(Some(String::from_utf8_lossy(&buf).trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn read_request<R: BufRead>(r: &mut R) -> io::Result<Option<IcapRequest>> {
+	let mut line = String::new();
+	while line.is_empty() {
+		match read_line(r)? {
+			Some(l) => line = l,
+			None => return Ok(None),
+		}
+	}
+	let mut parts = line.split_whitespace();
+	let method = parts.next().unwrap_or_default().to_string();
+	let uri = parts.next().unwrap_or_default().to_string();
+	let mut headers = Vec::new();
+	while let Some(l) = read_line(r)? {
+		if l.is_empty() { break; }
+		if let Some((k, v)) = l.split_once(':') {
+			headers.push((k.trim().to_string(), v.trim().to_string()));
+		}
+	}
+	Ok(Some(IcapRequest { method, uri, headers }))
+}
+
+/// Reads chunks up to the zero chunk, or until the next one would take the buffered body past
+/// `limit`; once that happens nothing more is buffered.
+fn read_chunks<R: BufRead>(r: &mut R, msg: &mut Encapsulated, limit: u64) -> io::Result<ChunksEnd> {
+	loop {
+		let (size, ext) = read_chunk_size(r)?;
+		if size == 0 {
+			skip_trailers(r)?;
+			return Ok(ChunksEnd::Last { ieof: ext == "ieof" });
+		}
+		if msg.body.len() as u64 + size > limit { return Ok(ChunksEnd::Overflow { pending: size }); }
+		msg.body_len += size;
+		let start = msg.body.len();
+		msg.body.resize(start + size as usize, 0);
+		r.read_exact(&mut msg.body[start..])?;
+		read_line(r)?;
+	}
+}
+
+/// Copies the rest of a chunked body to `w` as chunks, starting with `pending` bytes of the
+/// current chunk; stops before the zero chunk, which it consumes. Returns the bytes copied.
+fn forward_chunks<R: BufRead, W: Write>(r: &mut R, w: &mut W, pending: u64) -> io::Result<u64> {
+	let mut size = pending;
+	let mut total = 0;
+	while size > 0 {
+		write!(w, "{:x}\r\n", size)?;
+		if io::copy(&mut r.by_ref().take(size), w)? < size { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated chunk")); }
+		w.write_all(b"\r\n")?;
+		read_line(r)?;
+		total += size;
+		size = read_chunk_size(r)?.0;
+	}
+	skip_trailers(r)?;
+	Ok(total)
+}
+
+fn read_chunk_size<R: BufRead>(r: &mut R) -> io::Result<(u64, String)> {
+	let line = read_line(r)?.ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated chunk"))?;
+	let (size, ext) = match line.split_once(';') {
+		Some((s, e)) => (s.trim(), e.trim()),
+		None => (line.trim(), ""),
+	};
+	let size = u64::from_str_radix(size, 16).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+	Ok((size, ext.to_string()))
+}
+
+fn skip_trailers<R: BufRead>(r: &mut R) -> io::Result<()> {
+	while let Some(l) = read_line(r)? {
+		if l.is_empty() { break; }
+	}
+	Ok(())
+}
+
+fn write_chunks<W: Write>(w: &mut W, body: &[u8]) -> io::Result<()> {
+	for chunk in body.chunks(64 * 1024) {
+		write!(w, "{:x}\r\n", chunk.len())?;
+		w.write_all(chunk)?;
+		w.write_all(b"\r\n")?;
+	}
+	Ok(())
+}
+
+fn write_chunked<W: Write>(w: &mut W, body: &[u8]) -> io::Result<()> {
+	write_chunks(w, body)?;
+	w.write_all(b"0\r\n\r\n")
+}
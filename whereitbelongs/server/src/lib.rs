@@ -0,0 +1,26 @@
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+use wib_core::Scanner;
+
+pub mod clamd;
+pub mod icap;
+pub mod milter;
+
+pub use clamd::{ClamdConfig, ClamdServer};
+pub use icap::{IcapConfig, IcapServer};
+pub use milter::{MilterAction, MilterConfig, MilterServer};
+
+pub(crate) fn load_scanner(signature_file: Option<&Path>) -> Result<Scanner> {
+	match signature_file {
+		Some(path) => Scanner::with_signature_file(path),
+		None => Ok(Scanner::new()),
+	}
+}
+
+/// Logs a failed accept and pauses briefly; running out of descriptors or an aborted handshake
+/// must not take the listener down.
+pub(crate) fn accept_failed(service: &str, e: std::io::Error) {
+	log::warn!("{} accept failed: {}", service, e);
+	std::thread::sleep(Duration::from_millis(100));
+}
@@ -0,0 +1,247 @@
+use anyhow::Result;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use wib_core::{Detection, ScanOptions, Scanner};
+
+use crate::clamd::detection_name;
+use crate::{accept_failed, load_scanner};
+
+pub const DEFAULT_PORT: u16 = 8894;
+pub const RESULT_HEADER: &str = "X-WIB-Result";
+const MILTER_VERSION: u32 = 6;
+const MAX_PACKET: usize = 1024 * 1024;
+
+// SMFIF_* actions we may take
+const SMFIF_ADDHDRS: u32 = 0x01;
+const SMFIF_QUARANTINE: u32 = 0x20;
+// SMFIP_* steps the MTA may skip for us
+const SMFIP_NOCONNECT: u32 = 0x01;
+const SMFIP_NOHELO: u32 = 0x02;
+const SMFIP_NORCPT: u32 = 0x08;
+const SMFIP_NOUNKNOWN: u32 = 0x100;
+const SMFIP_NODATA: u32 = 0x200;
+
+/// What to do with a message carrying a detection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MilterAction {
+	/// 550 the message at end of DATA.
+	Reject,
+	/// Accept but put the message into the MTA's hold/quarantine queue.
+	Quarantine,
+	/// Accept and only add the result header.
+	Tag,
+}
+
+#[derive(Debug, Clone)]
+pub struct MilterConfig {
+	pub signature_file: Option<PathBuf>,
+	pub action: MilterAction,
+	/// Messages larger than this are accepted unscanned and tagged as such.
+	pub max_message_bytes: usize,
+	pub scan_options: ScanOptions,
+}
+
+impl Default for MilterConfig {
+	fn default() -> Self {
+		Self {
+			signature_file: None,
+			action: MilterAction::Reject,
+			max_message_bytes: 50 * 1024 * 1024, // 50 MiB
+			scan_options: ScanOptions::default(),
+		}
+	}
+}
+
+#[derive(Default)]
+struct MessageState {
+	queue_id: String,
+	sender: String,
+	raw: Vec<u8>,
+	oversized: bool,
+}
+
+impl MessageState {
+	fn append(&mut self, data: &[u8], limit: usize) {
+		if self.raw.len() + data.len() > limit {
+			self.oversized = true;
+		} else {
+			self.raw.extend_from_slice(data);
+		}
+	}
+}
+
+/// Sendmail/Postfix milter (protocol v6) that scans message attachments.
+pub struct MilterServer {
+	config: MilterConfig,
+	scanner: Arc<Scanner>,
+}
+
+fn cstrings(data: &[u8]) -> Vec<String> {
+	data.split(|b| *b == 0).map(|s| String::from_utf8_lossy(s).to_string()).collect()
+}
+
+fn write_packet<W: Write>(w: &mut W, cmd: u8, data: &[u8]) -> io::Result<()> {
+	w.write_all(&((data.len() + 1) as u32).to_be_bytes())?;
+	w.write_all(&[cmd])?;
+	w.write_all(data)?;
+	w.flush()
+}
+
+fn read_packet<R: Read>(r: &mut R) -> io::Result<Option<(u8, Vec<u8>)>> {
+	let mut len = [0u8; 4];
+	match r.read_exact(&mut len) {
+		Ok(()) => {}
+		Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+		Err(e) => return Err(e),
+	}
+	let len = u32::from_be_bytes(len) as usize;
+	if len == 0 || len > MAX_PACKET {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "bad milter packet length"));
+	}
+	let mut buf = vec![0u8; len];
+	r.read_exact(&mut buf)?;
+	Ok(Some((buf[0], buf[1..].to_vec())))
+}
+
+fn header_value(d: &Detection) -> String {
+	match &d.member {
+		Some(m) => format!("infected; threat={}; attachment=\"{}\"", detection_name(d), m.replace('"', "'")),
+		None => format!("infected; threat={}", detection_name(d)),
+	}
+}
+
+impl MilterServer {
+	pub fn new(config: MilterConfig) -> Result<Self> {
+		let scanner = load_scanner(config.signature_file.as_deref())?;
+		Ok(Self { config, scanner: Arc::new(scanner) })
+	}
+
+	pub fn serve_tcp(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+		log::info!("milter listening on {}", listener.local_addr()?);
+		for stream in listener.incoming() {
+			let stream = match stream {
+				Ok(s) => s,
+				Err(e) => { accept_failed("milter", e); continue; }
+			};
+			let server = self.clone();
+			thread::spawn(move || {
+				if let Err(e) = server.handle(stream) { log::warn!("milter connection error: {}", e); }
+			});
+		}
+		Ok(())
+	}
+
+	#[cfg(unix)]
+	pub fn serve_unix(self: Arc<Self>, listener: std::os::unix::net::UnixListener) -> Result<()> {
+		log::info!("milter listening on {:?}", listener.local_addr()?);
+		for stream in listener.incoming() {
+			let stream = match stream {
+				Ok(s) => s,
+				Err(e) => { accept_failed("milter", e); continue; }
+			};
+			let server = self.clone();
+			thread::spawn(move || {
+				if let Err(e) = server.handle(stream) { log::warn!("milter connection error: {}", e); }
+			});
+		}
+		Ok(())
+	}
+
+	pub fn handle<S: Read + Write>(&self, mut stream: S) -> io::Result<()> {
+		let mut msg = MessageState::default();
+		let limit = self.config.max_message_bytes;
+		while let Some((cmd, data)) = read_packet(&mut stream)? {
+			match cmd {
+				b'O' => {
+					let protocol = data.get(8..12).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])).unwrap_or(0);
+					let skip = protocol & (SMFIP_NOCONNECT | SMFIP_NOHELO | SMFIP_NORCPT | SMFIP_NOUNKNOWN | SMFIP_NODATA);
+					let mut reply = Vec::with_capacity(12);
+					reply.extend(MILTER_VERSION.to_be_bytes());
+					reply.extend((SMFIF_ADDHDRS | SMFIF_QUARANTINE).to_be_bytes());
+					reply.extend(skip.to_be_bytes());
+					write_packet(&mut stream, b'O', &reply)?;
+				}
+				b'D' => {
+					// macros: remember the queue id for logging
+					let fields = cstrings(data.get(1..).unwrap_or_default());
+					for pair in fields.chunks(2) {
+						if pair[0] == "i" || pair[0] == "{i}" {
+							if let Some(v) = pair.get(1) { msg.queue_id = v.clone(); }
+						}
+					}
+				}
+				b'M' => {
+					msg.sender = cstrings(&data).into_iter().next().unwrap_or_default();
+					write_packet(&mut stream, b'c', &[])?;
+				}
+				b'L' => {
+					let fields = cstrings(&data);
+					let line = format!("{}: {}\r\n", fields.first().map(String::as_str).unwrap_or_default(), fields.get(1).map(String::as_str).unwrap_or_default());
+					msg.append(line.as_bytes(), limit);
+					write_packet(&mut stream, b'c', &[])?;
+				}
+				b'N' => {
+					msg.append(b"\r\n", limit);
+					write_packet(&mut stream, b'c', &[])?;
+				}
+				b'B' => {
+					msg.append(&data, limit);
+					write_packet(&mut stream, b'c', &[])?;
+				}
+				b'E' => {
+					// end-of-body may carry the last body chunk
+					msg.append(&data, limit);
+					self.end_of_message(&mut stream, &msg)?;
+					msg = MessageState::default();
+				}
+				b'A' | b'K' => msg = MessageState::default(),
+				b'Q' => break,
+				// connect, helo, rcpt, data, unknown and anything newer
+				_ => write_packet(&mut stream, b'c', &[])?,
+			}
+		}
+		Ok(())
+	}
+
+	fn end_of_message<W: Write>(&self, w: &mut W, msg: &MessageState) -> io::Result<()> {
+		let id = if msg.queue_id.is_empty() { "-" } else { msg.queue_id.as_str() };
+		if msg.oversized {
+			log::info!("milter {} from=<{}> result=unscanned:oversized", id, msg.sender);
+			write_packet(w, b'h', format!("{}\0unscanned; reason=oversized\0", RESULT_HEADER).as_bytes())?;
+			return write_packet(w, b'a', &[]);
+		}
+		let detections = self.scanner.scan_mail(&msg.raw, Path::new("message.eml"), &self.config.scan_options);
+		let Some(first) = detections.first() else {
+			log::info!("milter {} from=<{}> bytes={} result=clean", id, msg.sender, msg.raw.len());
+			write_packet(w, b'h', format!("{}\0clean\0", RESULT_HEADER).as_bytes())?;
+			return write_packet(w, b'a', &[]);
+		};
+		let value = header_value(first);
+		log::info!(
+			"milter {} from=<{}> bytes={} result={} action={:?}",
+			id,
+			msg.sender,
+			msg.raw.len(),
+			value,
+			self.config.action
+		);
+		match self.config.action {
+			MilterAction::Reject => {
+				let reply = format!("550 5.7.1 Message rejected: {} detected\0", detection_name(first));
+				write_packet(w, b'y', reply.as_bytes())
+			}
+			MilterAction::Quarantine => {
+				write_packet(w, b'h', format!("{}\0{}\0", RESULT_HEADER, value).as_bytes())?;
+				write_packet(w, b'q', format!("WIB: {}\0", detection_name(first)).as_bytes())?;
+				write_packet(w, b'a', &[])
+			}
+			MilterAction::Tag => {
+				write_packet(w, b'h', format!("{}\0{}\0", RESULT_HEADER, value).as_bytes())?;
+				write_packet(w, b'a', &[])
+			}
+		}
+	}
+}
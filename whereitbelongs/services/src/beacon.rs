@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use wib_core::{Detection, DetectionKind};
+
+use crate::netmon::{NetEvent, NetEventKind};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BeaconOptions {
+	/// Connections needed before a flow is scored.
+	pub min_connections: usize,
+	/// Opens per flow kept for scoring.
+	pub history: usize,
+	/// Opens closer together than this are one burst (parallel connections), not two beacons.
+	pub min_interval_secs: f64,
+	/// Findings below this confidence are not reported.
+	pub min_confidence: f64,
+	/// Full executable paths, or process/executable names, never scored. Names can be chosen by
+	/// any program, so the defaults are all paths.
+	pub allowlist: Vec<String>,
+	/// Flows with no activity for this long are forgotten.
+	pub idle_secs: f64,
+}
+
+impl Default for BeaconOptions {
+	fn default() -> Self {
+		Self {
+			min_connections: 6,
+			history: 64,
+			min_interval_secs: 1.0,
+			min_confidence: 0.7,
+			allowlist: [
+				"/usr/lib/firefox/firefox", "/usr/lib/firefox-esr/firefox-esr", "/opt/google/chrome/chrome",
+				"/usr/lib/chromium/chromium", "/usr/lib/chromium-browser/chromium-browser", "/opt/brave.com/brave/brave",
+				"/opt/microsoft/msedge/msedge", "/usr/lib/thunderbird/thunderbird", "/usr/lib/apt/methods/http",
+				"/usr/lib/apt/methods/https", "/usr/libexec/packagekitd", "/usr/libexec/fwupd/fwupd", "/usr/lib/snapd/snapd",
+				"/usr/bin/gnome-software",
+				"/Applications/Safari.app/Contents/MacOS/Safari", "/Applications/Firefox.app/Contents/MacOS/firefox",
+				"/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+				r"C:\Program Files\Mozilla Firefox\firefox.exe", r"C:\Program Files\Google\Chrome\Application\chrome.exe",
+				r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe",
+				r"C:\Program Files (x86)\Google\Update\GoogleUpdate.exe",
+				r"C:\Program Files (x86)\Microsoft\EdgeUpdate\MicrosoftEdgeUpdate.exe", r"C:\Windows\System32\wuauclt.exe",
+			]
+			.map(String::from)
+			.to_vec(),
+			idle_secs: 6.0 * 3600.0,
+		}
+	}
+}
+
+/// A (process, remote host) flow that connects out on a schedule.
+#[derive(Debug, Clone, Serialize)]
+pub struct BeaconFinding {
+	pub process: String,
+	pub pid: Option<u32>,
+	pub exe: Option<PathBuf>,
+	pub remote: IpAddr,
+	pub port: u16,
+	pub connections: usize,
+	pub mean_interval_secs: f64,
+	/// Coefficient of variation of the intervals; 0 is a perfect metronome.
+	pub jitter: f64,
+	/// 1 - jitter, clamped to [0, 1].
+	pub periodicity: f64,
+	/// (sent - received) / total over closed connections: -1 is pure download, 1 pure upload.
+	/// `None` when byte counts are unavailable.
+	pub asymmetry: Option<f64>,
+	pub confidence: f64,
+}
+
+impl BeaconFinding {
+	pub fn to_detection(&self) -> Detection {
+		let description = format!(
+			"Possible C2 beaconing: {} -> {}:{} every {:.1}s (jitter {:.2}, {} connections, confidence {:.2})",
+			self.process, self.remote, self.port, self.mean_interval_secs, self.jitter, self.connections, self.confidence
+		);
+		Detection {
+			path: self.exe.clone().unwrap_or_else(|| PathBuf::from(&self.process)),
+			kind: DetectionKind::Heuristic { description },
+			// timing alone stays below the default policy's quarantine threshold
+			severity: (3.0 + 4.0 * self.confidence).round() as u8,
+			sha256: None,
+			config: None,
+			iocs: Vec::new(),
+			member: None,
+		}
+	}
+}
+
+#[derive(Default)]
+struct Flow {
+	opens: VecDeque<i64>,
+	sent: u64,
+	received: u64,
+	measured: usize,
+	pid: Option<u32>,
+	exe: Option<PathBuf>,
+	port: u16,
+	last_seen_ms: i64,
+	// opens seen when the flow was last reported
+	reported_at: Option<usize>,
+	total_opens: usize,
+}
+
+/// Scores netmon connection events per (process, remote host) for beacon-like timing.
+pub struct BeaconAnalyzer {
+	opts: BeaconOptions,
+	flows: HashMap<(String, IpAddr), Flow>,
+	last_sweep_ms: i64,
+}
+
+impl BeaconAnalyzer {
+	pub fn new(opts: BeaconOptions) -> Self {
+		Self { opts, flows: HashMap::new(), last_sweep_ms: 0 }
+	}
+
+	/// Allowlists `process` and forgets what was recorded for it.
+	pub fn allow(&mut self, process: &str) {
+		let list = [process.to_string()];
+		self.flows.retain(|(p, _), f| !is_allowed(&list, Some(p), f.exe.as_ref()));
+		self.opts.allowlist.extend(list);
+	}
+
+	/// Feeds one event; returns a finding when the flow newly crosses `min_confidence`, and again
+	/// after every further `min_connections` opens while it stays above.
+	pub fn observe(&mut self, event: &NetEvent) -> Option<BeaconFinding> {
+		let c = event.connection.as_ref()?;
+		let remote = c.remote.ip();
+		if c.remote.port() == 0 || remote.is_unspecified() || remote.is_loopback() || c.state == "LISTEN" { return None; }
+		if is_allowed(&self.opts.allowlist, c.process.as_deref(), c.exe.as_ref()) { return None; }
+		let process = c.exe.as_ref().map(|e| e.display().to_string()).or_else(|| c.process.clone()).unwrap_or_else(|| "unknown".into());
+		let (history, min_gap_ms) = (self.opts.history, (self.opts.min_interval_secs * 1000.0) as i64);
+		self.evict_idle(event.timestamp_ms);
+		let flow = self.flows.entry((process.clone(), remote)).or_default();
+		flow.last_seen_ms = event.timestamp_ms;
+		match event.kind {
+			NetEventKind::Opened => {
+				if flow.opens.back().is_some_and(|last| event.timestamp_ms - last < min_gap_ms) { return None; }
+				flow.opens.push_back(event.timestamp_ms);
+				if flow.opens.len() > history { flow.opens.pop_front(); }
+				flow.total_opens += 1;
+				flow.pid = c.pid.or(flow.pid);
+				flow.exe = c.exe.clone().or(flow.exe.take());
+				flow.port = c.remote.port();
+			}
+			NetEventKind::Closed => {
+				if let (Some(sent), Some(received)) = (c.bytes_sent, c.bytes_received) {
+					flow.sent += sent;
+					flow.received += received;
+					flow.measured += 1;
+				}
+				return None;
+			}
+			NetEventKind::Heartbeat => return None,
+		}
+		let min = self.opts.min_connections.max(3);
+		if flow.opens.len() < min { return None; }
+		if flow.reported_at.is_some_and(|at| flow.total_opens < at + min) { return None; }
+		let finding = score(&process, remote, flow, min);
+		if finding.confidence < self.opts.min_confidence { return None; }
+		flow.reported_at = Some(flow.total_opens);
+		Some(finding)
+	}
+
+	// at most once a minute, drop flows that went quiet
+	fn evict_idle(&mut self, now_ms: i64) {
+		if now_ms - self.last_sweep_ms < 60_000 { return; }
+		self.last_sweep_ms = now_ms;
+		let cutoff = now_ms - (self.opts.idle_secs * 1000.0) as i64;
+		self.flows.retain(|_, f| f.last_seen_ms >= cutoff);
+	}
+
+	/// Current scores for every flow with enough history, highest confidence first.
+	pub fn findings(&self) -> Vec<BeaconFinding> {
+		let min = self.opts.min_connections.max(3);
+		let mut all: Vec<BeaconFinding> = self.flows.iter().filter(|(_, f)| f.opens.len() >= min).map(|((p, r), f)| score(p, *r, f, min)).collect();
+		all.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+		all
+	}
+}
+
+// Entries with a path separator must equal the full executable path; bare names match the
+// process or executable name (case-insensitive).
+fn is_allowed(list: &[String], name: Option<&str>, exe: Option<&PathBuf>) -> bool {
+	let exe_name = exe.and_then(|e| e.file_name()).and_then(|n| n.to_str());
+	let exe_path = exe.and_then(|e| e.to_str());
+	list.iter().any(|a| {
+		if a.contains(['/', '\\']) {
+			return exe_path.is_some_and(|p| if cfg!(windows) { p.eq_ignore_ascii_case(a) } else { p == a });
+		}
+		[name, exe_name].into_iter().flatten().any(|n| n.eq_ignore_ascii_case(a))
+	})
+}
+
+fn score(process: &str, remote: IpAddr, flow: &Flow, min: usize) -> BeaconFinding {
+	let intervals: Vec<f64> = flow.opens.iter().zip(flow.opens.iter().skip(1)).map(|(a, b)| (b - a) as f64 / 1000.0).collect();
+	let n = intervals.len() as f64;
+	let mean = intervals.iter().sum::<f64>() / n;
+	let var = intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / n;
+	let jitter = if mean > 0.0 { var.sqrt() / mean } else { 1.0 };
+	let periodicity = (1.0 - jitter).clamp(0.0, 1.0);
+	let asymmetry = (flow.measured > 0 && flow.sent + flow.received > 0)
+		.then(|| (flow.sent as f64 - flow.received as f64) / (flow.sent + flow.received) as f64);
+	// beacons move little data per connection; bulk downloads look like updates or browsing
+	let volume = match asymmetry {
+		None => 0.5,
+		Some(asym) => {
+			let per_conn = (flow.sent + flow.received) as f64 / flow.measured as f64;
+			let small = if per_conn <= 16_384.0 { 1.0 } else if per_conn <= 262_144.0 { 0.5 } else { 0.1 };
+			if asym < -0.8 { small * 0.5 } else { small }
+		}
+	};
+	let count = (flow.opens.len() as f64 / (3 * min) as f64).min(1.0);
+	let confidence = if periodicity < 0.5 { periodicity * 0.5 } else { 0.55 * periodicity + 0.3 * volume + 0.15 * count };
+	BeaconFinding {
+		process: process.to_string(),
+		pid: flow.pid,
+		exe: flow.exe.clone(),
+		remote,
+		port: flow.port,
+		connections: flow.opens.len(),
+		mean_interval_secs: mean,
+		jitter,
+		periodicity,
+		asymmetry,
+		confidence,
+	}
+}
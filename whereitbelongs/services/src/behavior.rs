@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use wib_core::{Detection, DetectionKind};
+
+/// Rates that count as ransomware-like within `window_secs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BehaviorOptions {
+	pub window_secs: u64,
+	pub renames: usize,
+	pub extension_changes: usize,
+	/// Files whose content went from ordinary to near-random.
+	pub entropy_jumps: usize,
+	/// Stop (SIGSTOP) the offending process when the event source reports it (realtime does so
+	/// through fanotify, which needs CAP_SYS_ADMIN). Linux only.
+	pub suspend: bool,
+}
+
+impl Default for BehaviorOptions {
+	fn default() -> Self {
+		Self { window_secs: 10, renames: 50, extension_changes: 10, entropy_jumps: 10, suspend: false }
+	}
+}
+
+/// One filesystem change as seen by a watcher.
+#[derive(Debug, Clone)]
+pub enum FileActivity {
+	Created(PathBuf),
+	Modified(PathBuf),
+	Renamed { from: PathBuf, to: PathBuf },
+	Removed(PathBuf),
+}
+
+impl FileActivity {
+	fn path(&self) -> &Path {
+		match self {
+			FileActivity::Created(p) | FileActivity::Modified(p) | FileActivity::Removed(p) => p,
+			FileActivity::Renamed { to, .. } => to,
+		}
+	}
+}
+
+/// Who the activity is attributed to: the writing process when the source reports it
+/// (fanotify), otherwise the watched root the file lives under.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Actor {
+	Process(u32),
+	Directory(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signal {
+	Rename,
+	ExtensionChange,
+	EntropyJump,
+}
+
+#[derive(Default)]
+struct ActorWindow {
+	signals: VecDeque<(Instant, Signal)>,
+	last_alert: Option<Instant>,
+}
+
+// Above this (bits per byte) content is treated as encrypted or compressed.
+const HIGH_ENTROPY: f64 = 7.5;
+const LOW_ENTROPY: f64 = 6.0;
+const SAMPLE_BYTES: u64 = 64 * 1024;
+const ENTROPY_CACHE: usize = 16_384;
+
+// Formats that are normally low-entropy, so a near-random rewrite is suspicious.
+const PLAIN_EXTENSIONS: &[&str] = &[
+	"txt", "csv", "log", "md", "json", "xml", "html", "htm", "ini", "cfg", "conf", "yml", "yaml", "sql", "rtf",
+	"doc", "xls", "ppt", "bmp", "tif", "tiff", "wav", "psd", "c", "h", "cpp", "rs", "py", "js", "ts", "java", "cs",
+	"php", "sh", "ps1", "bat",
+];
+
+const NOTE_NAME_HINTS: &[&str] = &["readme", "decrypt", "restore", "recover", "how_to", "how-to", "help_", "your_files", "ransom"];
+const NOTE_TEXT_HINTS: &[&str] = &["bitcoin", "btc", "monero", "decrypt", "encrypted", "your files", "private key", "tor browser", ".onion", "ransom"];
+
+/// Tracks per-actor rates of renames, extension changes and entropy jumps, and spots ransom notes.
+pub struct BehaviorEngine {
+	opts: BehaviorOptions,
+	roots: Vec<PathBuf>,
+	actors: HashMap<Actor, ActorWindow>,
+	// last observed entropy per file, FIFO-bounded
+	entropy: HashMap<PathBuf, f64>,
+	entropy_order: VecDeque<PathBuf>,
+	// files created within the window and their writer, for the write-a-copy-then-delete-the-original pattern
+	created: VecDeque<(Instant, PathBuf, Option<u32>)>,
+}
+
+impl BehaviorEngine {
+	/// `roots` are the watched paths; without a process, activity is grouped by root.
+	pub fn new(opts: BehaviorOptions, roots: Vec<PathBuf>) -> Self {
+		Self { opts, roots, actors: HashMap::new(), entropy: HashMap::new(), entropy_order: VecDeque::new(), created: VecDeque::new() }
+	}
+
+	pub fn options(&self) -> &BehaviorOptions {
+		&self.opts
+	}
+
+	/// Feeds one change; returns an alert when it crosses a threshold or is a ransom note.
+	pub fn observe(&mut self, activity: &FileActivity, pid: Option<u32>) -> Option<Detection> {
+		let now = Instant::now();
+		let path = activity.path().to_path_buf();
+		let window = Duration::from_secs(self.opts.window_secs);
+		while self.created.front().is_some_and(|(t, _, _)| now.duration_since(*t) > window) { self.created.pop_front(); }
+		let mut pid = pid;
+		let mut signals = Vec::new();
+		match activity {
+			FileActivity::Renamed { from, to } => {
+				signals.push(Signal::Rename);
+				if extension(from) != extension(to) { signals.push(Signal::ExtensionChange); }
+				let before = self.forget_entropy(from);
+				if self.entropy_jumped(to, before, from) { signals.push(Signal::EntropyJump); }
+			}
+			FileActivity::Modified(p) => {
+				let before = self.entropy.get(p).copied();
+				if self.entropy_jumped(p, before, p) { signals.push(Signal::EntropyJump); }
+			}
+			FileActivity::Created(p) => {
+				if is_ransom_note(p) {
+					let description = format!("Ransomware: ransom note {}", p.display());
+					let alert = alert(p, description);
+					self.suspend(pid);
+					return Some(alert);
+				}
+				self.entropy_jumped(p, None, p);
+				self.created.push_back((now, p.clone(), pid));
+				if self.created.len() > ENTROPY_CACHE { self.created.pop_front(); }
+			}
+			FileActivity::Removed(p) => {
+				self.forget_entropy(p);
+				// "report.docx" deleted next to a freshly written, near-random "report.docx.locked";
+				// the deletion is charged to whoever wrote the copy
+				let copy = self.created.iter().find(|(_, c, _)| is_renamed_copy(p, c)).map(|(_, c, writer)| (c.clone(), *writer));
+				if let Some((_, writer)) = copy.filter(|(c, _)| sample_entropy(c).is_some_and(|e| e >= HIGH_ENTROPY)) {
+					signals.push(Signal::ExtensionChange);
+					pid = pid.or(writer);
+				}
+			}
+		}
+		if signals.is_empty() { return None; }
+		let actor = pid.map(Actor::Process).unwrap_or_else(|| Actor::Directory(self.root_of(&path)));
+		let state = self.actors.entry(actor.clone()).or_default();
+		state.signals.extend(signals.iter().map(|s| (now, *s)));
+		while state.signals.front().is_some_and(|(t, _)| now.duration_since(*t) > window) { state.signals.pop_front(); }
+		if state.last_alert.is_some_and(|t| now.duration_since(t) < window) { return None; }
+		let count = |sig: Signal| state.signals.iter().filter(|(_, s)| *s == sig).count();
+		let (renames, ext, jumps) = (count(Signal::Rename), count(Signal::ExtensionChange), count(Signal::EntropyJump));
+		if renames < self.opts.renames && ext < self.opts.extension_changes && jumps < self.opts.entropy_jumps { return None; }
+		state.last_alert = Some(now);
+		let who = match &actor {
+			Actor::Process(pid) => format!("pid {}", pid),
+			Actor::Directory(dir) => dir.display().to_string(),
+		};
+		let description = format!(
+			"Ransomware behavior: {} renames, {} extension changes, {} entropy jumps within {}s by {}",
+			renames, ext, jumps, window.as_secs(), who
+		);
+		self.suspend(pid);
+		Some(alert(&path, description))
+	}
+
+	fn root_of(&self, path: &Path) -> PathBuf {
+		self.roots
+			.iter()
+			.filter(|r| path.starts_with(r))
+			.max_by_key(|r| r.as_os_str().len())
+			.cloned()
+			.unwrap_or_else(|| path.parent().unwrap_or(path).to_path_buf())
+	}
+
+	// Records the new entropy of `path` and reports whether ordinary content became near-random.
+	// Without a previous reading, the original extension decides what "ordinary" means.
+	fn entropy_jumped(&mut self, path: &Path, before: Option<f64>, original: &Path) -> bool {
+		let Some(after) = sample_entropy(path) else { return false };
+		self.remember_entropy(path, after);
+		if after < HIGH_ENTROPY { return false; }
+		match before {
+			Some(before) => before < LOW_ENTROPY,
+			None => extension(original).is_some_and(|e| PLAIN_EXTENSIONS.contains(&e.as_str())),
+		}
+	}
+
+	fn remember_entropy(&mut self, path: &Path, value: f64) {
+		if self.entropy.insert(path.to_path_buf(), value).is_none() {
+			self.entropy_order.push_back(path.to_path_buf());
+			if self.entropy_order.len() > ENTROPY_CACHE {
+				if let Some(old) = self.entropy_order.pop_front() { self.entropy.remove(&old); }
+			}
+		}
+	}
+
+	fn forget_entropy(&mut self, path: &Path) -> Option<f64> {
+		self.entropy.remove(path)
+	}
+
+	// Only the reported writer is stopped; whoever merely has the file open may be a bystander.
+	fn suspend(&self, pid: Option<u32>) {
+		if !self.opts.suspend { return; }
+		let Some(pid) = pid else { return };
+		match suspend_process(pid) {
+			Ok(()) => log::warn!("Suspended pid {} for ransomware behavior", pid),
+			Err(e) => log::error!("Failed to suspend pid {}: {:#}", pid, e),
+		}
+	}
+}
+
+fn alert(path: &Path, description: String) -> Detection {
+	Detection {
+		path: path.to_path_buf(),
+		kind: DetectionKind::Heuristic { description },
+		severity: 10,
+		sha256: None,
+		config: None,
+		iocs: Vec::new(),
+		member: None,
+	}
+}
+
+// `copy` sits next to `original` under the same name with a changed or appended extension.
+fn is_renamed_copy(original: &Path, copy: &Path) -> bool {
+	if original == copy || original.parent() != copy.parent() { return false; }
+	let (Some(orig), Some(name)) = (original.file_name().and_then(|n| n.to_str()), copy.file_name().and_then(|n| n.to_str())) else { return false };
+	name.strip_prefix(orig).is_some_and(|rest| rest.starts_with('.'))
+		|| (original.file_stem() == copy.file_stem() && extension(original) != extension(copy))
+}
+
+fn extension(path: &Path) -> Option<String> {
+	path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase())
+}
+
+/// Shannon entropy (bits per byte) of the first 64 KiB of `path`.
+pub fn sample_entropy(path: &Path) -> Option<f64> {
+	let mut buf = Vec::new();
+	fs::File::open(path).ok()?.take(SAMPLE_BYTES).read_to_end(&mut buf).ok()?;
+	// tiny files say nothing about their content
+	if buf.len() < 256 { return None; }
+	Some(shannon_entropy(&buf))
+}
+
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+	if data.is_empty() { return 0.0; }
+	let mut counts = [0usize; 256];
+	for b in data {
+		counts[*b as usize] += 1;
+	}
+	let len = data.len() as f64;
+	counts.iter().filter(|c| **c > 0).map(|c| {
+		let p = *c as f64 / len;
+		-p * p.log2()
+	}).sum()
+}
+
+// A small text/html file named like a note that talks about payment or decryption.
+fn is_ransom_note(path: &Path) -> bool {
+	let Some(name) = path.file_name().and_then(|n| n.to_str()).map(|n| n.to_ascii_lowercase()) else { return false };
+	if !matches!(extension(path).as_deref(), Some("txt" | "html" | "htm" | "hta" | "rtf")) { return false; }
+	if !NOTE_NAME_HINTS.iter().any(|h| name.contains(h)) { return false; }
+	let Ok(meta) = fs::metadata(path) else { return false };
+	if meta.len() > 64 * 1024 { return false; }
+	let Ok(text) = fs::read(path) else { return false };
+	let text = String::from_utf8_lossy(&text).to_ascii_lowercase();
+	NOTE_TEXT_HINTS.iter().filter(|h| text.contains(*h)).count() >= 2
+}
+
+#[cfg(target_os = "linux")]
+pub fn suspend_process(pid: u32) -> anyhow::Result<()> {
+	if pid == std::process::id() || pid <= 1 { anyhow::bail!("Refusing to suspend pid {}", pid); }
+	if unsafe { libc::kill(pid as libc::pid_t, libc::SIGSTOP) } != 0 { return Err(std::io::Error::last_os_error().into()); }
+	Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn suspend_process(_pid: u32) -> anyhow::Result<()> {
+	anyhow::bail!("Process suspension is only supported on Linux")
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
+use wib_services::{analyze_file, IocStore, PcapOptions};
+
+const USAGE: &str = "usage: wib-pcap [--feeds DIR] [--json] [--max-stream BYTES] CAPTURE...
+exits with status 1 when a RAT marker or IOC match was found, 2 on errors";
+
+fn main() {
+	match run() {
+		Ok(true) => std::process::exit(1),
+		Ok(false) => {}
+		Err(e) => {
+			eprintln!("Error: {:#}", e);
+			std::process::exit(2);
+		}
+	}
+}
+
+/// Returns whether anything was flagged.
+fn run() -> Result<bool> {
+	let mut opts = PcapOptions::default();
+	let mut feeds: Option<PathBuf> = None;
+	let mut json = false;
+	let mut captures = Vec::new();
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value\n{}", arg, USAGE));
+		match arg.as_str() {
+			"--feeds" => feeds = Some(PathBuf::from(value()?)),
+			"--json" => json = true,
+			"--max-stream" => opts.max_stream_bytes = value()?.parse().context("--max-stream")?,
+			"-h" | "--help" => {
+				println!("{}", USAGE);
+				return Ok(false);
+			}
+			_ if arg.starts_with("--") => return Err(anyhow!("unknown argument {}\n{}", arg, USAGE)),
+			_ => captures.push(PathBuf::from(arg)),
+		}
+	}
+	if captures.is_empty() { return Err(anyhow!("no capture given\n{}", USAGE)); }
+
+	let store = match feeds {
+		Some(dir) => {
+			let mut store = IocStore::new();
+			store.load_dir(&dir)?;
+			store
+		}
+		None => IocStore::load_default()?.0,
+	};
+	let mut flagged = false;
+	for capture in &captures {
+		let report = analyze_file(capture, &opts)?;
+		let hits = report.match_iocs(&store);
+		flagged |= !report.findings.is_empty() || !hits.is_empty();
+		if json {
+			println!("{}", serde_json::json!({ "capture": capture, "report": report, "ioc_hits": hits }));
+			continue;
+		}
+		println!("{}: {} packets, {} TCP streams, {} skipped", capture.display(), report.packets, report.streams.len(), report.skipped);
+		for f in &report.findings {
+			println!("  FINDING [{}] {}", f.family.as_deref().unwrap_or("heuristic"), f.description);
+		}
+		for h in &hits {
+			let threat = h.matched.source.threat.as_deref().map(|t| format!(" ({})", t)).unwrap_or_default();
+			println!("  IOC {} {} matches {} from feed {}{}", h.context, h.observed, h.matched.indicator, h.matched.source.feed, threat);
+		}
+		for t in &report.tls {
+			println!(
+				"  tls {} -> {} sni={} ja3={} ja3s={}",
+				t.client,
+				t.server,
+				t.sni.as_deref().unwrap_or("-"),
+				t.ja3_hash.as_deref().unwrap_or("-"),
+				t.ja3s_hash.as_deref().unwrap_or("-")
+			);
+		}
+		for q in &report.dns {
+			let answers: Vec<String> = q.answers.iter().map(|a| a.to_string()).collect();
+			println!("  dns {} {} -> {}", q.query_type, q.name, if answers.is_empty() { "-".into() } else { answers.join(", ") });
+		}
+		for r in &report.http {
+			println!("  http {} {} ({})", r.method, r.url(), r.user_agent.as_deref().unwrap_or("no user agent"));
+		}
+	}
+	Ok(flagged)
+}
@@ -0,0 +1,356 @@
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use wib_core::quarantine::app_data_dir;
+use wib_core::{Detection, DetectionKind};
+
+/// A decoy file that nothing legitimate should touch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Canary {
+	pub path: PathBuf,
+	/// Seed of the decoy content, so a canary can be recreated byte for byte.
+	pub token: String,
+	pub sha256: String,
+	pub deployed_at: SystemTime,
+	pub refreshed_at: SystemTime,
+}
+
+// Names that sort first and look worth stealing or encrypting.
+const DECOY_NAMES: &[&str] = &["0_passwords_backup.csv", "0_bank_accounts.csv", "0_tax_records.txt", "0_wallet_seed_backup.txt"];
+
+fn sha256_hex(data: &[u8]) -> String {
+	format!("{:x}", Sha256::digest(data))
+}
+
+fn decoy_content(token: &str) -> Vec<u8> {
+	let mut out = String::from("account,username,password,notes\n");
+	for i in 0..40 {
+		let h = sha256_hex(format!("{}:{}", token, i).as_bytes());
+		out.push_str(&format!("acct-{:02},user.{},{},updated {}\n", i, &h[..6], &h[6..22], &h[22..30]));
+	}
+	out.into_bytes()
+}
+
+fn new_token(dir: &Path) -> String {
+	let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+	sha256_hex(format!("{}:{}:{}", nanos, std::process::id(), dir.display()).as_bytes())[..32].to_string()
+}
+
+/// Deployed canaries, persisted to `canaries.json` in the data directory.
+pub struct CanaryStore {
+	path: PathBuf,
+	canaries: Vec<Canary>,
+}
+
+impl CanaryStore {
+	pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let canaries = match fs::read(&path) {
+			Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("Invalid canary list in {}", path.display()))?,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+			Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+		};
+		Ok(Self { path, canaries })
+	}
+
+	pub fn open_default() -> Result<Self> {
+		Self::open(app_data_dir().join("canaries.json"))
+	}
+
+	pub fn canaries(&self) -> &[Canary] {
+		&self.canaries
+	}
+
+	/// Paths to put in `ScanOptions::exclude_paths`.
+	pub fn paths(&self) -> Vec<PathBuf> {
+		self.canaries.iter().map(|c| c.path.clone()).collect()
+	}
+
+	pub fn is_canary(&self, path: &Path) -> bool {
+		self.canaries.iter().any(|c| c.path == path)
+	}
+
+	/// Plants a canary in `dir`, or returns the one already there.
+	pub fn deploy<P: AsRef<Path>>(&mut self, dir: P) -> Result<Canary> {
+		let dir = dir.as_ref();
+		if let Some(c) = self.canaries.iter().find(|c| c.path.parent() == Some(dir)) { return Ok(c.clone()); }
+		let path = DECOY_NAMES
+			.iter()
+			.map(|n| dir.join(n))
+			.find(|p| !p.exists())
+			.with_context(|| format!("No free canary name in {}", dir.display()))?;
+		let token = new_token(dir);
+		let content = decoy_content(&token);
+		fs::write(&path, &content).with_context(|| format!("Failed to write canary {}", path.display()))?;
+		let now = SystemTime::now();
+		let canary = Canary { path, token, sha256: sha256_hex(&content), deployed_at: now, refreshed_at: now };
+		self.canaries.push(canary.clone());
+		self.save()?;
+		log::info!("Deployed canary {}", canary.path.display());
+		Ok(canary)
+	}
+
+	/// Touches intact canaries so they look recently used and recreates missing or altered ones.
+	/// Returns the recreated paths; a running monitor must be restarted to watch them again.
+	pub fn refresh(&mut self) -> Result<Vec<PathBuf>> {
+		let now = SystemTime::now();
+		let mut recreated = Vec::new();
+		for c in &mut self.canaries {
+			let intact = fs::read(&c.path).is_ok_and(|b| sha256_hex(&b) == c.sha256);
+			if intact {
+				let f = fs::OpenOptions::new().write(true).open(&c.path).with_context(|| format!("Failed to open canary {}", c.path.display()))?;
+				f.set_times(fs::FileTimes::new().set_accessed(now).set_modified(now))?;
+			} else {
+				if !c.path.parent().is_some_and(Path::is_dir) {
+					log::warn!("Canary directory for {} is gone; skipping", c.path.display());
+					continue;
+				}
+				fs::write(&c.path, decoy_content(&c.token)).with_context(|| format!("Failed to recreate canary {}", c.path.display()))?;
+				recreated.push(c.path.clone());
+			}
+			c.refreshed_at = now;
+		}
+		self.save()?;
+		Ok(recreated)
+	}
+
+	pub fn remove(&mut self, path: &Path) -> Result<()> {
+		let Some(pos) = self.canaries.iter().position(|c| c.path == path) else { return Ok(()) };
+		match fs::remove_file(path) {
+			Ok(()) => {}
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+			Err(e) => return Err(e).with_context(|| format!("Failed to remove canary {}", path.display())),
+		}
+		self.canaries.remove(pos);
+		self.save()
+	}
+
+	fn save(&self) -> Result<()> {
+		if let Some(dir) = self.path.parent() {
+			fs::create_dir_all(dir).with_context(|| format!("Failed to create dir: {}", dir.display()))?;
+		}
+		let tmp = self.path.with_extension("json.tmp");
+		fs::write(&tmp, serde_json::to_vec_pretty(&self.canaries)?).with_context(|| format!("Failed to write {}", tmp.display()))?;
+		fs::rename(&tmp, &self.path).with_context(|| format!("Failed to replace {}", self.path.display()))
+	}
+}
+
+/// Watches canaries for modification, rename and deletion; dropping it stops monitoring.
+pub struct CanaryGuard {
+	watcher: Option<RecommendedWatcher>,
+	stop: Arc<AtomicBool>,
+	joins: Vec<thread::JoinHandle<()>>,
+	#[cfg(target_os = "linux")]
+	fan: Option<std::os::fd::OwnedFd>,
+}
+
+impl CanaryGuard {
+	/// Whether alerts can name the acting process reliably (fanotify, needs CAP_SYS_ADMIN).
+	/// Otherwise only processes still holding the file open are found.
+	pub fn attributes_processes(&self) -> bool {
+		#[cfg(target_os = "linux")]
+		return self.fan.is_some();
+		#[cfg(not(target_os = "linux"))]
+		false
+	}
+
+	pub fn stop(&mut self) {
+		let Some(watcher) = self.watcher.take() else { return };
+		self.stop.store(true, Ordering::Relaxed);
+		drop(watcher);
+		for j in self.joins.drain(..) {
+			if j.join().is_err() { log::warn!("Canary thread panicked"); }
+		}
+		#[cfg(target_os = "linux")]
+		drop(self.fan.take());
+	}
+}
+
+impl Drop for CanaryGuard {
+	fn drop(&mut self) {
+		self.stop();
+	}
+}
+
+// Last process other than us seen touching each canary inode, by (dev, ino).
+type Actors = Arc<Mutex<HashMap<(u64, u64), u32>>>;
+
+const ALERT_COOLDOWN: Duration = Duration::from_secs(5);
+
+/// Monitors a snapshot of `canaries`; every touch raises a severity-10 detection on `tx_detect`.
+pub fn start_canary_monitor(canaries: &[Canary], tx_detect: Sender<Detection>) -> Result<CanaryGuard> {
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let mut watcher: RecommendedWatcher = RecommendedWatcher::new(move |res| {
+		let _ = tx.send(res);
+	}, Config::default())?;
+	let dirs: HashSet<&Path> = canaries.iter().filter_map(|c| c.path.parent()).collect();
+	for dir in dirs {
+		watcher.watch(dir, RecursiveMode::NonRecursive).with_context(|| format!("Failed to watch {}", dir.display()))?;
+	}
+	let inodes: HashMap<PathBuf, (u64, u64)> = canaries.iter().filter_map(|c| Some((c.path.clone(), inode(&c.path)?))).collect();
+	let by_path: HashMap<PathBuf, Canary> = canaries.iter().map(|c| (c.path.clone(), c.clone())).collect();
+	let actors: Actors = Arc::default();
+	let stop = Arc::new(AtomicBool::new(false));
+	let mut joins = Vec::new();
+	#[cfg(target_os = "linux")]
+	let fan = match linux::watch_actors(canaries, actors.clone(), stop.clone()) {
+		Ok((fan, join)) => {
+			joins.push(join);
+			Some(fan)
+		}
+		Err(e) => {
+			log::info!("Canary process attribution unavailable: {:#}", e);
+			None
+		}
+	};
+	let alert_actors = actors.clone();
+	joins.push(thread::spawn(move || {
+		let mut last_alert: HashMap<PathBuf, Instant> = HashMap::new();
+		for event in rx.into_iter().flatten() {
+			for (path, what) in tampering(&event, &by_path) {
+				if last_alert.get(&path).is_some_and(|t| t.elapsed() < ALERT_COOLDOWN) { continue; }
+				last_alert.insert(path.clone(), Instant::now());
+				let pid = actor(&path, inodes.get(&path), &alert_actors);
+				let who = match pid {
+					Some(pid) => format!("pid {} ({})", pid, process_name(pid).unwrap_or_else(|| "?".into())),
+					None => "an unknown process".into(),
+				};
+				log::error!("Canary {} {} by {}", path.display(), what, who);
+				let _ = tx_detect.send(Detection {
+					path,
+					kind: DetectionKind::Heuristic { description: format!("Canary file {} by {}", what, who) },
+					severity: 10,
+					sha256: None,
+					config: None,
+					iocs: Vec::new(),
+					member: None,
+				});
+			}
+		}
+	}));
+	log::info!("Monitoring {} canaries", canaries.len());
+	Ok(CanaryGuard {
+		watcher: Some(watcher),
+		stop,
+		joins,
+		#[cfg(target_os = "linux")]
+		fan,
+	})
+}
+
+// (canary path, what happened) for every canary the event touches.
+fn tampering(event: &Event, canaries: &HashMap<PathBuf, Canary>) -> Vec<(PathBuf, String)> {
+	let mut hits = Vec::new();
+	for (i, p) in event.paths.iter().enumerate() {
+		let Some(canary) = canaries.get(p) else { continue };
+		let what = match event.kind {
+			EventKind::Remove(_) => "deleted".to_string(),
+			EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if i == 0 && event.paths.len() == 2 => {
+				format!("renamed to {}", event.paths[1].display())
+			}
+			EventKind::Modify(ModifyKind::Name(RenameMode::To)) => "replaced".to_string(),
+			EventKind::Modify(ModifyKind::Name(_)) => "renamed".to_string(),
+			EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) | EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+				// a write that left the content as it was (our own refresh) is not tampering
+				if fs::read(p).is_ok_and(|b| sha256_hex(&b) == canary.sha256) { continue; }
+				"modified".to_string()
+			}
+			_ => continue,
+		};
+		hits.push((p.clone(), what));
+	}
+	hits
+}
+
+fn inode(path: &Path) -> Option<(u64, u64)> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::MetadataExt;
+		let meta = fs::metadata(path).ok()?;
+		Some((meta.dev(), meta.ino()))
+	}
+	#[cfg(not(unix))]
+	{
+		let _ = path;
+		None
+	}
+}
+
+// The fanotify reader can lag the watcher slightly, so give it a moment before falling back
+// to whoever still has the file open.
+fn actor(path: &Path, inode: Option<&(u64, u64)>, actors: &Actors) -> Option<u32> {
+	if let Some(key) = inode {
+		for _ in 0..10 {
+			if let Some(pid) = actors.lock().unwrap().get(key) { return Some(*pid); }
+			thread::sleep(Duration::from_millis(20));
+		}
+	}
+	let own = std::process::id();
+	crate::response::processes_using(path).into_iter().find(|p| *p != own)
+}
+
+fn process_name(pid: u32) -> Option<String> {
+	let mut sys = sysinfo::System::new();
+	let pid = sysinfo::Pid::from_u32(pid);
+	sys.refresh_process(pid);
+	sys.process(pid).map(|p| p.name().to_string())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use anyhow::{Context, Result};
+	use std::ffi::CString;
+	use std::fs::File;
+	use std::io;
+	use std::os::fd::{FromRawFd, OwnedFd};
+	use std::os::unix::ffi::OsStrExt;
+	use std::os::unix::fs::MetadataExt;
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::sync::Arc;
+	use std::thread;
+
+	use super::{Actors, Canary};
+	use crate::fanotify::parse_events;
+
+	// Notification-only fanotify group with inode marks on each canary; it only records who
+	// touched what, the notify watcher decides what counts as tampering.
+	pub(super) fn watch_actors(canaries: &[Canary], actors: Actors, stop: Arc<AtomicBool>) -> Result<(OwnedFd, thread::JoinHandle<()>)> {
+		let raw = unsafe {
+			libc::fanotify_init(libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC | libc::FAN_NONBLOCK, (libc::O_RDONLY | libc::O_CLOEXEC) as libc::c_uint)
+		};
+		if raw < 0 { return Err(io::Error::last_os_error()).context("fanotify_init failed (needs CAP_SYS_ADMIN)"); }
+		let fan = unsafe { OwnedFd::from_raw_fd(raw) };
+		let mask = libc::FAN_OPEN | libc::FAN_MODIFY | libc::FAN_CLOSE_WRITE;
+		for c in canaries {
+			let p = CString::new(c.path.as_os_str().as_bytes()).with_context(|| format!("Invalid path {}", c.path.display()))?;
+			let r = unsafe { libc::fanotify_mark(raw, libc::FAN_MARK_ADD, mask, libc::AT_FDCWD, p.as_ptr()) };
+			if r < 0 { return Err(io::Error::last_os_error()).with_context(|| format!("fanotify_mark failed for {}", c.path.display())); }
+		}
+		let join = thread::spawn(move || {
+			let own_pid = std::process::id() as i32;
+			let mut buf = vec![0u8; 16 * 1024];
+			while !stop.load(Ordering::Relaxed) {
+				let mut pfd = libc::pollfd { fd: raw, events: libc::POLLIN, revents: 0 };
+				if unsafe { libc::poll(&mut pfd, 1, 200) } <= 0 { continue; }
+				let n = unsafe { libc::read(raw, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+				if n <= 0 { continue; }
+				for (fd, pid) in parse_events(&buf[..n as usize]) {
+					let file = unsafe { File::from_raw_fd(fd) };
+					if pid == own_pid || pid <= 0 { continue; }
+					if let Ok(meta) = file.metadata() { actors.lock().unwrap().insert((meta.dev(), meta.ino()), pid as u32); }
+				}
+			}
+		});
+		Ok((fan, join))
+	}
+}
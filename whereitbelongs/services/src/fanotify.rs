@@ -0,0 +1,407 @@
+use anyhow::{bail, Context, Result};
+use crossbeam_channel::{bounded, Sender, TrySendError};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use wib_core::{Detection, ScanOptions, Scanner};
+
+// On-access scanning through fanotify permission events. The kernel holds each open()/execve()
+// of a marked file until we answer, so every path here must answer, and answer quickly.
+
+const METADATA_LEN: usize = 24;
+
+#[derive(Clone, Debug)]
+pub struct OnAccessOptions {
+	/// The whole mount containing each path is marked.
+	pub paths: Vec<PathBuf>,
+	pub block_open: bool,
+	pub block_exec: bool,
+	/// Access is allowed (fail-open) when no verdict arrives within this time.
+	pub scan_timeout: Duration,
+	/// Verdicts remembered per (device, inode, size, mtime).
+	pub cache_capacity: usize,
+	pub workers: usize,
+	pub scan_options: ScanOptions,
+}
+
+impl Default for OnAccessOptions {
+	fn default() -> Self {
+		Self {
+			paths: Vec::new(),
+			block_open: true,
+			block_exec: true,
+			scan_timeout: Duration::from_secs(2),
+			cache_capacity: 65_536,
+			workers: thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+			scan_options: ScanOptions::default(),
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+	events: AtomicU64,
+	allowed: AtomicU64,
+	denied: AtomicU64,
+	cache_hits: AtomicU64,
+	timeouts: AtomicU64,
+	errors: AtomicU64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OnAccessStats {
+	pub events: u64,
+	pub allowed: u64,
+	pub denied: u64,
+	pub cache_hits: u64,
+	/// Accesses allowed unscanned because the verdict was late or the pool was saturated.
+	pub timeouts: u64,
+	pub errors: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FileKey {
+	dev: u64,
+	ino: u64,
+	size: u64,
+	mtime: i64,
+	mtime_nsec: i64,
+}
+
+impl FileKey {
+	fn of(meta: &std::fs::Metadata) -> Self {
+		Self { dev: meta.dev(), ino: meta.ino(), size: meta.size(), mtime: meta.mtime(), mtime_nsec: meta.mtime_nsec() }
+	}
+}
+
+/// Bounded verdict cache, oldest entries evicted first.
+struct VerdictCache {
+	map: HashMap<FileKey, Option<Detection>>,
+	order: VecDeque<FileKey>,
+	capacity: usize,
+}
+
+impl VerdictCache {
+	fn get(&self, key: &FileKey) -> Option<Option<Detection>> {
+		self.map.get(key).cloned()
+	}
+
+	fn insert(&mut self, key: FileKey, verdict: Option<Detection>) {
+		if self.capacity == 0 { return; }
+		if self.map.insert(key, verdict).is_none() {
+			self.order.push_back(key);
+		}
+		while self.order.len() > self.capacity {
+			if let Some(old) = self.order.pop_front() { self.map.remove(&old); }
+		}
+	}
+}
+
+struct PermEvent {
+	file: File,
+	pid: i32,
+}
+
+struct ScanJob {
+	file: File,
+	path: PathBuf,
+	key: FileKey,
+	reply: Sender<Option<Detection>>,
+}
+
+struct Shared {
+	// owned by the guard, which closes it only after every thread has exited
+	fan: i32,
+	scanner: Arc<Scanner>,
+	scan_options: ScanOptions,
+	timeout: Duration,
+	cache: Mutex<VerdictCache>,
+	counters: Counters,
+	tx_detect: Sender<Detection>,
+}
+
+impl Shared {
+	fn respond(&self, event_fd: i32, allow: bool) {
+		let resp = libc::fanotify_response { fd: event_fd, response: if allow { libc::FAN_ALLOW } else { libc::FAN_DENY } };
+		let n = unsafe { libc::write(self.fan, &resp as *const _ as *const libc::c_void, std::mem::size_of_val(&resp)) };
+		if n < 0 {
+			self.counters.errors.fetch_add(1, Ordering::Relaxed);
+			log::warn!("fanotify response failed: {}", io::Error::last_os_error());
+		}
+		let counter = if allow { &self.counters.allowed } else { &self.counters.denied };
+		counter.fetch_add(1, Ordering::Relaxed);
+	}
+
+	// Runs on a decider thread: cache lookup, else hand off to the scan pool and wait.
+	fn decide(&self, ev: PermEvent, jobs: &Sender<ScanJob>) {
+		let fd = ev.file.as_raw_fd();
+		let path = std::fs::read_link(format!("/proc/self/fd/{}", fd)).unwrap_or_default();
+		let Ok(meta) = ev.file.metadata() else {
+			self.counters.errors.fetch_add(1, Ordering::Relaxed);
+			return self.respond(fd, true);
+		};
+		if !meta.is_file() || meta.size() > self.scan_options.max_file_size_bytes { return self.respond(fd, true); }
+		let key = FileKey::of(&meta);
+		if let Some(verdict) = self.cache.lock().unwrap().get(&key) {
+			self.counters.cache_hits.fetch_add(1, Ordering::Relaxed);
+			return self.verdict(fd, verdict, &path, ev.pid);
+		}
+		let Ok(dup) = ev.file.try_clone() else { return self.respond(fd, true) };
+		let (reply, verdict) = bounded(1);
+		match jobs.try_send(ScanJob { file: dup, path: path.clone(), key, reply }) {
+			Ok(()) => {}
+			Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+				self.counters.timeouts.fetch_add(1, Ordering::Relaxed);
+				return self.respond(fd, true);
+			}
+		}
+		match verdict.recv_timeout(self.timeout) {
+			Ok(v) => self.verdict(fd, v, &path, ev.pid),
+			Err(_) => {
+				self.counters.timeouts.fetch_add(1, Ordering::Relaxed);
+				log::debug!("on-access scan of {} timed out; allowing", path.display());
+				self.respond(fd, true);
+			}
+		}
+	}
+
+	fn verdict(&self, fd: i32, detection: Option<Detection>, path: &std::path::Path, pid: i32) {
+		let Some(mut d) = detection else { return self.respond(fd, true) };
+		self.respond(fd, false);
+		log::warn!("Blocked access to {} by pid {}", path.display(), pid);
+		d.path = path.to_path_buf();
+		let _ = self.tx_detect.send(d);
+	}
+
+	fn scan(&self, job: ScanJob) {
+		let mut buf = Vec::new();
+		let verdict = match (&job.file).take(self.scan_options.max_file_size_bytes).read_to_end(&mut buf) {
+			Ok(_) => self.scanner.scan_bytes(&buf, &job.path.to_string_lossy(), &self.scan_options).into_iter().next(),
+			Err(e) => {
+				self.counters.errors.fetch_add(1, Ordering::Relaxed);
+				log::debug!("on-access read of {} failed: {}", job.path.display(), e);
+				None
+			}
+		};
+		self.cache.lock().unwrap().insert(job.key, verdict.clone());
+		let _ = job.reply.send(verdict);
+	}
+}
+
+/// Running fanotify monitor; dropping it removes the marks and lets pending accesses through.
+pub struct OnAccessGuard {
+	fan: Option<OwnedFd>,
+	shared: Arc<Shared>,
+	stop: Arc<AtomicBool>,
+	joins: Vec<thread::JoinHandle<()>>,
+}
+
+impl OnAccessGuard {
+	pub fn stats(&self) -> OnAccessStats {
+		let c = &self.shared.counters;
+		OnAccessStats {
+			events: c.events.load(Ordering::Relaxed),
+			allowed: c.allowed.load(Ordering::Relaxed),
+			denied: c.denied.load(Ordering::Relaxed),
+			cache_hits: c.cache_hits.load(Ordering::Relaxed),
+			timeouts: c.timeouts.load(Ordering::Relaxed),
+			errors: c.errors.load(Ordering::Relaxed),
+		}
+	}
+
+	/// Answers everything in flight, then closes the fanotify fd, which drops the marks and
+	/// releases any access the kernel is still holding.
+	pub fn stop(&mut self) {
+		let Some(fan) = self.fan.take() else { return };
+		self.stop.store(true, Ordering::Relaxed);
+		for j in self.joins.drain(..) {
+			if j.join().is_err() { log::warn!("On-access thread panicked"); }
+		}
+		drop(fan);
+		log::info!("On-access scanning stopped");
+	}
+}
+
+impl Drop for OnAccessGuard {
+	fn drop(&mut self) {
+		self.stop();
+	}
+}
+
+/// Starts blocking on-access scanning. Needs CAP_SYS_ADMIN; exec blocking needs Linux 5.0+.
+pub fn start_on_access(opts: OnAccessOptions, scanner: Arc<Scanner>, tx_detect: Sender<Detection>) -> Result<OnAccessGuard> {
+	let mut mask = 0;
+	if opts.block_open { mask |= libc::FAN_OPEN_PERM; }
+	if opts.block_exec { mask |= libc::FAN_OPEN_EXEC_PERM; }
+	if mask == 0 { bail!("Nothing to block: enable block_open or block_exec"); }
+	let raw = unsafe {
+		libc::fanotify_init(
+			libc::FAN_CLASS_CONTENT | libc::FAN_CLOEXEC | libc::FAN_NONBLOCK,
+			(libc::O_RDONLY | libc::O_LARGEFILE | libc::O_CLOEXEC) as libc::c_uint,
+		)
+	};
+	if raw < 0 { return Err(io::Error::last_os_error()).context("fanotify_init failed (needs CAP_SYS_ADMIN)"); }
+	let fan = unsafe { OwnedFd::from_raw_fd(raw) };
+	for p in &opts.paths {
+		let c = CString::new(p.as_os_str().as_bytes()).with_context(|| format!("Invalid path {}", p.display()))?;
+		let r = unsafe { libc::fanotify_mark(raw, libc::FAN_MARK_ADD | libc::FAN_MARK_MOUNT, mask, libc::AT_FDCWD, c.as_ptr()) };
+		if r < 0 { return Err(io::Error::last_os_error()).with_context(|| format!("fanotify_mark failed for {}", p.display())); }
+	}
+	let shared = Arc::new(Shared {
+		fan: raw,
+		scanner,
+		scan_options: opts.scan_options,
+		timeout: opts.scan_timeout,
+		cache: Mutex::new(VerdictCache { map: HashMap::new(), order: VecDeque::new(), capacity: opts.cache_capacity }),
+		counters: Counters::default(),
+		tx_detect,
+	});
+	let workers = opts.workers.max(1);
+	let stop = Arc::new(AtomicBool::new(false));
+	let (event_tx, event_rx) = bounded::<PermEvent>(workers * 16);
+	let (job_tx, job_rx) = bounded::<ScanJob>(workers * 4);
+	let mut joins = Vec::new();
+	for _ in 0..workers {
+		let (s, job_rx) = (shared.clone(), job_rx.clone());
+		joins.push(thread::spawn(move || {
+			for job in job_rx { s.scan(job); }
+		}));
+		// deciders mostly wait on verdicts, so run two per scan worker
+		for _ in 0..2 {
+			let (s, event_rx, job_tx) = (shared.clone(), event_rx.clone(), job_tx.clone());
+			joins.push(thread::spawn(move || {
+				for ev in event_rx { s.decide(ev, &job_tx); }
+			}));
+		}
+	}
+	drop(job_tx);
+	let (reader_shared, reader_stop) = (shared.clone(), stop.clone());
+	joins.insert(0, thread::spawn(move || read_events(&reader_shared, event_tx, &reader_stop)));
+	log::info!("On-access scanning active on {} mount(s)", opts.paths.len());
+	Ok(OnAccessGuard { fan: Some(fan), shared, stop, joins })
+}
+
+fn read_events(shared: &Shared, events: Sender<PermEvent>, stop: &AtomicBool) {
+	let fan = shared.fan;
+	let own_pid = std::process::id() as i32;
+	let mut buf = vec![0u8; 64 * 1024];
+	while !stop.load(Ordering::Relaxed) {
+		let mut pfd = libc::pollfd { fd: fan, events: libc::POLLIN, revents: 0 };
+		if unsafe { libc::poll(&mut pfd, 1, 200) } <= 0 { continue; }
+		let n = unsafe { libc::read(fan, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+		if n <= 0 { continue; }
+		for (fd, pid) in parse_events(&buf[..n as usize]) {
+			shared.counters.events.fetch_add(1, Ordering::Relaxed);
+			let file = unsafe { File::from_raw_fd(fd) };
+			// our own reads (quarantine, rescans) must never wait on ourselves
+			if pid == own_pid {
+				shared.respond(fd, true);
+				continue;
+			}
+			if let Err(e) = events.try_send(PermEvent { file, pid }) {
+				let ev = e.into_inner();
+				shared.counters.timeouts.fetch_add(1, Ordering::Relaxed);
+				shared.respond(ev.file.as_raw_fd(), true);
+			}
+		}
+	}
+}
+
+// Files whose last writer is remembered; the map is cleared when it grows past this.
+const WRITERS_CAPACITY: usize = 65_536;
+
+/// Last process to write each file on the marked mounts, by (device, inode), from a
+/// notification-only fanotify group. Stops when dropped.
+pub struct WriterTracker {
+	fan: Option<OwnedFd>,
+	writers: Arc<Mutex<HashMap<(u64, u64), u32>>>,
+	stop: Arc<AtomicBool>,
+	join: Option<thread::JoinHandle<()>>,
+}
+
+impl WriterTracker {
+	/// The process that last wrote `path`. fanotify can lag the watcher that reported the
+	/// change, so a missing entry is retried briefly.
+	pub fn writer_of(&self, path: &Path) -> Option<u32> {
+		let meta = std::fs::metadata(path).ok()?;
+		let key = (meta.dev(), meta.ino());
+		for _ in 0..3 {
+			if let Some(pid) = self.writers.lock().unwrap().get(&key) { return Some(*pid); }
+			thread::sleep(Duration::from_millis(10));
+		}
+		None
+	}
+}
+
+impl Drop for WriterTracker {
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::Relaxed);
+		if let Some(join) = self.join.take() {
+			if join.join().is_err() { log::warn!("Writer tracking thread panicked"); }
+		}
+		drop(self.fan.take());
+	}
+}
+
+/// Records writers on the mounts containing `paths`. Needs CAP_SYS_ADMIN.
+pub fn track_writers(paths: &[PathBuf]) -> Result<WriterTracker> {
+	let raw = unsafe {
+		libc::fanotify_init(libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC | libc::FAN_NONBLOCK, (libc::O_RDONLY | libc::O_LARGEFILE | libc::O_CLOEXEC) as libc::c_uint)
+	};
+	if raw < 0 { return Err(io::Error::last_os_error()).context("fanotify_init failed (needs CAP_SYS_ADMIN)"); }
+	let fan = unsafe { OwnedFd::from_raw_fd(raw) };
+	for p in paths {
+		let c = CString::new(p.as_os_str().as_bytes()).with_context(|| format!("Invalid path {}", p.display()))?;
+		let r = unsafe { libc::fanotify_mark(raw, libc::FAN_MARK_ADD | libc::FAN_MARK_MOUNT, libc::FAN_MODIFY | libc::FAN_CLOSE_WRITE, libc::AT_FDCWD, c.as_ptr()) };
+		if r < 0 { return Err(io::Error::last_os_error()).with_context(|| format!("fanotify_mark failed for {}", p.display())); }
+	}
+	let writers: Arc<Mutex<HashMap<(u64, u64), u32>>> = Arc::default();
+	let stop = Arc::new(AtomicBool::new(false));
+	let (thread_writers, thread_stop) = (writers.clone(), stop.clone());
+	let join = thread::spawn(move || {
+		let own_pid = std::process::id() as i32;
+		let mut buf = vec![0u8; 16 * 1024];
+		while !thread_stop.load(Ordering::Relaxed) {
+			let mut pfd = libc::pollfd { fd: raw, events: libc::POLLIN, revents: 0 };
+			if unsafe { libc::poll(&mut pfd, 1, 200) } <= 0 { continue; }
+			let n = unsafe { libc::read(raw, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+			if n <= 0 { continue; }
+			let mut writers = thread_writers.lock().unwrap();
+			for (fd, pid) in parse_events(&buf[..n as usize]) {
+				let file = unsafe { File::from_raw_fd(fd) };
+				if pid == own_pid || pid <= 0 { continue; }
+				let Ok(meta) = file.metadata() else { continue };
+				if writers.len() >= WRITERS_CAPACITY { writers.clear(); }
+				writers.insert((meta.dev(), meta.ino()), pid as u32);
+			}
+		}
+	});
+	Ok(WriterTracker { fan: Some(fan), writers, stop, join: Some(join) })
+}
+
+/// (event fd, pid) of each event in a buffer read from a fanotify fd; overflow events are skipped.
+pub(crate) fn parse_events(buf: &[u8]) -> Vec<(i32, i32)> {
+	let mut events = Vec::new();
+	let mut off = 0;
+	while off + METADATA_LEN <= buf.len() {
+		let b = &buf[off..];
+		let event_len = u32::from_ne_bytes(b[0..4].try_into().unwrap()) as usize;
+		let vers = b[4];
+		let fd = i32::from_ne_bytes(b[16..20].try_into().unwrap());
+		let pid = i32::from_ne_bytes(b[20..24].try_into().unwrap());
+		if event_len < METADATA_LEN || vers != libc::FANOTIFY_METADATA_VERSION { break; }
+		off += event_len;
+		if fd >= 0 { events.push((fd, pid)); }
+	}
+	events
+}
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use wib_core::RatConfig;
 
 #[cfg(target_os = "windows")]
 mod windows {
@@ -39,4 +40,22 @@ mod windows {
 	pub fn remove_rule(_name: &str) -> Result<()> { Ok(()) }
 }
 
-pub use windows::*;
\ No newline at end of file
+pub use windows::*;
+
+/// True where firewall rules are actually applied; elsewhere the functions above do nothing.
+pub fn is_supported() -> bool {
+	cfg!(target_os = "windows")
+}
+
+/// Blocks the IP-address C2 endpoints of a RAT config; returns the addresses that were blocked.
+/// Hostnames are left alone, since netsh cannot scope an outbound rule to a domain.
+pub fn block_c2(config: &RatConfig) -> Result<Vec<String>> {
+	let mut blocked = Vec::new();
+	for ip in config.c2.iter().filter_map(|c2| c2.ip()) {
+		let ip = ip.to_string();
+		if blocked.contains(&ip) { continue; }
+		block_ip(&ip)?;
+		blocked.push(ip);
+	}
+	Ok(blocked)
+}
@@ -1,8 +1,24 @@
 pub mod realtime;
+pub mod beacon;
+pub mod behavior;
+pub mod canary;
+#[cfg(target_os = "linux")]
+pub mod fanotify;
+pub mod mounts;
 pub mod netmon;
+pub mod netioc;
+pub mod pcap;
+pub mod response;
 pub mod firewall;
 pub mod updater;
 
-pub use realtime::{RealtimeGuard, RealtimeOptions};
-pub use netmon::{NetworkMonitor, NetEvent};
+pub use realtime::{MetricsSnapshot, RealtimeGuard, RealtimeOptions, RealtimeState, RealtimeStatus, WatchBackend};
+pub use beacon::{BeaconAnalyzer, BeaconFinding, BeaconOptions};
+pub use behavior::{BehaviorEngine, BehaviorOptions, FileActivity};
+pub use canary::{start_canary_monitor, Canary, CanaryGuard, CanaryStore};
+pub use mounts::{MountInfo, MountScanOptions, MountScanReport};
+pub use response::{ActionLog, ActionOutcome, ActionRecord, PolicyRule, Responder, ResponseAction, ResponsePolicy};
+pub use netmon::{Connection, NetEvent, NetEventKind, NetworkMonitor, Protocol};
+pub use netioc::{spawn_ioc_matcher, FeedFormat, FeedReport, IndicatorKind, IocMatch, IocSource, IocStore};
+pub use pcap::{analyze_file, PcapFinding, PcapIocHit, PcapOptions, PcapReport};
 pub use firewall::*;
\ No newline at end of file
@@ -0,0 +1,280 @@
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+use wib_core::{Detection, DetectionKind, ScanOptions, Scanner};
+
+/// One line of `/proc/self/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MountInfo {
+	pub mount_id: u32,
+	pub parent_id: u32,
+	/// `major:minor` of the backing device.
+	pub device: String,
+	pub root: PathBuf,
+	pub mount_point: PathBuf,
+	pub options: String,
+	pub fs_type: String,
+	pub source: String,
+}
+
+// Fields escape space, tab, newline and backslash as octal, e.g. `\040`.
+fn unescape(field: &str) -> String {
+	let b = field.as_bytes();
+	let mut out = Vec::with_capacity(b.len());
+	let mut i = 0;
+	while i < b.len() {
+		if b[i] == b'\\' && i + 3 < b.len() && b[i + 1..i + 4].iter().all(|c| (b'0'..=b'7').contains(c)) {
+			out.push((b[i + 1] - b'0') * 64 + (b[i + 2] - b'0') * 8 + (b[i + 3] - b'0'));
+			i += 4;
+		} else {
+			out.push(b[i]);
+			i += 1;
+		}
+	}
+	String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parses mountinfo text; malformed lines are skipped.
+pub fn parse_mountinfo(text: &str) -> Vec<MountInfo> {
+	text.lines()
+		.filter_map(|line| {
+			let (pre, post) = line.split_once(" - ")?;
+			let pre: Vec<&str> = pre.split(' ').collect();
+			let mut post = post.split(' ');
+			if pre.len() < 6 { return None; }
+			Some(MountInfo {
+				mount_id: pre[0].parse().ok()?,
+				parent_id: pre[1].parse().ok()?,
+				device: pre[2].to_string(),
+				root: PathBuf::from(unescape(pre[3])),
+				mount_point: PathBuf::from(unescape(pre[4])),
+				options: pre[5].to_string(),
+				fs_type: post.next()?.to_string(),
+				source: unescape(post.next().unwrap_or("")),
+			})
+		})
+		.collect()
+}
+
+pub fn read_mounts() -> Result<Vec<MountInfo>> {
+	let text = fs::read_to_string("/proc/self/mountinfo").context("Failed to read /proc/self/mountinfo")?;
+	Ok(parse_mountinfo(&text))
+}
+
+/// The mount that contains `path`: the longest matching mount point.
+pub fn mount_of<'a>(mounts: &'a [MountInfo], path: &Path) -> Option<&'a MountInfo> {
+	// later entries shadow earlier ones at the same mount point
+	mounts.iter().rev().filter(|m| path.starts_with(&m.mount_point)).max_by_key(|m| m.mount_point.as_os_str().len())
+}
+
+/// Filesystems where inotify misses changes made by other hosts or by the FUSE daemon.
+/// `fuseblk` is excluded: it backs local block devices (ntfs-3g, exfat-fuse) written through this kernel.
+pub fn needs_polling(fs_type: &str) -> bool {
+	matches!(fs_type, "cifs" | "smb3" | "smbfs" | "9p" | "afs" | "ceph" | "glusterfs" | "lustre" | "davfs" | "fuse")
+		|| fs_type.starts_with("nfs")
+		|| fs_type.starts_with("fuse.")
+}
+
+#[derive(Clone, Debug)]
+pub struct MountScanOptions {
+	/// A new mount is scanned when its filesystem type is listed here...
+	pub fs_types: Vec<String>,
+	/// ...or it is mounted below one of these directories.
+	pub mount_prefixes: Vec<PathBuf>,
+	/// Scanning stops after this many bytes of file content.
+	pub max_bytes: u64,
+	pub max_duration: Duration,
+	/// Wait this long after the mount appears so automounters can finish.
+	pub settle: Duration,
+	pub scan_options: ScanOptions,
+}
+
+impl Default for MountScanOptions {
+	fn default() -> Self {
+		Self {
+			fs_types: ["vfat", "exfat", "ntfs", "ntfs3", "fuseblk", "iso9660", "udf", "hfsplus"].map(String::from).to_vec(),
+			// where udisks and friends put removable media
+			mount_prefixes: vec![PathBuf::from("/media"), PathBuf::from("/run/media")],
+			max_bytes: 4 * 1024 * 1024 * 1024,
+			max_duration: Duration::from_secs(5 * 60),
+			settle: Duration::from_secs(1),
+			scan_options: ScanOptions::default(),
+		}
+	}
+}
+
+impl MountScanOptions {
+	pub fn wants(&self, mount: &MountInfo) -> bool {
+		self.fs_types.iter().any(|t| t == &mount.fs_type) || self.mount_prefixes.iter().any(|p| mount.mount_point.starts_with(p))
+	}
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MountScanReport {
+	pub mount_point: PathBuf,
+	pub fs_type: String,
+	pub files_scanned: u64,
+	pub bytes_scanned: u64,
+	pub detections: usize,
+	/// The size or time budget ran out before the whole mount was scanned.
+	pub truncated: bool,
+	pub elapsed: Duration,
+}
+
+// Programs a shortcut has no business launching from a USB stick.
+const LNK_LAUNCHERS: &[&str] = &["cmd.exe", "powershell", "pwsh", "rundll32", "wscript", "cscript", "mshta", "regsvr32", "%comspec%", "certutil", "bitsadmin"];
+
+fn heuristic(path: &Path, severity: u8, description: String) -> Detection {
+	Detection { path: path.to_path_buf(), kind: DetectionKind::Heuristic { description }, severity, sha256: None, config: None, iocs: Vec::new(), member: None }
+}
+
+/// Flags an `autorun.inf` that starts a program when the medium is opened.
+pub fn check_autorun(path: &Path) -> Option<Detection> {
+	let text = fs::read(path).ok()?;
+	let text = String::from_utf8_lossy(&text);
+	let mut in_autorun = false;
+	for line in text.lines().map(str::trim) {
+		if line.starts_with('[') {
+			in_autorun = line.eq_ignore_ascii_case("[autorun]");
+			continue;
+		}
+		if !in_autorun { continue; }
+		let Some((key, value)) = line.split_once('=') else { continue };
+		let key = key.trim().to_ascii_lowercase();
+		if key == "open" || key == "shellexecute" || (key.starts_with("shell\\") && key.ends_with("\\command")) {
+			return Some(heuristic(path, 7, format!("autorun.inf launches {}", value.trim())));
+		}
+	}
+	None
+}
+
+// ASCII and UTF-16LE lowercase text of a shortcut, enough to find its target and arguments.
+fn lnk_strings(data: &[u8]) -> String {
+	let ascii: String = data.iter().map(|b| b.to_ascii_lowercase() as char).collect();
+	let wide: String = data.chunks_exact(2).filter(|c| c[1] == 0).map(|c| c[0].to_ascii_lowercase() as char).collect();
+	ascii + "\n" + &wide
+}
+
+/// Flags a Windows shortcut that runs a script host or shell, the usual USB-worm trick.
+pub fn check_lnk(path: &Path) -> Option<Detection> {
+	let data = fs::read(path).ok()?;
+	// HeaderSize 0x4C followed by the ShellLink CLSID
+	if data.len() < 0x4c || data[..4] != [0x4c, 0, 0, 0] || data[4..8] != [0x01, 0x14, 0x02, 0x00] { return None; }
+	let text = lnk_strings(&data);
+	let launcher = LNK_LAUNCHERS.iter().find(|l| text.contains(*l))?;
+	let stem = path.file_stem().unwrap_or_default();
+	let disguised = path.parent().is_some_and(|dir| dir.join(stem).is_dir());
+	let description = if disguised {
+		format!("Shortcut disguised as folder {} launches {}", stem.to_string_lossy(), launcher)
+	} else {
+		format!("Shortcut launches {}", launcher)
+	};
+	Some(heuristic(path, 8, description))
+}
+
+/// Scans one mount within the byte and time budget, sending detections to `tx_detect`.
+pub fn scan_mount(mount: &MountInfo, scanner: &Scanner, opts: &MountScanOptions, tx_detect: &Sender<Detection>, stop: &AtomicBool) -> MountScanReport {
+	let start = Instant::now();
+	let mut report = MountScanReport { mount_point: mount.mount_point.clone(), fs_type: mount.fs_type.clone(), ..Default::default() };
+	let send = |d: Detection, report: &mut MountScanReport| {
+		report.detections += 1;
+		let _ = tx_detect.send(d);
+	};
+	for entry in WalkDir::new(&mount.mount_point).same_file_system(true).follow_links(false).into_iter().filter_map(Result::ok) {
+		if stop.load(Ordering::Relaxed) || start.elapsed() > opts.max_duration || report.bytes_scanned >= opts.max_bytes {
+			report.truncated = true;
+			break;
+		}
+		if !entry.file_type().is_file() { continue; }
+		let path = entry.path();
+		let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+		// autorun.inf only acts at the root of the medium
+		if name == "autorun.inf" && entry.depth() == 1 {
+			if let Some(d) = check_autorun(path) { send(d, &mut report); }
+		}
+		if name.ends_with(".lnk") {
+			if let Some(d) = check_lnk(path) { send(d, &mut report); }
+		}
+		let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+		if size > opts.scan_options.max_file_size_bytes { continue; }
+		for d in scanner.scan_paths(&[path], &opts.scan_options) { send(d, &mut report); }
+		report.files_scanned += 1;
+		report.bytes_scanned += size;
+	}
+	report.elapsed = start.elapsed();
+	log::info!(
+		"Scanned mount {} ({}): {} files, {} bytes, {} detections{}",
+		report.mount_point.display(), report.fs_type, report.files_scanned, report.bytes_scanned, report.detections,
+		if report.truncated { ", budget exhausted" } else { "" }
+	);
+	report
+}
+
+/// Running mount monitor; dropping it stops watching and abandons scans in progress.
+pub struct MountGuard {
+	stop: Arc<AtomicBool>,
+	join: Option<thread::JoinHandle<()>>,
+}
+
+impl MountGuard {
+	pub fn stop(&mut self) {
+		let Some(join) = self.join.take() else { return };
+		self.stop.store(true, Ordering::Relaxed);
+		if join.join().is_err() { log::warn!("Mount monitor thread panicked"); }
+	}
+}
+
+impl Drop for MountGuard {
+	fn drop(&mut self) {
+		self.stop();
+	}
+}
+
+/// Watches `/proc/self/mountinfo` and scans filesystems mounted after the call that match
+/// `opts`. Each mount is scanned on its own thread; `on_report` receives the summaries.
+#[cfg(target_os = "linux")]
+pub fn start_mount_monitor(opts: MountScanOptions, scanner: Arc<Scanner>, tx_detect: Sender<Detection>, on_report: Option<Sender<MountScanReport>>) -> Result<MountGuard> {
+	use std::os::fd::AsRawFd;
+
+	let file = fs::File::open("/proc/self/mountinfo").context("Failed to open /proc/self/mountinfo")?;
+	let mut known: Vec<u32> = read_mounts()?.iter().map(|m| m.mount_id).collect();
+	let stop = Arc::new(AtomicBool::new(false));
+	let thread_stop = stop.clone();
+	let join = thread::spawn(move || {
+		let mut scans: Vec<thread::JoinHandle<()>> = Vec::new();
+		while !thread_stop.load(Ordering::Relaxed) {
+			// the kernel flags mountinfo with POLLPRI whenever the mount table changes
+			let mut pfd = libc::pollfd { fd: file.as_raw_fd(), events: libc::POLLPRI, revents: 0 };
+			if unsafe { libc::poll(&mut pfd, 1, 250) } <= 0 { continue; }
+			let mounts = match read_mounts() {
+				Ok(m) => m,
+				Err(e) => {
+					log::warn!("{:#}", e);
+					continue;
+				}
+			};
+			for m in mounts.iter().filter(|m| !known.contains(&m.mount_id) && opts.wants(m)) {
+				log::info!("New mount {} ({}) from {}", m.mount_point.display(), m.fs_type, m.source);
+				let (m, opts, scanner, tx, stop, on_report) = (m.clone(), opts.clone(), scanner.clone(), tx_detect.clone(), thread_stop.clone(), on_report.clone());
+				scans.push(thread::spawn(move || {
+					thread::sleep(opts.settle);
+					let report = scan_mount(&m, &scanner, &opts, &tx, &stop);
+					if let Some(r) = on_report { let _ = r.send(report); }
+				}));
+			}
+			known = mounts.iter().map(|m| m.mount_id).collect();
+			scans.retain(|j| !j.is_finished());
+		}
+		for j in scans {
+			let _ = j.join();
+		}
+	});
+	Ok(MountGuard { stop, join: Some(join) })
+}
@@ -0,0 +1,439 @@
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::thread;
+use wib_core::quarantine::app_data_dir;
+use wib_core::{Detection, DetectionKind, Ioc, IocKind};
+
+use crate::firewall;
+use crate::netmon::{NetEvent, NetEventKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedFormat {
+	/// One indicator per line, `#` or `;` comments.
+	Plain,
+	/// Header-aware CSV such as the abuse.ch Feodo/ThreatFox exports.
+	Csv,
+	/// abuse.ch Feodo Tracker `ipblocklist.json`.
+	FeodoJson,
+	/// abuse.ch ThreatFox export or API response.
+	ThreatFoxJson,
+	/// `0.0.0.0 evil.example` style blocklists.
+	Hosts,
+}
+
+impl FeedFormat {
+	/// Guesses the format from the first meaningful line.
+	pub fn detect(text: &str) -> Self {
+		let trimmed = text.trim_start();
+		if trimmed.starts_with('[') { return FeedFormat::FeodoJson; }
+		if trimmed.starts_with('{') { return FeedFormat::ThreatFoxJson; }
+		let header = text.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with(';'));
+		let commented_header = text.lines().any(|l| l.starts_with('#') && l.contains("\",\""));
+		match header {
+			_ if commented_header => FeedFormat::Csv,
+			Some(l) if l.contains(',') => FeedFormat::Csv,
+			Some(l) if is_hosts_line(l) => FeedFormat::Hosts,
+			_ => FeedFormat::Plain,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndicatorKind {
+	Ip,
+	Cidr,
+	Domain,
+	Url,
+}
+
+/// Where an indicator came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IocSource {
+	pub feed: String,
+	/// Malware family or threat label, when the feed has one.
+	pub threat: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IocMatch {
+	pub kind: IndicatorKind,
+	/// The listed indicator, e.g. `198.51.100.0/24` for an address inside it.
+	pub indicator: String,
+	pub source: IocSource,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedReport {
+	pub feed: String,
+	pub file: Option<PathBuf>,
+	pub format: Option<FeedFormat>,
+	pub imported: usize,
+	/// Lines or records that held no usable indicator.
+	pub skipped: usize,
+}
+
+/// IPs, CIDRs, domains and URLs from local threat-intel feeds.
+#[derive(Debug, Default)]
+pub struct IocStore {
+	ips: HashMap<IpAddr, IocSource>,
+	cidrs: Vec<(IpAddr, u8, IocSource)>,
+	domains: HashMap<String, IocSource>,
+	urls: HashMap<String, IocSource>,
+}
+
+impl IocStore {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Loads every file in the deployment's `feeds` directory; a missing directory is an empty store.
+	pub fn load_default() -> Result<(Self, Vec<FeedReport>)> {
+		let mut store = Self::new();
+		let dir = app_data_dir().join("feeds");
+		let reports = if dir.is_dir() { store.load_dir(&dir)? } else { Vec::new() };
+		Ok((store, reports))
+	}
+
+	pub fn load_dir(&mut self, dir: &Path) -> Result<Vec<FeedReport>> {
+		let mut files: Vec<PathBuf> = fs::read_dir(dir)
+			.with_context(|| format!("Failed to read {}", dir.display()))?
+			.filter_map(|e| e.ok().map(|e| e.path()))
+			.filter(|p| p.is_file())
+			.collect();
+		files.sort();
+		files.iter().map(|p| self.load_file(p, None)).collect()
+	}
+
+	/// Ingests a feed file named after its stem; `format` is detected when `None`.
+	pub fn load_file(&mut self, path: &Path, format: Option<FeedFormat>) -> Result<FeedReport> {
+		let raw = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+		let text = String::from_utf8_lossy(&raw);
+		let feed = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| path.display().to_string());
+		let mut report = self.load_str(&feed, &text, format).with_context(|| format!("Failed to parse feed {}", path.display()))?;
+		report.file = Some(path.to_path_buf());
+		Ok(report)
+	}
+
+	pub fn load_str(&mut self, feed: &str, text: &str, format: Option<FeedFormat>) -> Result<FeedReport> {
+		let format = format.unwrap_or_else(|| FeedFormat::detect(text));
+		let entries = match format {
+			FeedFormat::Plain => parse_plain(text),
+			FeedFormat::Csv => parse_csv(text),
+			FeedFormat::Hosts => parse_hosts(text),
+			FeedFormat::FeodoJson | FeedFormat::ThreatFoxJson => {
+				let json: Value = serde_json::from_str(text).context("Invalid JSON feed")?;
+				parse_json(&json)
+			}
+		};
+		let mut report = FeedReport { feed: feed.to_string(), format: Some(format), ..Default::default() };
+		for (value, threat) in entries {
+			let source = IocSource { feed: feed.to_string(), threat };
+			if self.insert(&value, source) { report.imported += 1 } else { report.skipped += 1 }
+		}
+		Ok(report)
+	}
+
+	/// Adds one indicator; false if `value` is not an IP, CIDR, domain or URL.
+	pub fn insert(&mut self, value: &str, source: IocSource) -> bool {
+		let Some((kind, normalized)) = parse_indicator(value) else { return false };
+		match kind {
+			IndicatorKind::Ip => { self.ips.entry(normalized.parse().unwrap()).or_insert(source); }
+			IndicatorKind::Cidr => {
+				let (net, prefix) = parse_cidr(&normalized).unwrap();
+				if !self.cidrs.iter().any(|(n, p, _)| *n == net && *p == prefix) { self.cidrs.push((net, prefix, source)); }
+			}
+			IndicatorKind::Domain => { self.domains.entry(normalized).or_insert(source); }
+			IndicatorKind::Url => { self.urls.entry(normalized).or_insert(source); }
+		}
+		true
+	}
+
+	pub fn len(&self) -> usize {
+		self.ips.len() + self.cidrs.len() + self.domains.len() + self.urls.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Exact address first, then the narrowest listed network containing it.
+	pub fn match_ip(&self, ip: IpAddr) -> Option<IocMatch> {
+		let ip = canonical_ip(ip);
+		if let Some(source) = self.ips.get(&ip) {
+			return Some(IocMatch { kind: IndicatorKind::Ip, indicator: ip.to_string(), source: source.clone() });
+		}
+		self.cidrs
+			.iter()
+			.filter(|(net, prefix, _)| in_network(ip, *net, *prefix))
+			.max_by_key(|(_, prefix, _)| *prefix)
+			.map(|(net, prefix, source)| IocMatch { kind: IndicatorKind::Cidr, indicator: format!("{}/{}", net, prefix), source: source.clone() })
+	}
+
+	/// Matches the domain or any parent of it, so `a.evil.example` hits a listed `evil.example`.
+	pub fn match_domain(&self, domain: &str) -> Option<IocMatch> {
+		let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+		if let Ok(ip) = domain.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() { return self.match_ip(ip); }
+		let mut candidate = domain.as_str();
+		loop {
+			if let Some(source) = self.domains.get(candidate) {
+				return Some(IocMatch { kind: IndicatorKind::Domain, indicator: candidate.to_string(), source: source.clone() });
+			}
+			candidate = candidate.split_once('.')?.1;
+		}
+	}
+
+	/// Exact URL, falling back to its host.
+	pub fn match_url(&self, url: &str) -> Option<IocMatch> {
+		let (_, normalized) = parse_indicator(url).filter(|(k, _)| *k == IndicatorKind::Url)?;
+		if let Some(source) = self.urls.get(&normalized) {
+			return Some(IocMatch { kind: IndicatorKind::Url, indicator: normalized, source: source.clone() });
+		}
+		self.match_domain(url_host(&normalized)?)
+	}
+
+	/// Checks the remote end of a newly opened connection.
+	pub fn match_event(&self, event: &NetEvent) -> Option<(IocMatch, Detection)> {
+		if event.kind != NetEventKind::Opened { return None; }
+		let c = event.connection.as_ref()?;
+		let remote = c.remote.ip();
+		if c.remote.port() == 0 || remote.is_unspecified() { return None; }
+		let m = self.match_ip(remote)?;
+		let who = match (&c.process, c.pid) {
+			(Some(name), Some(pid)) => format!("{} (pid {})", name, pid),
+			(None, Some(pid)) => format!("pid {}", pid),
+			_ => "unknown process".into(),
+		};
+		let threat = m.source.threat.as_ref().map(|t| format!(", {}", t)).unwrap_or_default();
+		let description = format!("Connection to {} listed as {} in feed {}{} by {}", c.remote, m.indicator, m.source.feed, threat, who);
+		let kind = if remote.is_ipv4() { IocKind::Ipv4 } else { IocKind::Ipv6 };
+		let detection = Detection {
+			path: c.exe.clone().or_else(|| c.process.as_ref().map(PathBuf::from)).unwrap_or_default(),
+			kind: DetectionKind::Heuristic { description },
+			// a listed destination alone does not convict the process; stays below the quarantine threshold
+			severity: 7,
+			sha256: None,
+			config: None,
+			iocs: vec![Ioc { kind, value: remote.to_string() }],
+			member: None,
+		};
+		Some((m, detection))
+	}
+}
+
+/// Matches netmon events against `store`, raising one detection per (process, remote address) and
+/// optionally blocking each matched address with [`firewall::block_ip`].
+pub fn spawn_ioc_matcher(store: IocStore, events: Receiver<NetEvent>, tx: Sender<Detection>, block_ips: bool) -> thread::JoinHandle<()> {
+	thread::spawn(move || {
+		let mut seen: HashSet<(Option<PathBuf>, Option<String>, IpAddr)> = HashSet::new();
+		let mut blocked: HashSet<IpAddr> = HashSet::new();
+		for event in events {
+			let Some((_, detection)) = store.match_event(&event) else { continue };
+			let c = event.connection.as_ref().unwrap();
+			let remote = c.remote.ip();
+			if !seen.insert((c.exe.clone(), c.process.clone(), remote)) { continue; }
+			if block_ips && blocked.insert(remote) {
+				if let Err(e) = firewall::block_ip(&remote.to_string()) { log::warn!("Failed to block {}: {:#}", remote, e); }
+			}
+			if tx.send(detection).is_err() { break; }
+		}
+	})
+}
+
+fn strip_comment(line: &str) -> &str {
+	let line = line.split(" #").next().unwrap_or(line);
+	line.trim()
+}
+
+fn parse_plain(text: &str) -> Vec<(String, Option<String>)> {
+	text.lines()
+		.map(strip_comment)
+		.filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with(';'))
+		.map(|l| (l.split_whitespace().next().unwrap_or(l).to_string(), None))
+		.collect()
+}
+
+fn is_hosts_line(line: &str) -> bool {
+	let mut parts = line.split_whitespace();
+	matches!(parts.next(), Some("0.0.0.0" | "127.0.0.1" | "::" | "::1")) && parts.next().is_some()
+}
+
+const HOSTS_LOCAL_NAMES: [&str; 6] = ["localhost", "localhost.localdomain", "local", "broadcasthost", "ip6-localhost", "ip6-loopback"];
+
+fn parse_hosts(text: &str) -> Vec<(String, Option<String>)> {
+	let mut out = Vec::new();
+	for line in text.lines().map(|l| l.split('#').next().unwrap_or("").trim()) {
+		if line.is_empty() { continue; }
+		let mut parts = line.split_whitespace();
+		let _sink = parts.next();
+		for name in parts {
+			if HOSTS_LOCAL_NAMES.contains(&name) { continue; }
+			out.push((name.to_string(), None));
+		}
+	}
+	out
+}
+
+// Column names holding the indicator / threat label across common CSV exports.
+const VALUE_COLUMNS: [&str; 9] = ["ioc_value", "ioc", "dst_ip", "ip_address", "indicator", "url", "domain", "ip", "value"];
+const THREAT_COLUMNS: [&str; 5] = ["malware_printable", "malware", "threat", "family", "tags"];
+
+fn split_csv(line: &str) -> Vec<String> {
+	let mut fields = Vec::new();
+	let mut field = String::new();
+	let mut quoted = false;
+	let mut chars = line.chars().peekable();
+	while let Some(ch) = chars.next() {
+		match ch {
+			'"' if quoted && chars.peek() == Some(&'"') => { field.push('"'); chars.next(); }
+			'"' => quoted = !quoted,
+			',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+			_ => field.push(ch),
+		}
+	}
+	fields.push(field.trim().to_string());
+	fields
+}
+
+/// Indicator and threat column indexes, if `fields` is a header row.
+fn csv_header(fields: &[String]) -> Option<(usize, Option<usize>)> {
+	let lower: Vec<String> = fields.iter().map(|f| f.to_ascii_lowercase()).collect();
+	let value = VALUE_COLUMNS.iter().find_map(|c| lower.iter().position(|f| f == c))?;
+	Some((value, THREAT_COLUMNS.iter().find_map(|c| lower.iter().position(|f| f == c))))
+}
+
+fn parse_csv(text: &str) -> Vec<(String, Option<String>)> {
+	let mut header = None;
+	let mut out = Vec::new();
+	for line in text.lines().map(|l| l.trim_end_matches('\r')) {
+		if line.trim().is_empty() { continue; }
+		if let Some(comment) = line.strip_prefix('#') {
+			// abuse.ch exports put the header in a comment
+			if comment.contains(',') { header = csv_header(&split_csv(comment.trim())).or(header); }
+			continue;
+		}
+		let fields = split_csv(line);
+		if header.is_none() && out.is_empty() {
+			header = csv_header(&fields);
+			if header.is_some() { continue; }
+		}
+		let value = match header {
+			Some((i, _)) => fields.get(i).cloned(),
+			None => fields.iter().find(|f| parse_indicator(f).is_some()).cloned(),
+		};
+		let threat = header.and_then(|(_, t)| fields.get(t?)).filter(|t| !t.is_empty() && *t != "None").cloned();
+		out.push((value.unwrap_or_default(), threat));
+	}
+	out
+}
+
+fn json_str<'a>(v: &'a Value, keys: &[&str]) -> Option<&'a str> {
+	keys.iter().find_map(|k| v.get(k).and_then(Value::as_str)).filter(|s| !s.is_empty())
+}
+
+/// Feodo `ipblocklist.json` (array of objects), ThreatFox exports (`{"id": [record]}`) and API
+/// responses (`{"data": [record]}`) all reduce to records with an indicator and a family.
+fn parse_json(json: &Value) -> Vec<(String, Option<String>)> {
+	let records: Vec<&Value> = match json {
+		Value::Array(items) => items.iter().collect(),
+		Value::Object(map) => match map.get("data") {
+			Some(Value::Array(items)) => items.iter().collect(),
+			_ => map.values().flat_map(|v| match v { Value::Array(items) => items.iter().collect(), other => vec![other] }).collect(),
+		},
+		_ => Vec::new(),
+	};
+	records
+		.into_iter()
+		.map(|r| {
+			// file hashes are for the scanner, not the network
+			let hash = json_str(r, &["ioc_type"]).is_some_and(|t| t.ends_with("_hash"));
+			let value = json_str(r, &["ioc_value", "ioc", "ip_address", "dst_ip", "url", "domain"]).filter(|_| !hash).unwrap_or_default().to_string();
+			let threat = json_str(r, &["malware_printable", "malware", "threat_type"]).map(String::from);
+			(value, threat)
+		})
+		.collect()
+}
+
+fn refang(value: &str) -> String {
+	value.trim().trim_matches('"').replace("[.]", ".").replace("(.)", ".").replace("[:]", ":").replacen("hxxp", "http", 1)
+}
+
+/// Classifies and normalizes one indicator; `ip:port` forms reduce to the address.
+pub fn parse_indicator(value: &str) -> Option<(IndicatorKind, String)> {
+	let value = refang(value);
+	if value.is_empty() { return None; }
+	if let Some((scheme, rest)) = value.split_once("://") {
+		if scheme.is_empty() || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+') || rest.is_empty() { return None; }
+		let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+		let path = path.trim_end_matches('/');
+		return Some((IndicatorKind::Url, format!("{}://{}{}", scheme.to_ascii_lowercase(), host.to_ascii_lowercase(), path)));
+	}
+	if let Ok(ip) = value.parse::<IpAddr>() { return Some((IndicatorKind::Ip, canonical_ip(ip).to_string())); }
+	if let Ok(sock) = value.parse::<SocketAddr>() { return Some((IndicatorKind::Ip, canonical_ip(sock.ip()).to_string())); }
+	if let Some((net, prefix)) = parse_cidr(&value) {
+		if prefix == max_prefix(net) { return Some((IndicatorKind::Ip, net.to_string())); }
+		return Some((IndicatorKind::Cidr, format!("{}/{}", net, prefix)));
+	}
+	let host = value.rsplit_once(':').filter(|(_, port)| port.parse::<u16>().is_ok()).map_or(value.as_str(), |(h, _)| h);
+	let host = host.trim_end_matches('.').trim_start_matches("*.").to_ascii_lowercase();
+	let labels: Vec<&str> = host.split('.').collect();
+	let valid_label = |l: &&str| !l.is_empty() && l.len() <= 63 && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+	let tld_ok = labels.last().is_some_and(|t| t.len() >= 2 && t.chars().all(|c| c.is_ascii_alphabetic() || c == '-'));
+	if labels.len() >= 2 && labels.iter().all(valid_label) && tld_ok { return Some((IndicatorKind::Domain, host)); }
+	None
+}
+
+fn url_host(url: &str) -> Option<&str> {
+	let rest = url.split_once("://")?.1;
+	let authority = rest.split('/').next()?;
+	let authority = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+	if authority.starts_with('[') { return authority.split(']').next().map(|h| h.trim_start_matches('[')); }
+	Some(authority.split(':').next().unwrap_or(authority))
+}
+
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+	match ip {
+		IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+		v4 => v4,
+	}
+}
+
+fn max_prefix(ip: IpAddr) -> u8 {
+	if ip.is_ipv4() { 32 } else { 128 }
+}
+
+/// `198.51.100.7/24` parses as the network `198.51.100.0` with prefix 24.
+fn parse_cidr(value: &str) -> Option<(IpAddr, u8)> {
+	let (addr, prefix) = value.split_once('/')?;
+	let ip = canonical_ip(addr.parse().ok()?);
+	let prefix: u8 = prefix.parse().ok()?;
+	if prefix > max_prefix(ip) { return None; }
+	let net = match ip {
+		IpAddr::V4(v4) => IpAddr::V4((u32::from(v4) & mask32(prefix)).into()),
+		IpAddr::V6(v6) => IpAddr::V6((u128::from(v6) & mask128(prefix)).into()),
+	};
+	Some((net, prefix))
+}
+
+fn mask32(prefix: u8) -> u32 {
+	if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) }
+}
+
+fn mask128(prefix: u8) -> u128 {
+	if prefix == 0 { 0 } else { u128::MAX << (128 - prefix) }
+}
+
+fn in_network(ip: IpAddr, net: IpAddr, prefix: u8) -> bool {
+	match (ip, net) {
+		(IpAddr::V4(a), IpAddr::V4(n)) => u32::from(a) & mask32(prefix) == u32::from(n),
+		(IpAddr::V6(a), IpAddr::V6(n)) => u128::from(a) & mask128(prefix) == u128::from(n),
+		_ => false,
+	}
+}
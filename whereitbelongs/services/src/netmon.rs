@@ -1,24 +1,345 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+	Tcp,
+	Udp,
+}
+
+/// One socket from the kernel's connection table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Connection {
+	pub protocol: Protocol,
+	pub local: SocketAddr,
+	/// Unspecified (`0.0.0.0:0`) for listening and unconnected sockets.
+	pub remote: SocketAddr,
+	/// TCP state such as `ESTABLISHED` or `LISTEN`; `UNCONN` for unconnected UDP.
+	pub state: String,
+	pub uid: u32,
+	pub inode: u64,
+	pub pid: Option<u32>,
+	pub process: Option<String>,
+	pub exe: Option<PathBuf>,
+	/// TCP payload bytes acknowledged by the peer, as of the last poll.
+	#[serde(default)]
+	pub bytes_sent: Option<u64>,
+	#[serde(default)]
+	pub bytes_received: Option<u64>,
+}
+
+impl Connection {
+	fn key(&self) -> (Protocol, SocketAddr, SocketAddr, u64) {
+		(self.protocol, self.local, self.remote, self.inode)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetEventKind {
+	Opened,
+	Closed,
+	/// Liveness only; sent where no connection table is available.
+	Heartbeat,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetEvent {
 	pub timestamp_ms: i64,
 	pub description: String,
+	#[serde(default = "heartbeat")]
+	pub kind: NetEventKind,
+	#[serde(default)]
+	pub connection: Option<Connection>,
+}
+
+fn heartbeat() -> NetEventKind {
+	NetEventKind::Heartbeat
+}
+
+impl NetEvent {
+	fn for_connection(kind: NetEventKind, c: Connection) -> Self {
+		let who = match (&c.process, c.pid) {
+			(Some(name), Some(pid)) => format!("{} ({})", name, pid),
+			(None, Some(pid)) => format!("pid {}", pid),
+			_ => "unknown process".into(),
+		};
+		let description = format!("{:?} {:?} {} -> {} [{}] by {}", kind, c.protocol, c.local, c.remote, c.state, who);
+		Self { timestamp_ms: chrono::Utc::now().timestamp_millis(), description, kind, connection: Some(c) }
+	}
 }
 
+/// Running monitor; dropping it stops the polling thread.
 pub struct NetworkMonitor {
-	_join: thread::JoinHandle<()>,
+	stop: Arc<AtomicBool>,
+	join: Option<thread::JoinHandle<()>>,
+}
+
+impl NetworkMonitor {
+	pub fn stop(&mut self) {
+		let Some(join) = self.join.take() else { return };
+		self.stop.store(true, Ordering::Relaxed);
+		if join.join().is_err() { log::warn!("Network monitor thread panicked"); }
+	}
+}
+
+impl Drop for NetworkMonitor {
+	fn drop(&mut self) {
+		self.stop();
+	}
 }
 
 pub fn start(tx: crossbeam_channel::Sender<NetEvent>) -> NetworkMonitor {
+	start_with_interval(Duration::from_secs(2), tx)
+}
+
+/// Polls the connection table every `interval` and reports sockets that appeared or went away.
+/// Sockets already open at start are reported as `Opened` on the first poll.
+pub fn start_with_interval(interval: Duration, tx: crossbeam_channel::Sender<NetEvent>) -> NetworkMonitor {
+	let stop = Arc::new(AtomicBool::new(false));
+	let thread_stop = stop.clone();
 	let handle = thread::spawn(move || {
-		loop {
-			let ts = chrono::Utc::now().timestamp_millis();
-			let _ = tx.send(NetEvent { timestamp_ms: ts, description: "Heartbeat".into() });
-			thread::sleep(Duration::from_secs(5));
+		let mut known: HashMap<(Protocol, SocketAddr, SocketAddr, u64), Connection> = HashMap::new();
+		while !thread_stop.load(Ordering::Relaxed) {
+			match connections() {
+				Ok(current) => {
+					let mut seen = HashMap::with_capacity(current.len());
+					for c in current {
+						if !known.contains_key(&c.key()) && tx.send(NetEvent::for_connection(NetEventKind::Opened, c.clone())).is_err() { return; }
+						seen.insert(c.key(), c);
+					}
+					for (key, c) in known.drain() {
+						if !seen.contains_key(&key) && tx.send(NetEvent::for_connection(NetEventKind::Closed, c)).is_err() { return; }
+					}
+					known = seen;
+				}
+				Err(_) => {
+					let ts = chrono::Utc::now().timestamp_millis();
+					if tx.send(NetEvent { timestamp_ms: ts, description: "Heartbeat".into(), kind: NetEventKind::Heartbeat, connection: None }).is_err() { return; }
+				}
+			}
+			// sleep in slices so stop() returns promptly
+			let mut slept = Duration::ZERO;
+			while slept < interval && !thread_stop.load(Ordering::Relaxed) {
+				let step = (interval - slept).min(Duration::from_millis(100));
+				thread::sleep(step);
+				slept += step;
+			}
 		}
 	});
-	NetworkMonitor { _join: handle }
-}
\ No newline at end of file
+	NetworkMonitor { stop, join: Some(handle) }
+}
+
+fn tcp_state(code: u8) -> &'static str {
+	match code {
+		0x01 => "ESTABLISHED",
+		0x02 => "SYN_SENT",
+		0x03 => "SYN_RECV",
+		0x04 => "FIN_WAIT1",
+		0x05 => "FIN_WAIT2",
+		0x06 => "TIME_WAIT",
+		0x07 => "CLOSE",
+		0x08 => "CLOSE_WAIT",
+		0x09 => "LAST_ACK",
+		0x0A => "LISTEN",
+		0x0B => "CLOSING",
+		0x0C => "NEW_SYN_RECV",
+		_ => "UNKNOWN",
+	}
+}
+
+// `0100007F:0277`: the kernel prints each 32-bit word of the network-order address as a native
+// integer, so the word's native byte order restores the octets; the port is plain hex.
+fn parse_endpoint(s: &str) -> Option<SocketAddr> {
+	let (addr, port) = s.split_once(':')?;
+	let port = u16::from_str_radix(port, 16).ok()?;
+	let word = |i: usize| u32::from_str_radix(addr.get(i * 8..i * 8 + 8)?, 16).ok().map(u32::to_ne_bytes);
+	let ip = match addr.len() {
+		8 => IpAddr::V4(Ipv4Addr::from(word(0)?)),
+		32 => {
+			let mut octets = [0u8; 16];
+			for i in 0..4 {
+				octets[i * 4..i * 4 + 4].copy_from_slice(&word(i)?);
+			}
+			IpAddr::V6(Ipv6Addr::from(octets))
+		}
+		_ => return None,
+	};
+	Some(SocketAddr::new(ip, port))
+}
+
+/// Parses `/proc/net/{tcp,tcp6,udp,udp6}` text. Sockets without an inode (TIME_WAIT leftovers)
+/// are skipped; `pid`, `process` and `exe` are left empty.
+pub fn parse_proc_net(text: &str, protocol: Protocol) -> Vec<Connection> {
+	text.lines()
+		.skip(1)
+		.filter_map(|line| {
+			let f: Vec<&str> = line.split_whitespace().collect();
+			if f.len() < 10 { return None; }
+			let inode: u64 = f[9].parse().ok()?;
+			if inode == 0 { return None; }
+			let code = u8::from_str_radix(f[3], 16).ok()?;
+			let state = match (protocol, code) {
+				(Protocol::Udp, 0x07) => "UNCONN",
+				_ => tcp_state(code),
+			};
+			Some(Connection {
+				protocol,
+				local: parse_endpoint(f[1])?,
+				remote: parse_endpoint(f[2])?,
+				state: state.to_string(),
+				uid: f[7].parse().ok()?,
+				inode,
+				pid: None,
+				process: None,
+				exe: None,
+				bytes_sent: None,
+				bytes_received: None,
+			})
+		})
+		.collect()
+}
+
+/// Current sockets with their owning process, where `/proc/<pid>/fd` is readable.
+#[cfg(target_os = "linux")]
+pub fn connections() -> anyhow::Result<Vec<Connection>> {
+	use anyhow::Context;
+
+	let mut all = Vec::new();
+	for (file, protocol) in [("tcp", Protocol::Tcp), ("tcp6", Protocol::Tcp), ("udp", Protocol::Udp), ("udp6", Protocol::Udp)] {
+		let path = format!("/proc/net/{}", file);
+		match std::fs::read_to_string(&path) {
+			Ok(text) => all.extend(parse_proc_net(&text, protocol)),
+			// no IPv6 in this kernel
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+			Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path)),
+		}
+	}
+	let owners = socket_owners();
+	let bytes = tcp_byte_counts();
+	let mut procs: HashMap<u32, (Option<String>, Option<PathBuf>)> = HashMap::new();
+	for c in &mut all {
+		if let Some((sent, received)) = bytes.get(&c.inode).filter(|_| c.protocol == Protocol::Tcp) {
+			c.bytes_sent = Some(*sent);
+			c.bytes_received = Some(*received);
+		}
+		let Some(pid) = owners.get(&c.inode).copied() else { continue };
+		let (name, exe) = procs
+			.entry(pid)
+			.or_insert_with(|| {
+				let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok().map(|s| s.trim_end().to_string());
+				(name, std::fs::read_link(format!("/proc/{}/exe", pid)).ok())
+			})
+			.clone();
+		c.pid = Some(pid);
+		c.process = name;
+		c.exe = exe;
+	}
+	Ok(all)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn connections() -> anyhow::Result<Vec<Connection>> {
+	anyhow::bail!("Connection table is only available on Linux")
+}
+
+/// Socket inode to owning pid, from the `socket:[inode]` links in `/proc/<pid>/fd`.
+/// A socket shared by several processes maps to the lowest pid.
+#[cfg(target_os = "linux")]
+pub fn socket_owners() -> HashMap<u64, u32> {
+	let mut owners = HashMap::new();
+	let Ok(procs) = std::fs::read_dir("/proc") else { return owners };
+	let mut pids: Vec<u32> = procs.flatten().filter_map(|e| e.file_name().to_str()?.parse().ok()).collect();
+	pids.sort_unstable();
+	for pid in pids {
+		let Ok(fds) = std::fs::read_dir(format!("/proc/{}/fd", pid)) else { continue };
+		for fd in fds.flatten() {
+			let Ok(target) = std::fs::read_link(fd.path()) else { continue };
+			let Some(inode) = target.to_str().and_then(|t| t.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok()) else { continue };
+			owners.entry(inode).or_insert(pid);
+		}
+	}
+	owners
+}
+
+/// Bytes sent (acknowledged) and received per TCP socket inode, from sock_diag's `tcp_info`.
+#[cfg(target_os = "linux")]
+fn tcp_byte_counts() -> HashMap<u64, (u64, u64)> {
+	let mut counts = HashMap::new();
+	for family in [libc::AF_INET, libc::AF_INET6] {
+		if let Err(e) = dump_tcp_info(family as u8, &mut counts) { log::debug!("sock_diag dump failed: {}", e); }
+	}
+	counts
+}
+
+#[cfg(target_os = "linux")]
+fn dump_tcp_info(family: u8, counts: &mut HashMap<u64, (u64, u64)>) -> std::io::Result<()> {
+	use std::io;
+	use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+	const SOCK_DIAG_BY_FAMILY: u16 = 20;
+	const INET_DIAG_INFO: u16 = 2;
+	// struct inet_diag_msg, and the offsets of tcpi_bytes_acked / tcpi_bytes_received in struct tcp_info
+	const DIAG_MSG_LEN: usize = 72;
+	const BYTES_ACKED: usize = 120;
+	const BYTES_RECEIVED: usize = 128;
+
+	let raw = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, libc::NETLINK_SOCK_DIAG) };
+	if raw < 0 { return Err(io::Error::last_os_error()); }
+	let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+	// nlmsghdr followed by inet_diag_req_v2 asking for tcp_info on sockets in every state
+	let mut req = Vec::with_capacity(72);
+	req.extend(72u32.to_ne_bytes());
+	req.extend(SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+	req.extend(((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+	req.extend(1u32.to_ne_bytes());
+	req.extend(0u32.to_ne_bytes());
+	req.extend([family, libc::IPPROTO_TCP as u8, 1 << (INET_DIAG_INFO - 1), 0]);
+	req.extend(u32::MAX.to_ne_bytes());
+	req.extend([0u8; 48]);
+	if unsafe { libc::send(fd.as_raw_fd(), req.as_ptr() as *const libc::c_void, req.len(), 0) } < 0 { return Err(io::Error::last_os_error()); }
+
+	let u16_at = |b: &[u8], i: usize| u16::from_ne_bytes([b[i], b[i + 1]]);
+	let u32_at = |b: &[u8], i: usize| u32::from_ne_bytes(b[i..i + 4].try_into().unwrap());
+	let u64_at = |b: &[u8], i: usize| u64::from_ne_bytes(b[i..i + 8].try_into().unwrap());
+	let align = |n: usize| (n + 3) & !3;
+	let mut buf = vec![0u8; 64 * 1024];
+	loop {
+		let n = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+		if n < 0 { return Err(io::Error::last_os_error()); }
+		let data = &buf[..n as usize];
+		let mut off = 0;
+		while off + 16 <= data.len() {
+			let len = u32_at(data, off) as usize;
+			let kind = u16_at(data, off + 4);
+			if len < 16 || off + len > data.len() { return Ok(()); }
+			match kind as i32 {
+				libc::NLMSG_DONE => return Ok(()),
+				libc::NLMSG_ERROR => return Err(io::Error::from_raw_os_error(-(u32_at(data, off + 16) as i32))),
+				_ => {}
+			}
+			let msg = &data[off + 16..off + len];
+			if msg.len() >= DIAG_MSG_LEN {
+				let inode = u32_at(msg, 68) as u64;
+				let mut a = DIAG_MSG_LEN;
+				while a + 4 <= msg.len() {
+					let (alen, atype) = (u16_at(msg, a) as usize, u16_at(msg, a + 2));
+					if alen < 4 || a + alen > msg.len() { break; }
+					if atype == INET_DIAG_INFO && alen - 4 >= BYTES_RECEIVED + 8 {
+						let info = &msg[a + 4..a + alen];
+						// the acked count includes the SYN
+						counts.insert(inode, (u64_at(info, BYTES_ACKED).saturating_sub(1), u64_at(info, BYTES_RECEIVED)));
+					}
+					a += align(alen);
+				}
+			}
+			off += align(len);
+		}
+	}
+}
@@ -0,0 +1,924 @@
+use anyhow::{bail, Context, Result};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use wib_core::{Detection, DetectionKind, Ioc, IocKind};
+
+use crate::netioc::{IocMatch, IocStore};
+
+const NJRAT_SPLITTER: &[u8] = b"|'|'|";
+// Remcos frames start with this magic once its RC4 layer is off (or was never configured)
+const REMCOS_MAGIC: &[u8] = &[0x24, 0x04, 0xff, 0x00];
+const REMCOS_FIELD_SEPARATOR: &[u8] = b"|\x1e\x1e\x1f|";
+// Subject/issuer CNs of the self-signed certificates the RAT builders generate
+const RAT_CERT_NAMES: [(&str, &str); 4] = [("AsyncRAT Server", "AsyncRAT"), ("Quasar Server CA", "Quasar"), ("DcRat Server", "DcRat"), ("VenomRAT Server", "VenomRAT")];
+const HTTP_METHODS: [&str; 9] = ["GET", "POST", "HEAD", "PUT", "DELETE", "OPTIONS", "PATCH", "CONNECT", "TRACE"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PcapOptions {
+	/// Reassembled bytes kept per stream direction.
+	pub max_stream_bytes: usize,
+}
+
+impl Default for PcapOptions {
+	fn default() -> Self {
+		Self { max_stream_bytes: 8 * 1024 * 1024 }
+	}
+}
+
+/// One captured frame, still link-layer encoded.
+#[derive(Debug, Clone)]
+pub struct RawPacket {
+	pub timestamp_us: i64,
+	/// pcap `LINKTYPE_*` value, e.g. 1 for Ethernet.
+	pub link_type: u32,
+	pub data: Vec<u8>,
+}
+
+/// A TCP connection with each direction reassembled in sequence order.
+#[derive(Debug, Clone)]
+pub struct ReassembledStream {
+	pub summary: StreamSummary,
+	pub to_server: Vec<u8>,
+	pub to_client: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamSummary {
+	pub client: SocketAddr,
+	pub server: SocketAddr,
+	pub first_seen_us: i64,
+	pub last_seen_us: i64,
+	pub bytes_to_server: usize,
+	pub bytes_to_client: usize,
+	/// Holes in the reassembled data, e.g. from packets the capture dropped.
+	pub gaps: usize,
+	/// `tls`, `http` or `dns` when recognized.
+	pub protocol: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateInfo {
+	pub subject_cn: Option<String>,
+	pub issuer_cn: Option<String>,
+	/// `YYYY-MM-DD`.
+	pub not_after: Option<String>,
+	pub self_signed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsSession {
+	pub client: SocketAddr,
+	pub server: SocketAddr,
+	pub sni: Option<String>,
+	/// Full JA3 string; `ja3_hash` is its MD5.
+	pub ja3: Option<String>,
+	pub ja3_hash: Option<String>,
+	pub ja3s: Option<String>,
+	pub ja3s_hash: Option<String>,
+	/// Leaf certificate, when the handshake sent it in the clear (TLS 1.2 and older).
+	pub certificate: Option<CertificateInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsQuery {
+	pub name: String,
+	/// `A`, `AAAA`, ... or the numeric type.
+	pub query_type: String,
+	pub answers: Vec<IpAddr>,
+	pub first_seen_us: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpRequest {
+	pub client: SocketAddr,
+	pub server: SocketAddr,
+	pub method: String,
+	pub host: Option<String>,
+	pub path: String,
+	pub user_agent: Option<String>,
+}
+
+impl HttpRequest {
+	pub fn url(&self) -> String {
+		// proxy requests already carry the absolute URL
+		if self.path.starts_with("http://") { return self.path.clone(); }
+		let host = self.host.clone().unwrap_or_else(|| self.server.to_string());
+		format!("http://{}{}", host, self.path)
+	}
+}
+
+/// A RAT protocol marker seen in one stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcapFinding {
+	pub family: Option<String>,
+	/// Short marker id, e.g. `delimiter` or `certificate`.
+	pub marker: String,
+	pub description: String,
+	pub client: SocketAddr,
+	pub server: SocketAddr,
+	pub severity: u8,
+}
+
+impl PcapFinding {
+	pub fn to_detection(&self, capture: &Path) -> Detection {
+		let kind = match &self.family {
+			Some(family) => DetectionKind::Signature { name: format!("Net.{}.{}", family, self.marker), family: family.clone() },
+			None => DetectionKind::Heuristic { description: self.description.clone() },
+		};
+		let ioc_kind = if self.server.is_ipv4() { IocKind::Ipv4 } else { IocKind::Ipv6 };
+		Detection {
+			path: capture.to_path_buf(),
+			kind,
+			severity: self.severity,
+			sha256: None,
+			config: None,
+			iocs: vec![Ioc { kind: ioc_kind, value: self.server.ip().to_string() }],
+			member: Some(format!("{} -> {}", self.client, self.server)),
+		}
+	}
+}
+
+/// An observed host, address or URL listed in an IOC feed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcapIocHit {
+	pub observed: String,
+	/// Where it was seen: `tcp server`, `dns query`, `dns answer`, `tls sni` or `http request`.
+	pub context: String,
+	pub matched: IocMatch,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PcapReport {
+	pub packets: usize,
+	/// Frames that were not IPv4/IPv6 TCP or UDP, or were truncated.
+	pub skipped: usize,
+	pub streams: Vec<StreamSummary>,
+	pub tls: Vec<TlsSession>,
+	pub dns: Vec<DnsQuery>,
+	pub http: Vec<HttpRequest>,
+	pub findings: Vec<PcapFinding>,
+}
+
+impl PcapReport {
+	/// Matches TCP servers, DNS names and answers, TLS SNI and HTTP URLs against `store`.
+	pub fn match_iocs(&self, store: &IocStore) -> Vec<PcapIocHit> {
+		let mut hits = Vec::new();
+		let mut seen = HashSet::new();
+		let mut hit = |observed: String, context: &str, m: Option<IocMatch>| {
+			let Some(matched) = m else { return };
+			if seen.insert((observed.clone(), context.to_string())) { hits.push(PcapIocHit { observed, context: context.into(), matched }); }
+		};
+		for s in &self.streams {
+			hit(s.server.ip().to_string(), "tcp server", store.match_ip(s.server.ip()));
+		}
+		for q in &self.dns {
+			hit(q.name.clone(), "dns query", store.match_domain(&q.name));
+			for ip in &q.answers {
+				hit(ip.to_string(), "dns answer", store.match_ip(*ip));
+			}
+		}
+		for t in &self.tls {
+			if let Some(sni) = &t.sni { hit(sni.clone(), "tls sni", store.match_domain(sni)); }
+		}
+		for h in &self.http {
+			let url = h.url();
+			let m = store.match_url(&url);
+			hit(url, "http request", m);
+		}
+		hits
+	}
+}
+
+pub fn analyze_file(path: &Path, opts: &PcapOptions) -> Result<PcapReport> {
+	let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+	analyze_bytes(&data, opts).with_context(|| format!("Failed to parse capture {}", path.display()))
+}
+
+pub fn analyze_bytes(data: &[u8], opts: &PcapOptions) -> Result<PcapReport> {
+	let packets = read_capture(data)?;
+	let mut report = PcapReport { packets: packets.len(), ..Default::default() };
+	let mut reassembler = Reassembler::new(opts.max_stream_bytes);
+	let mut dns: Vec<DnsQuery> = Vec::new();
+	for p in &packets {
+		let Some(ip) = network_layer(p.link_type, &p.data).and_then(parse_ip) else { report.skipped += 1; continue };
+		match ip.protocol {
+			6 => {
+				if !reassembler.push(&ip, p.timestamp_us) { report.skipped += 1; }
+			}
+			17 if ip.payload.len() >= 8 => {
+				let (sport, dport) = (be16(ip.payload, 0), be16(ip.payload, 2));
+				if sport == 53 || dport == 53 { record_dns(&mut dns, &ip.payload[8..], p.timestamp_us); }
+			}
+			_ => report.skipped += 1,
+		}
+	}
+	for mut stream in reassembler.finish() {
+		stream.summary.protocol = analyze_stream(&stream, &mut report, &mut dns).map(String::from);
+		report.streams.push(stream.summary);
+	}
+	report.dns = dns;
+	Ok(report)
+}
+
+/// Extracts TLS/HTTP/DNS details and RAT markers; returns the protocol recognized.
+fn analyze_stream(stream: &ReassembledStream, report: &mut PcapReport, dns: &mut Vec<DnsQuery>) -> Option<&'static str> {
+	let s = &stream.summary;
+	let protocol = if stream.to_server.starts_with(&[0x16, 0x03]) {
+		let tls = parse_tls(s.client, s.server, &stream.to_server, &stream.to_client);
+		if let Some(cert) = &tls.certificate { report.findings.extend(certificate_finding(s, cert)); }
+		report.tls.push(tls);
+		Some("tls")
+	} else if HTTP_METHODS.iter().any(|m| stream.to_server.starts_with(format!("{} ", m).as_bytes())) {
+		report.http.extend(parse_http(s.client, s.server, &stream.to_server));
+		Some("http")
+	} else if s.server.port() == 53 {
+		// DNS over TCP: each message carries a two-byte length prefix; answers come back on the client side
+		for mut rest in [stream.to_server.as_slice(), stream.to_client.as_slice()] {
+			while rest.len() > 2 {
+				let len = be16(rest, 0) as usize;
+				if rest.len() < 2 + len { break; }
+				record_dns(dns, &rest[2..2 + len], s.first_seen_us);
+				rest = &rest[2 + len..];
+			}
+		}
+		Some("dns")
+	} else {
+		None
+	};
+	report.findings.extend(marker_findings(stream));
+	protocol
+}
+
+/// Reads every frame of a pcap (micro- or nanosecond, either byte order) or pcapng capture.
+pub fn read_capture(data: &[u8]) -> Result<Vec<RawPacket>> {
+	if data.len() < 4 { bail!("Capture is too short"); }
+	if data[..4] == [0x0a, 0x0d, 0x0d, 0x0a] { read_pcapng(data) } else { read_pcap(data) }
+}
+
+#[derive(Clone, Copy)]
+struct Endian {
+	big: bool,
+}
+
+impl Endian {
+	fn u16(self, b: &[u8], at: usize) -> u16 {
+		let v = be16(b, at);
+		if self.big { v } else { v.swap_bytes() }
+	}
+
+	fn u32(self, b: &[u8], at: usize) -> u32 {
+		let v = be32(b, at);
+		if self.big { v } else { v.swap_bytes() }
+	}
+}
+
+fn read_pcap(data: &[u8]) -> Result<Vec<RawPacket>> {
+	let (endian, nanos) = match data[..4] {
+		[0xd4, 0xc3, 0xb2, 0xa1] => (Endian { big: false }, false),
+		[0xa1, 0xb2, 0xc3, 0xd4] => (Endian { big: true }, false),
+		[0x4d, 0x3c, 0xb2, 0xa1] => (Endian { big: false }, true),
+		[0xa1, 0xb2, 0x3c, 0x4d] => (Endian { big: true }, true),
+		_ => bail!("Not a pcap or pcapng capture"),
+	};
+	if data.len() < 24 { bail!("Truncated pcap header"); }
+	// upper bits carry FCS information
+	let link_type = endian.u32(data, 20) & 0x0fff_ffff;
+	let mut packets = Vec::new();
+	let mut at = 24;
+	while at + 16 <= data.len() {
+		let (secs, frac, len) = (endian.u32(data, at), endian.u32(data, at + 4), endian.u32(data, at + 8) as usize);
+		// a capture cut off mid-record keeps what came before
+		let Some(frame) = data.get(at + 16..at + 16 + len) else { break };
+		let micros = if nanos { frac / 1000 } else { frac };
+		packets.push(RawPacket { timestamp_us: secs as i64 * 1_000_000 + micros as i64, link_type, data: frame.to_vec() });
+		at += 16 + len;
+	}
+	Ok(packets)
+}
+
+fn read_pcapng(data: &[u8]) -> Result<Vec<RawPacket>> {
+	let mut endian = Endian { big: false };
+	// (link type, timestamp units per second) for each interface of the current section
+	let mut interfaces: Vec<(u32, u64)> = Vec::new();
+	let mut packets = Vec::new();
+	let mut at = 0;
+	while at + 12 <= data.len() {
+		if data[at..at + 4] == [0x0a, 0x0d, 0x0d, 0x0a] {
+			endian = match data[at + 8..at + 12] {
+				[0x1a, 0x2b, 0x3c, 0x4d] => Endian { big: true },
+				[0x4d, 0x3c, 0x2b, 0x1a] => Endian { big: false },
+				_ => bail!("Bad pcapng byte-order magic at offset {}", at),
+			};
+			interfaces.clear();
+		}
+		let (block_type, len) = (endian.u32(data, at), endian.u32(data, at + 4) as usize);
+		if len < 12 || at + len > data.len() { break; }
+		let body = &data[at + 8..at + len - 4];
+		match block_type {
+			// interface description
+			1 if body.len() >= 8 => interfaces.push((endian.u16(body, 0) as u32, timestamp_units(endian, &body[8..]))),
+			// enhanced packet
+			6 if body.len() >= 20 => {
+				let ts = (endian.u32(body, 4) as u64) << 32 | endian.u32(body, 8) as u64;
+				let caplen = endian.u32(body, 12) as usize;
+				if let (Some(&(link_type, units)), Some(frame)) = (interfaces.get(endian.u32(body, 0) as usize), body.get(20..20 + caplen)) {
+					packets.push(RawPacket { timestamp_us: to_micros(ts, units), link_type, data: frame.to_vec() });
+				}
+			}
+			// simple packet: no interface id and no timestamp
+			3 if body.len() >= 4 => {
+				if let Some(&(link_type, _)) = interfaces.first() {
+					let caplen = (endian.u32(body, 0) as usize).min(body.len() - 4);
+					packets.push(RawPacket { timestamp_us: 0, link_type, data: body[4..4 + caplen].to_vec() });
+				}
+			}
+			// obsolete packet block
+			2 if body.len() >= 20 => {
+				let ts = (endian.u32(body, 4) as u64) << 32 | endian.u32(body, 8) as u64;
+				let caplen = endian.u32(body, 12) as usize;
+				if let (Some(&(link_type, units)), Some(frame)) = (interfaces.get(endian.u16(body, 0) as usize), body.get(20..20 + caplen)) {
+					packets.push(RawPacket { timestamp_us: to_micros(ts, units), link_type, data: frame.to_vec() });
+				}
+			}
+			_ => {}
+		}
+		at += len;
+	}
+	Ok(packets)
+}
+
+/// Units per second from an interface's `if_tsresol` option; microseconds by default.
+fn timestamp_units(endian: Endian, mut options: &[u8]) -> u64 {
+	while options.len() >= 4 {
+		let (code, len) = (endian.u16(options, 0), endian.u16(options, 2) as usize);
+		if code == 0 { break; }
+		if code == 9 && len >= 1 && options.len() > 4 {
+			let v = options[4];
+			return if v & 0x80 == 0 { 10u64.saturating_pow(v as u32) } else { 1u64.checked_shl((v & 0x7f) as u32).unwrap_or(u64::MAX) };
+		}
+		let padded = 4 + len.div_ceil(4) * 4;
+		if padded > options.len() { break; }
+		options = &options[padded..];
+	}
+	1_000_000
+}
+
+fn to_micros(ts: u64, units_per_sec: u64) -> i64 {
+	(ts as u128 * 1_000_000 / units_per_sec.max(1) as u128) as i64
+}
+
+/// Strips the link-layer header, returning the IP packet.
+fn network_layer(link_type: u32, frame: &[u8]) -> Option<&[u8]> {
+	let ip = match link_type {
+		// Ethernet, skipping 802.1Q/802.1ad tags
+		1 => {
+			let mut at = 12;
+			while matches!(be16(frame, at), 0x8100 | 0x88a8) { at += 4; }
+			if !matches!(be16(frame, at), 0x0800 | 0x86dd) { return None; }
+			frame.get(at + 2..)?
+		}
+		// BSD loopback: 4-byte address family
+		0 | 108 => frame.get(4..)?,
+		12 | 14 | 101 | 228 | 229 => frame,
+		// Linux cooked capture v1 / v2
+		113 => frame.get(16..)?,
+		276 => frame.get(20..)?,
+		_ => return None,
+	};
+	matches!(ip.first()? >> 4, 4 | 6).then_some(ip)
+}
+
+struct IpPacket<'a> {
+	src: IpAddr,
+	dst: IpAddr,
+	protocol: u8,
+	payload: &'a [u8],
+}
+
+fn parse_ip(data: &[u8]) -> Option<IpPacket<'_>> {
+	match data.first()? >> 4 {
+		4 => {
+			let ihl = (data[0] & 0x0f) as usize * 4;
+			if ihl < 20 || data.len() < ihl { return None; }
+			// later fragments carry no transport header
+			if be16(data, 6) & 0x1fff != 0 { return None; }
+			// zero total length is seen with segmentation offload
+			let total = match be16(data, 2) as usize { 0 => data.len(), n => n.min(data.len()) };
+			let src = Ipv4Addr::new(data[12], data[13], data[14], data[15]);
+			let dst = Ipv4Addr::new(data[16], data[17], data[18], data[19]);
+			Some(IpPacket { src: src.into(), dst: dst.into(), protocol: data[9], payload: data.get(ihl..total)? })
+		}
+		6 => {
+			if data.len() < 40 { return None; }
+			let src: [u8; 16] = data[8..24].try_into().ok()?;
+			let dst: [u8; 16] = data[24..40].try_into().ok()?;
+			let end = match be16(data, 4) as usize { 0 => data.len(), n => (40 + n).min(data.len()) };
+			let (mut next, mut at) = (data[6], 40);
+			loop {
+				match next {
+					// hop-by-hop, routing and destination options
+					0 | 43 | 60 => {
+						next = *data.get(at)?;
+						at += (*data.get(at + 1)? as usize + 1) * 8;
+					}
+					44 => {
+						if be16(data, at + 2) & 0xfff8 != 0 { return None; }
+						next = *data.get(at)?;
+						at += 8;
+					}
+					_ => break,
+				}
+			}
+			Some(IpPacket { src: Ipv6Addr::from(src).into(), dst: Ipv6Addr::from(dst).into(), protocol: next, payload: data.get(at..end)? })
+		}
+		_ => None,
+	}
+}
+
+/// One direction of a TCP connection.
+#[derive(Default)]
+struct HalfStream {
+	// sequence number of the first payload byte
+	base: Option<u32>,
+	segments: BTreeMap<u32, Vec<u8>>,
+}
+
+impl HalfStream {
+	fn add(&mut self, seq: u32, syn: bool, payload: &[u8], max: usize) {
+		let data_seq = if syn { seq.wrapping_add(1) } else { seq };
+		if syn { self.base = Some(data_seq); }
+		if payload.is_empty() { return; }
+		let base = *self.base.get_or_insert(data_seq);
+		let offset = data_seq.wrapping_sub(base);
+		// before the first byte we saw, or past the cap
+		if offset > u32::MAX / 2 || offset as usize >= max { return; }
+		let slot = self.segments.entry(offset).or_default();
+		if payload.len() > slot.len() { *slot = payload.to_vec(); }
+	}
+
+	/// Bytes in sequence order, dropping retransmitted overlap, plus the number of holes skipped.
+	fn assemble(&self, max: usize) -> (Vec<u8>, usize) {
+		let (mut out, mut next, mut gaps) = (Vec::new(), 0usize, 0);
+		for (&offset, segment) in &self.segments {
+			let offset = offset as usize;
+			if offset + segment.len() <= next { continue; }
+			if offset > next { gaps += 1; }
+			out.extend_from_slice(&segment[next.saturating_sub(offset)..]);
+			next = offset + segment.len();
+			if out.len() >= max {
+				out.truncate(max);
+				break;
+			}
+		}
+		(out, gaps)
+	}
+}
+
+struct Flow {
+	client: SocketAddr,
+	server: SocketAddr,
+	first_seen_us: i64,
+	last_seen_us: i64,
+	to_server: HalfStream,
+	to_client: HalfStream,
+}
+
+struct Reassembler {
+	max: usize,
+	flows: Vec<Flow>,
+	index: HashMap<(SocketAddr, SocketAddr), usize>,
+}
+
+impl Reassembler {
+	fn new(max: usize) -> Self {
+		Self { max, flows: Vec::new(), index: HashMap::new() }
+	}
+
+	/// False if the segment is too short for a TCP header.
+	fn push(&mut self, ip: &IpPacket, ts: i64) -> bool {
+		let tcp = ip.payload;
+		let header = tcp.get(12).map_or(0, |b| (b >> 4) as usize * 4);
+		if header < 20 || tcp.len() < header { return false; }
+		let src = SocketAddr::new(ip.src, be16(tcp, 0));
+		let dst = SocketAddr::new(ip.dst, be16(tcp, 2));
+		let (syn, ack) = (tcp[13] & 0x02 != 0, tcp[13] & 0x10 != 0);
+		let key = if src <= dst { (src, dst) } else { (dst, src) };
+		let existing = self.index.get(&key).copied();
+		// a fresh SYN on a 4-tuple that already carried data starts a new connection
+		let reused = syn && !ack && existing.is_some_and(|i| !self.flows[i].to_server.segments.is_empty());
+		let i = match existing {
+			Some(i) if !reused => i,
+			_ => {
+				// without a handshake, the side on a well-known port is the server
+				let src_is_client = if syn { !ack } else { !(src.port() < 1024 && dst.port() >= 1024) };
+				let (client, server) = if src_is_client { (src, dst) } else { (dst, src) };
+				self.flows.push(Flow { client, server, first_seen_us: ts, last_seen_us: ts, to_server: HalfStream::default(), to_client: HalfStream::default() });
+				self.index.insert(key, self.flows.len() - 1);
+				self.flows.len() - 1
+			}
+		};
+		let flow = &mut self.flows[i];
+		flow.last_seen_us = flow.last_seen_us.max(ts);
+		let half = if src == flow.client { &mut flow.to_server } else { &mut flow.to_client };
+		half.add(be32(tcp, 4), syn, &tcp[header..], self.max);
+		true
+	}
+
+	fn finish(self) -> Vec<ReassembledStream> {
+		let max = self.max;
+		self.flows
+			.into_iter()
+			.map(|f| {
+				let (to_server, server_gaps) = f.to_server.assemble(max);
+				let (to_client, client_gaps) = f.to_client.assemble(max);
+				let summary = StreamSummary {
+					client: f.client,
+					server: f.server,
+					first_seen_us: f.first_seen_us,
+					last_seen_us: f.last_seen_us,
+					bytes_to_server: to_server.len(),
+					bytes_to_client: to_client.len(),
+					gaps: server_gaps + client_gaps,
+					protocol: None,
+				};
+				ReassembledStream { summary, to_server, to_client }
+			})
+			.collect()
+	}
+}
+
+/// Reassembles every TCP connection in a capture.
+pub fn reassemble(packets: &[RawPacket], max_stream_bytes: usize) -> Vec<ReassembledStream> {
+	let mut reassembler = Reassembler::new(max_stream_bytes);
+	for p in packets {
+		if let Some(ip) = network_layer(p.link_type, &p.data).and_then(parse_ip).filter(|ip| ip.protocol == 6) { reassembler.push(&ip, p.timestamp_us); }
+	}
+	reassembler.finish()
+}
+
+struct Reader<'a> {
+	data: &'a [u8],
+	at: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn new(data: &'a [u8]) -> Self {
+		Self { data, at: 0 }
+	}
+
+	fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+		let s = self.data.get(self.at..self.at.checked_add(n)?)?;
+		self.at += n;
+		Some(s)
+	}
+
+	fn u8(&mut self) -> Option<u8> {
+		self.take(1).map(|b| b[0])
+	}
+
+	fn u16(&mut self) -> Option<u16> {
+		self.take(2).map(|b| be16(b, 0))
+	}
+
+	fn vec8(&mut self) -> Option<&'a [u8]> {
+		let n = self.u8()? as usize;
+		self.take(n)
+	}
+
+	fn vec16(&mut self) -> Option<&'a [u8]> {
+		let n = self.u16()? as usize;
+		self.take(n)
+	}
+}
+
+/// Payload of the leading handshake records of one direction; stops at ChangeCipherSpec.
+fn handshake_bytes(stream: &[u8]) -> Vec<u8> {
+	let mut out = Vec::new();
+	let mut rest = stream;
+	while rest.len() >= 5 && rest[0] == 0x16 {
+		let end = (5 + be16(rest, 3) as usize).min(rest.len());
+		out.extend_from_slice(&rest[5..end]);
+		rest = &rest[end..];
+	}
+	out
+}
+
+fn handshake_messages(data: &[u8]) -> Vec<(u8, &[u8])> {
+	let mut out = Vec::new();
+	let mut rest = data;
+	while rest.len() >= 4 {
+		let Some(body) = rest.get(4..4 + be24(rest, 1)) else { break };
+		out.push((rest[0], body));
+		rest = &rest[4 + body.len()..];
+	}
+	out
+}
+
+// GREASE values (RFC 8701) are random per connection and left out of JA3
+fn is_grease(v: u16) -> bool {
+	v & 0x0f0f == 0x0a0a && v >> 8 == v & 0xff
+}
+
+fn u16_list(data: &[u8]) -> Vec<u16> {
+	data.chunks_exact(2).map(|c| be16(c, 0)).filter(|v| !is_grease(*v)).collect()
+}
+
+fn dashed<T: ToString>(values: &[T]) -> String {
+	values.iter().map(T::to_string).collect::<Vec<_>>().join("-")
+}
+
+fn md5_hex(s: &str) -> String {
+	Md5::digest(s.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// JA3 string and SNI of a ClientHello.
+fn client_hello(body: &[u8]) -> Option<(String, Option<String>)> {
+	let mut r = Reader::new(body);
+	let version = r.u16()?;
+	r.take(32)?;
+	r.vec8()?;
+	let ciphers = u16_list(r.vec16()?);
+	r.vec8()?;
+	let (mut extensions, mut groups, mut formats, mut sni) = (Vec::new(), Vec::new(), Vec::new(), None);
+	if let Some(data) = r.vec16() {
+		let mut ext = Reader::new(data);
+		while let (Some(kind), Some(data)) = (ext.u16(), ext.vec16()) {
+			if is_grease(kind) { continue; }
+			extensions.push(kind);
+			match kind {
+				0 => sni = server_name(data),
+				10 => groups = u16_list(Reader::new(data).vec16().unwrap_or_default()),
+				11 => formats = Reader::new(data).vec8().unwrap_or_default().to_vec(),
+				_ => {}
+			}
+		}
+	}
+	Some((format!("{},{},{},{},{}", version, dashed(&ciphers), dashed(&extensions), dashed(&groups), dashed(&formats)), sni))
+}
+
+fn server_name(data: &[u8]) -> Option<String> {
+	let mut list = Reader::new(Reader::new(data).vec16()?);
+	while let Some(kind) = list.u8() {
+		let name = list.vec16()?;
+		if kind == 0 { return Some(String::from_utf8_lossy(name).to_ascii_lowercase()); }
+	}
+	None
+}
+
+/// JA3S string of a ServerHello.
+fn server_hello(body: &[u8]) -> Option<String> {
+	let mut r = Reader::new(body);
+	let version = r.u16()?;
+	r.take(32)?;
+	r.vec8()?;
+	let cipher = r.u16()?;
+	r.u8()?;
+	let mut extensions = Vec::new();
+	if let Some(data) = r.vec16() {
+		let mut ext = Reader::new(data);
+		while let (Some(kind), Some(_)) = (ext.u16(), ext.vec16()) {
+			extensions.push(kind);
+		}
+	}
+	Some(format!("{},{},{}", version, cipher, dashed(&extensions)))
+}
+
+fn parse_tls(client: SocketAddr, server: SocketAddr, to_server: &[u8], to_client: &[u8]) -> TlsSession {
+	let mut tls = TlsSession { client, server, sni: None, ja3: None, ja3_hash: None, ja3s: None, ja3s_hash: None, certificate: None };
+	let hs = handshake_bytes(to_server);
+	if let Some((ja3, sni)) = handshake_messages(&hs).into_iter().find(|(kind, _)| *kind == 1).and_then(|(_, body)| client_hello(body)) {
+		tls.ja3_hash = Some(md5_hex(&ja3));
+		tls.ja3 = Some(ja3);
+		tls.sni = sni;
+	}
+	let hs = handshake_bytes(to_client);
+	for (kind, body) in handshake_messages(&hs) {
+		match kind {
+			2 => {
+				tls.ja3s = server_hello(body);
+				tls.ja3s_hash = tls.ja3s.as_deref().map(md5_hex);
+			}
+			// the chain's first entry is the leaf
+			11 => {
+				let chain = body.get(3..3 + be24(body, 0)).unwrap_or_default();
+				tls.certificate = chain.get(3..3 + be24(chain, 0)).and_then(parse_certificate);
+			}
+			_ => {}
+		}
+	}
+	tls
+}
+
+/// (tag, contents, rest) of the first DER element.
+fn der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+	let tag = *data.first()?;
+	let first = *data.get(1)? as usize;
+	let (len, header) = if first < 0x80 {
+		(first, 2)
+	} else {
+		let n = first & 0x7f;
+		if n == 0 || n > 4 { return None; }
+		(data.get(2..2 + n)?.iter().fold(0usize, |acc, b| acc << 8 | *b as usize), 2 + n)
+	};
+	let contents = data.get(header..header.checked_add(len)?)?;
+	Some((tag, contents, &data[header + len..]))
+}
+
+fn parse_certificate(cert: &[u8]) -> Option<CertificateInfo> {
+	let (_, cert, _) = der(cert)?;
+	let (_, tbs, _) = der(cert)?;
+	let (tag, _, mut rest) = der(tbs)?;
+	// the optional explicit version precedes the serial number
+	if tag == 0xa0 { rest = der(rest)?.2; }
+	let (_, _, rest) = der(rest)?;
+	let (_, issuer, rest) = der(rest)?;
+	let (_, validity, rest) = der(rest)?;
+	let (_, subject, _) = der(rest)?;
+	let not_after = der(validity).and_then(|(_, _, rest)| der(rest)).and_then(|(tag, time, _)| der_date(tag, time));
+	Some(CertificateInfo { subject_cn: common_name(subject), issuer_cn: common_name(issuer), not_after, self_signed: subject == issuer })
+}
+
+fn common_name(name: &[u8]) -> Option<String> {
+	let mut rdns = name;
+	while let Some((_, set, rest)) = der(rdns) {
+		if let Some((0x06, oid, value)) = der(set).and_then(|(_, attr, _)| der(attr)) {
+			if oid == [0x55, 0x04, 0x03] { return der(value).map(|(_, v, _)| String::from_utf8_lossy(v).to_string()); }
+		}
+		rdns = rest;
+	}
+	None
+}
+
+/// UTCTime or GeneralizedTime as `YYYY-MM-DD`.
+fn der_date(tag: u8, time: &[u8]) -> Option<String> {
+	let time = std::str::from_utf8(time).ok()?;
+	let (year, rest): (u32, &str) = match tag {
+		0x17 => {
+			let yy: u32 = time.get(..2)?.parse().ok()?;
+			(if yy >= 50 { 1900 + yy } else { 2000 + yy }, time.get(2..)?)
+		}
+		0x18 => (time.get(..4)?.parse().ok()?, time.get(4..)?),
+		_ => return None,
+	};
+	Some(format!("{:04}-{}-{}", year, rest.get(..2)?, rest.get(2..4)?))
+}
+
+fn certificate_finding(s: &StreamSummary, cert: &CertificateInfo) -> Option<PcapFinding> {
+	let names = [cert.subject_cn.as_deref(), cert.issuer_cn.as_deref()];
+	let finding = |family: Option<&str>, description: String, severity| PcapFinding {
+		family: family.map(String::from),
+		marker: "certificate".into(),
+		description,
+		client: s.client,
+		server: s.server,
+		severity,
+	};
+	if let Some((cn, family)) = RAT_CERT_NAMES.iter().find(|(cn, _)| names.iter().flatten().any(|n| n.eq_ignore_ascii_case(cn))) {
+		return Some(finding(Some(family), format!("{} builder certificate (CN={}) presented by {}", family, cn, s.server), 9));
+	}
+	// the builders self-sign with a validity ending in year 9999
+	let not_after = cert.not_after.as_deref().filter(|d| cert.self_signed && d.starts_with("9999"))?;
+	let cn = cert.subject_cn.as_deref().unwrap_or("?");
+	Some(finding(None, format!("Self-signed certificate CN={} valid until {} presented by {}, typical of AsyncRAT/Quasar builds", cn, not_after, s.server), 6))
+}
+
+fn marker_findings(stream: &ReassembledStream) -> Vec<PcapFinding> {
+	let s = &stream.summary;
+	let finding = |family: &str, marker: &str, description: String| PcapFinding {
+		family: Some(family.into()),
+		marker: marker.into(),
+		description,
+		client: s.client,
+		server: s.server,
+		severity: 9,
+	};
+	let directions = [&stream.to_server, &stream.to_client];
+	let mut out = Vec::new();
+	if let Some(data) = directions.iter().find(|d| find(d, NJRAT_SPLITTER).is_some()) {
+		out.push(finding("njRAT", "delimiter", format!("njRAT |'|'| delimited messages between {} and {}: {}", s.client, s.server, printable(&data[..data.len().min(48)]))));
+	}
+	if directions.iter().any(|d| d.starts_with(REMCOS_MAGIC) || find(d, REMCOS_FIELD_SEPARATOR).is_some()) {
+		out.push(finding("Remcos", "handshake", format!("Remcos packet framing between {} and {}", s.client, s.server)));
+	}
+	out
+}
+
+fn printable(data: &[u8]) -> String {
+	data.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect()
+}
+
+fn record_dns(queries: &mut Vec<DnsQuery>, msg: &[u8], ts: i64) {
+	if msg.len() < 12 { return; }
+	let (questions, answer_count) = (be16(msg, 4), be16(msg, 6));
+	let mut at = 12;
+	let mut asked = Vec::new();
+	for _ in 0..questions {
+		let Some((name, next)) = dns_name(msg, at) else { return };
+		asked.push((name, be16(msg, next)));
+		at = next + 4;
+	}
+	let mut answers: Vec<IpAddr> = Vec::new();
+	for _ in 0..answer_count {
+		let Some((_, next)) = dns_name(msg, at) else { break };
+		let (rtype, rdlen) = (be16(msg, next), be16(msg, next + 8) as usize);
+		let Some(rdata) = msg.get(next + 10..next + 10 + rdlen) else { break };
+		match (rtype, rdata.len()) {
+			(1, 4) => answers.push(IpAddr::from(<[u8; 4]>::try_from(rdata).unwrap())),
+			(28, 16) => answers.push(IpAddr::from(<[u8; 16]>::try_from(rdata).unwrap())),
+			_ => {}
+		}
+		at = next + 10 + rdlen;
+	}
+	for (name, qtype) in asked.into_iter().filter(|(name, _)| !name.is_empty()) {
+		let query_type = match qtype {
+			1 => "A".into(),
+			5 => "CNAME".into(),
+			12 => "PTR".into(),
+			15 => "MX".into(),
+			16 => "TXT".into(),
+			28 => "AAAA".into(),
+			33 => "SRV".into(),
+			65 => "HTTPS".into(),
+			n => n.to_string(),
+		};
+		let i = match queries.iter().position(|q| q.name == name && q.query_type == query_type) {
+			Some(i) => i,
+			None => {
+				queries.push(DnsQuery { name, query_type, answers: Vec::new(), first_seen_us: ts });
+				queries.len() - 1
+			}
+		};
+		for ip in &answers {
+			if !queries[i].answers.contains(ip) { queries[i].answers.push(*ip); }
+		}
+	}
+}
+
+/// Name at `at` and the offset just past it, following compression pointers.
+fn dns_name(msg: &[u8], mut at: usize) -> Option<(String, usize)> {
+	let mut labels: Vec<String> = Vec::new();
+	let mut end = None;
+	// bounds pointer loops
+	for _ in 0..128 {
+		let len = *msg.get(at)? as usize;
+		match len {
+			0 => return Some((labels.join(".").to_ascii_lowercase(), end.unwrap_or(at + 1))),
+			l if l & 0xc0 == 0xc0 => {
+				end.get_or_insert(at + 2);
+				at = (l & 0x3f) << 8 | *msg.get(at + 1)? as usize;
+			}
+			l => {
+				labels.push(String::from_utf8_lossy(msg.get(at + 1..at + 1 + l)?).to_string());
+				at += 1 + l;
+			}
+		}
+	}
+	None
+}
+
+fn parse_http(client: SocketAddr, server: SocketAddr, data: &[u8]) -> Vec<HttpRequest> {
+	let mut out = Vec::new();
+	let mut rest = data;
+	while let Some(end) = find(rest, b"\r\n\r\n") {
+		let head = String::from_utf8_lossy(&rest[..end]);
+		let mut lines = head.split("\r\n");
+		let mut request_line = lines.next().unwrap_or_default().split(' ');
+		let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else { break };
+		if !HTTP_METHODS.contains(&method) { break; }
+		let mut req = HttpRequest { client, server, method: method.into(), host: None, path: path.into(), user_agent: None };
+		let mut body = 0;
+		for (name, value) in lines.filter_map(|l| l.split_once(':')) {
+			let value = value.trim();
+			match name.trim().to_ascii_lowercase().as_str() {
+				"host" => req.host = Some(value.to_ascii_lowercase()),
+				"user-agent" => req.user_agent = Some(value.into()),
+				"content-length" => body = value.parse().unwrap_or(0),
+				_ => {}
+			}
+		}
+		out.push(req);
+		rest = rest.get(end + 4 + body..).unwrap_or_default();
+	}
+	out
+}
+
+fn find(hay: &[u8], needle: &[u8]) -> Option<usize> {
+	hay.windows(needle.len()).position(|w| w == needle)
+}
+
+fn be16(b: &[u8], at: usize) -> u16 {
+	b.get(at..at + 2).map_or(0, |s| u16::from_be_bytes([s[0], s[1]]))
+}
+
+fn be24(b: &[u8], at: usize) -> usize {
+	b.get(at..at + 3).map_or(0, |s| (s[0] as usize) << 16 | (s[1] as usize) << 8 | s[2] as usize)
+}
+
+fn be32(b: &[u8], at: usize) -> u32 {
+	b.get(at..at + 4).map_or(0, |s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]))
+}
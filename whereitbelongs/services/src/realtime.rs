@@ -1,41 +1,481 @@
-use anyhow::Result;
-use crossbeam_channel::unbounded;
+use anyhow::{bail, Context, Result};
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender, TrySendError};
+use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind, RenameMode};
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::PathBuf;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
-use wib_core::{scan_paths, Detection, ScanOptions};
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
+use wib_core::{Detection, ScanOptions, Scanner};
+
+use crate::behavior::{BehaviorEngine, BehaviorOptions, FileActivity};
+
+/// How a watched path is monitored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum WatchBackend {
+	/// Polling on network and FUSE filesystems, native notifications elsewhere.
+	Auto,
+	/// inotify, FSEvents or ReadDirectoryChangesW.
+	Native,
+	/// Rescans the tree every `interval` against an index of file sizes and modification
+	/// times, so it also sees changes made by other hosts.
+	Poll { interval: Duration },
+}
 
 #[derive(Clone, Debug)]
 pub struct RealtimeOptions {
 	pub paths: Vec<PathBuf>,
+	/// Per-path backend; paths not listed use [`WatchBackend::Auto`].
+	pub backends: HashMap<PathBuf, WatchBackend>,
+	/// Interval for paths that `Auto` decides to poll.
+	pub poll_interval: Duration,
+	/// A path is scanned once it has been quiet for this long.
+	pub debounce: Duration,
+	/// Paths waiting for a worker; when full, ready paths stay pending until there is room.
+	pub queue_capacity: usize,
+	pub workers: usize,
+	pub scan_options: ScanOptions,
+	/// Ransomware behavior tracking on the same events; alerts go to the detection channel.
+	pub behavior: Option<BehaviorOptions>,
+}
+
+impl Default for RealtimeOptions {
+	fn default() -> Self {
+		Self {
+			paths: Vec::new(),
+			backends: HashMap::new(),
+			poll_interval: Duration::from_secs(10),
+			debounce: Duration::from_millis(500),
+			queue_capacity: 1024,
+			workers: thread::available_parallelism().map(|n| (n.get() / 2).max(1)).unwrap_or(2),
+			scan_options: ScanOptions::default(),
+			behavior: None,
+		}
+	}
+}
+
+/// Counters shared by the coordinator and workers.
+#[derive(Debug, Default)]
+struct RealtimeMetrics {
+	events: AtomicU64,
+	coalesced: AtomicU64,
+	queued: AtomicU64,
+	deferred: AtomicU64,
+	scanned: AtomicU64,
+	detections: AtomicU64,
+}
+
+/// Point-in-time realtime counters.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetricsSnapshot {
+	/// Filesystem events received from the watcher.
+	pub events: u64,
+	/// Events folded into an already pending path.
+	pub coalesced: u64,
+	pub queued: u64,
+	/// Times a ready path had to wait because the work queue was full.
+	pub deferred: u64,
+	pub scanned: u64,
+	pub detections: u64,
+	/// Paths waiting for a worker right now.
+	pub queue_depth: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RealtimeState {
+	Running,
+	/// Watching continues but events are discarded until `resume`.
+	Paused,
+	Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RealtimeStatus {
+	pub state: RealtimeState,
+	pub paths: Vec<PathBuf>,
+	/// The subset of `paths` watched by polling.
+	pub polled: Vec<PathBuf>,
+	pub metrics: MetricsSnapshot,
+}
+
+// Flags shared with the coordinator and worker threads.
+#[derive(Default)]
+struct Control {
+	paused: AtomicBool,
+	stopping: AtomicBool,
+}
+
+/// Resolves `Auto` by the filesystem type `path` lives on; never returns `Auto`.
+pub fn resolve_backend(path: &Path, backend: WatchBackend, poll_interval: Duration) -> WatchBackend {
+	if backend != WatchBackend::Auto { return backend; }
+	#[cfg(target_os = "linux")]
+	{
+		let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+		let mounts = crate::mounts::read_mounts().unwrap_or_default();
+		if let Some(m) = crate::mounts::mount_of(&mounts, &path).filter(|m| crate::mounts::needs_polling(&m.fs_type)) {
+			log::info!("{} is on {} ({}); polling every {:?}", path.display(), m.mount_point.display(), m.fs_type, poll_interval);
+			return WatchBackend::Poll { interval: poll_interval };
+		}
+	}
+	#[cfg(not(target_os = "linux"))]
+	let _ = (path, poll_interval);
+	WatchBackend::Native
+}
+
+// Forwards watcher events to the coordinator and, when enabled, the behavior engine.
+#[derive(Clone)]
+struct EventSink {
+	tx: Sender<notify::Result<Event>>,
+	behavior: Option<Sender<Event>>,
+}
+
+impl EventSink {
+	fn send(&self, res: notify::Result<Event>) {
+		if let (Some(behavior), Ok(event)) = (&self.behavior, &res) { let _ = behavior.send(event.clone()); }
+		let _ = self.tx.send(res);
+	}
+}
+
+// Change index entry of a polled file; a change in either field counts as a modification.
+#[derive(PartialEq, Eq)]
+struct Stamp {
+	size: u64,
+	modified: Option<SystemTime>,
 }
 
+fn index_tree(root: &Path) -> HashMap<PathBuf, Stamp> {
+	WalkDir::new(root)
+		.follow_links(false)
+		.into_iter()
+		.filter_map(Result::ok)
+		.filter(|e| e.file_type().is_file())
+		.filter_map(|e| {
+			let meta = e.metadata().ok()?;
+			Some((e.into_path(), Stamp { size: meta.len(), modified: meta.modified().ok() }))
+		})
+		.collect()
+}
+
+/// Watches one tree by rescanning it; stops when dropped.
+struct Poller {
+	stop: Arc<AtomicBool>,
+	join: Option<thread::JoinHandle<()>>,
+}
+
+impl Poller {
+	fn start(root: &Path, interval: Duration, sink: EventSink) -> Result<Self> {
+		if !root.exists() { bail!("{} does not exist", root.display()); }
+		let root = root.to_path_buf();
+		let mut index = index_tree(&root);
+		let stop = Arc::new(AtomicBool::new(false));
+		let thread_stop = stop.clone();
+		let join = thread::spawn(move || loop {
+			let deadline = Instant::now() + interval;
+			while Instant::now() < deadline {
+				if thread_stop.load(Ordering::Relaxed) { return; }
+				thread::sleep(deadline.saturating_duration_since(Instant::now()).min(Duration::from_millis(100)));
+			}
+			let next = index_tree(&root);
+			for (path, stamp) in &next {
+				let kind = match index.get(path) {
+					None => EventKind::Create(CreateKind::File),
+					Some(old) if old != stamp => EventKind::Modify(ModifyKind::Data(DataChange::Any)),
+					Some(_) => continue,
+				};
+				sink.send(Ok(Event::new(kind).add_path(path.clone())));
+			}
+			for path in index.keys().filter(|p| !next.contains_key(*p)) {
+				sink.send(Ok(Event::new(EventKind::Remove(RemoveKind::File)).add_path(path.clone())));
+			}
+			index = next;
+		});
+		Ok(Self { stop, join: Some(join) })
+	}
+}
+
+impl Drop for Poller {
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::Relaxed);
+		if let Some(join) = self.join.take() {
+			if join.join().is_err() { log::warn!("Poll watcher thread panicked"); }
+		}
+	}
+}
+
+// The native watcher plus one poller per polled path.
+struct Watchers {
+	native: RecommendedWatcher,
+	polled: Vec<(PathBuf, Poller)>,
+	sink: EventSink,
+}
+
+impl Watchers {
+	fn new(sink: EventSink) -> Result<Self> {
+		let native_sink = sink.clone();
+		let native = RecommendedWatcher::new(move |res| native_sink.send(res), Config::default())?;
+		Ok(Self { native, polled: Vec::new(), sink })
+	}
+
+	fn watch(&mut self, path: &Path, backend: WatchBackend) -> Result<()> {
+		match backend {
+			WatchBackend::Poll { interval } => {
+				let poller = Poller::start(path, interval, self.sink.clone())?;
+				self.polled.push((path.to_path_buf(), poller));
+			}
+			WatchBackend::Native | WatchBackend::Auto => self.native.watch(path, RecursiveMode::Recursive)?,
+		}
+		Ok(())
+	}
+
+	fn unwatch(&mut self, path: &Path) -> Result<()> {
+		match self.polled.iter().position(|(p, _)| p == path) {
+			Some(pos) => drop(self.polled.remove(pos)),
+			None => self.native.unwatch(path)?,
+		}
+		Ok(())
+	}
+
+	fn polled_paths(&self) -> Vec<PathBuf> {
+		self.polled.iter().map(|(p, _)| p.clone()).collect()
+	}
+}
+
+/// Owns the watchers and threads of a realtime monitor; dropping it stops monitoring.
 pub struct RealtimeGuard {
-	_join: thread::JoinHandle<()>,
+	watchers: Option<Watchers>,
+	paths: Vec<PathBuf>,
+	poll_interval: Duration,
+	joins: Vec<thread::JoinHandle<()>>,
+	control: Arc<Control>,
+	metrics: Arc<RealtimeMetrics>,
+	work_rx: Receiver<PathBuf>,
 }
 
-pub fn start_realtime(opts: RealtimeOptions, tx_detect: crossbeam_channel::Sender<Detection>) -> Result<RealtimeGuard> {
+impl RealtimeGuard {
+	pub fn metrics(&self) -> MetricsSnapshot {
+		let m = &self.metrics;
+		MetricsSnapshot {
+			events: m.events.load(Ordering::Relaxed),
+			coalesced: m.coalesced.load(Ordering::Relaxed),
+			queued: m.queued.load(Ordering::Relaxed),
+			deferred: m.deferred.load(Ordering::Relaxed),
+			scanned: m.scanned.load(Ordering::Relaxed),
+			detections: m.detections.load(Ordering::Relaxed),
+			queue_depth: self.work_rx.len(),
+		}
+	}
+
+	pub fn state(&self) -> RealtimeState {
+		if self.watchers.is_none() {
+			RealtimeState::Stopped
+		} else if self.control.paused.load(Ordering::Relaxed) {
+			RealtimeState::Paused
+		} else {
+			RealtimeState::Running
+		}
+	}
+
+	pub fn is_active(&self) -> bool {
+		self.state() == RealtimeState::Running
+	}
+
+	pub fn watched_paths(&self) -> &[PathBuf] {
+		&self.paths
+	}
+
+	pub fn status(&self) -> RealtimeStatus {
+		let polled = self.watchers.as_ref().map(Watchers::polled_paths).unwrap_or_default();
+		RealtimeStatus { state: self.state(), paths: self.paths.clone(), polled, metrics: self.metrics() }
+	}
+
+	pub fn pause(&self) {
+		if !self.control.paused.swap(true, Ordering::Relaxed) { log::info!("Realtime protection paused"); }
+	}
+
+	pub fn resume(&self) {
+		if self.control.paused.swap(false, Ordering::Relaxed) { log::info!("Realtime protection resumed"); }
+	}
+
+	pub fn add_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+		self.add_path_with(path, WatchBackend::Auto)
+	}
+
+	pub fn add_path_with<P: AsRef<Path>>(&mut self, path: P, backend: WatchBackend) -> Result<()> {
+		let path = path.as_ref();
+		let Some(watchers) = self.watchers.as_mut() else { bail!("Realtime monitor is stopped") };
+		if self.paths.iter().any(|p| p == path) { return Ok(()); }
+		let backend = resolve_backend(path, backend, self.poll_interval);
+		watchers.watch(path, backend).with_context(|| format!("Failed to watch {}", path.display()))?;
+		self.paths.push(path.to_path_buf());
+		Ok(())
+	}
+
+	pub fn remove_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+		let path = path.as_ref();
+		let Some(watchers) = self.watchers.as_mut() else { bail!("Realtime monitor is stopped") };
+		let Some(pos) = self.paths.iter().position(|p| p == path) else { bail!("{} is not watched", path.display()) };
+		watchers.unwatch(path).with_context(|| format!("Failed to unwatch {}", path.display()))?;
+		self.paths.remove(pos);
+		Ok(())
+	}
+
+	/// Stops watching, abandons queued scans and waits for the threads to exit.
+	pub fn stop(&mut self) {
+		let Some(watchers) = self.watchers.take() else { return };
+		self.control.stopping.store(true, Ordering::Relaxed);
+		drop(watchers);
+		for j in self.joins.drain(..) {
+			if j.join().is_err() { log::warn!("Realtime thread panicked"); }
+		}
+		log::info!("Realtime protection stopped");
+	}
+}
+
+impl Drop for RealtimeGuard {
+	fn drop(&mut self) {
+		self.stop();
+	}
+}
+
+pub fn start_realtime(opts: RealtimeOptions, tx_detect: Sender<Detection>) -> Result<RealtimeGuard> {
+	start_realtime_with_scanner(opts, Arc::new(Scanner::new()), tx_detect)
+}
+
+/// Watches `opts.paths`, coalescing bursts of events per path and scanning on a worker pool
+/// that shares `scanner`.
+pub fn start_realtime_with_scanner(opts: RealtimeOptions, scanner: Arc<Scanner>, tx_detect: Sender<Detection>) -> Result<RealtimeGuard> {
 	let (tx, rx) = unbounded();
-	let mut watcher: RecommendedWatcher = RecommendedWatcher::new(move |res| {
-		let _ = tx.send(res);
-	}, Config::default())?;
+	let (behavior_tx, behavior_rx) = unbounded::<Event>();
+	let mut watchers = Watchers::new(EventSink { tx, behavior: opts.behavior.is_some().then_some(behavior_tx) })?;
 	for p in &opts.paths {
-		watcher.watch(p, RecursiveMode::Recursive)?;
-	}
-	let handle = thread::spawn(move || {
-		let scan_opts = ScanOptions::default();
-		loop {
-			match rx.recv_timeout(Duration::from_millis(500)) {
-				Ok(Ok(Event { kind: EventKind::Create(_) | EventKind::Modify(_), paths, .. })) => {
-					for p in paths {
-						let ds = scan_paths(&[p.clone()], scan_opts.clone());
-						for d in ds { let _ = tx_detect.send(d); }
-					}
+		let backend = resolve_backend(p, opts.backends.get(p).copied().unwrap_or(WatchBackend::Auto), opts.poll_interval);
+		watchers.watch(p, backend).with_context(|| format!("Failed to watch {}", p.display()))?;
+	}
+	let metrics = Arc::new(RealtimeMetrics::default());
+	let control = Arc::new(Control::default());
+	let (work_tx, work_rx) = bounded::<PathBuf>(opts.queue_capacity.max(1));
+	let mut joins = Vec::new();
+	for _ in 0..opts.workers.max(1) {
+		let (work_rx, scanner, tx_detect, metrics, control) = (work_rx.clone(), scanner.clone(), tx_detect.clone(), metrics.clone(), control.clone());
+		let scan_opts = opts.scan_options.clone();
+		joins.push(thread::spawn(move || {
+			for p in work_rx {
+				if control.stopping.load(Ordering::Relaxed) { return; }
+				let ds = scanner.scan_paths(std::slice::from_ref(&p), &scan_opts);
+				metrics.scanned.fetch_add(1, Ordering::Relaxed);
+				metrics.detections.fetch_add(ds.len() as u64, Ordering::Relaxed);
+				for d in ds { let _ = tx_detect.send(d); }
+			}
+		}));
+	}
+	if let Some(behavior) = opts.behavior.clone() {
+		let engine = BehaviorEngine::new(behavior, opts.paths.clone());
+		let writer_of = writer_lookup(&opts.paths);
+		let (tx_detect, metrics, control) = (tx_detect.clone(), metrics.clone(), control.clone());
+		joins.push(thread::spawn(move || track(behavior_rx, engine, writer_of, tx_detect, &metrics, &control)));
+	}
+	let debounce = opts.debounce;
+	let (coordinator_metrics, coordinator_control) = (metrics.clone(), control.clone());
+	joins.push(thread::spawn(move || coordinate(rx, work_tx, debounce, &coordinator_metrics, &coordinator_control)));
+	Ok(RealtimeGuard { watchers: Some(watchers), paths: opts.paths, poll_interval: opts.poll_interval, joins, control, metrics, work_rx })
+}
+
+// Collects events into `pending` (path -> last event time) and hands quiet paths to the workers.
+fn coordinate(rx: Receiver<notify::Result<Event>>, work_tx: Sender<PathBuf>, debounce: Duration, metrics: &RealtimeMetrics, control: &Control) {
+	let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+	let tick = (debounce / 2).clamp(Duration::from_millis(10), Duration::from_millis(250));
+	loop {
+		if control.stopping.load(Ordering::Relaxed) { return; }
+		let event = rx.recv_timeout(tick);
+		if control.paused.load(Ordering::Relaxed) {
+			pending.clear();
+			if matches!(event, Err(RecvTimeoutError::Disconnected)) { return; }
+			continue;
+		}
+		match event {
+			// metadata-only changes (chmod, atime) cannot change what we would find
+			Ok(Ok(Event { kind: EventKind::Modify(ModifyKind::Metadata(_)), .. })) => {}
+			Ok(Ok(Event { kind: EventKind::Create(_) | EventKind::Modify(_), paths, .. })) => {
+				metrics.events.fetch_add(1, Ordering::Relaxed);
+				let now = Instant::now();
+				for p in paths {
+					if pending.insert(p, now).is_some() { metrics.coalesced.fetch_add(1, Ordering::Relaxed); }
 				}
-				_ => {}
+			}
+			Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+			Err(RecvTimeoutError::Disconnected) => return,
+		}
+		let now = Instant::now();
+		let ready: Vec<PathBuf> = pending.iter().filter(|(_, t)| now.duration_since(**t) >= debounce).map(|(p, _)| p.clone()).collect();
+		for p in ready {
+			if p.is_dir() {
+				pending.remove(&p);
+				continue;
+			}
+			match work_tx.try_send(p.clone()) {
+				Ok(()) => {
+					pending.remove(&p);
+					metrics.queued.fetch_add(1, Ordering::Relaxed);
+				}
+				Err(TrySendError::Full(_)) => {
+					metrics.deferred.fetch_add(1, Ordering::Relaxed);
+					break;
+				}
+				Err(TrySendError::Disconnected(_)) => return,
+			}
+		}
+	}
+}
+
+type WriterLookup = Box<dyn Fn(&Path) -> Option<u32> + Send>;
+
+// Names the process writing a file where fanotify is available (Linux, CAP_SYS_ADMIN), so behavior
+// is tracked per process and can be suspended; otherwise activity is grouped by watched root.
+// Mount marks cover the mounts of the initial paths only.
+fn writer_lookup(paths: &[PathBuf]) -> WriterLookup {
+	#[cfg(target_os = "linux")]
+	match crate::fanotify::track_writers(paths) {
+		Ok(tracker) => return Box::new(move |p| tracker.writer_of(p)),
+		Err(e) => log::info!("Behavior tracking cannot attribute processes: {:#}", e),
+	}
+	#[cfg(not(target_os = "linux"))]
+	let _ = paths;
+	Box::new(|_| None)
+}
+
+// Runs the behavior engine over raw watcher events; ends when the watcher is dropped.
+fn track(rx: Receiver<Event>, mut engine: BehaviorEngine, writer_of: WriterLookup, tx_detect: Sender<Detection>, metrics: &RealtimeMetrics, control: &Control) {
+	for event in rx {
+		if control.stopping.load(Ordering::Relaxed) { return; }
+		if control.paused.load(Ordering::Relaxed) { continue; }
+		for activity in activities(event) {
+			let pid = match &activity {
+				FileActivity::Created(p) | FileActivity::Modified(p) | FileActivity::Renamed { to: p, .. } => writer_of(p),
+				FileActivity::Removed(_) => None,
+			};
+			if let Some(d) = engine.observe(&activity, pid) {
+				metrics.detections.fetch_add(1, Ordering::Relaxed);
+				let _ = tx_detect.send(d);
 			}
 		}
-	});
-	Ok(RealtimeGuard { _join: handle })
-}
\ No newline at end of file
+	}
+}
+
+fn activities(event: Event) -> Vec<FileActivity> {
+	match event.kind {
+		EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+			let mut paths = event.paths.into_iter();
+			let (from, to) = (paths.next().unwrap(), paths.next().unwrap());
+			vec![FileActivity::Renamed { from, to }]
+		}
+		// moved in from outside the watched tree
+		EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => event.paths.into_iter().map(FileActivity::Created).collect(),
+		EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) => event.paths.into_iter().map(FileActivity::Modified).collect(),
+		EventKind::Remove(_) => event.paths.into_iter().map(FileActivity::Removed).collect(),
+		_ => Vec::new(),
+	}
+}
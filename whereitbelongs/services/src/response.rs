@@ -0,0 +1,274 @@
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use wib_core::quarantine::{self, app_data_dir};
+use wib_core::{Detection, DetectionKind};
+
+use crate::firewall;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseAction {
+	Log,
+	/// Forward to the notification channel (desktop toast, SIEM, ...).
+	Notify,
+	Quarantine,
+	/// Kill processes that have the file open or are running it.
+	KillProcess,
+	/// Block the C2 IP addresses from an extracted RAT config (Windows only).
+	BlockC2,
+}
+
+/// Matches when every given condition holds; empty conditions match anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyRule {
+	#[serde(default)]
+	pub min_severity: Option<u8>,
+	/// Case-insensitive family names; heuristic detections have no family.
+	#[serde(default)]
+	pub families: Vec<String>,
+	#[serde(default)]
+	pub path_prefixes: Vec<PathBuf>,
+	pub actions: Vec<ResponseAction>,
+}
+
+impl PolicyRule {
+	fn matches(&self, d: &Detection) -> bool {
+		if self.min_severity.is_some_and(|min| d.severity < min) { return false; }
+		if !self.families.is_empty() {
+			let DetectionKind::Signature { family, .. } = &d.kind else { return false };
+			if !self.families.iter().any(|f| f.eq_ignore_ascii_case(family)) { return false; }
+		}
+		self.path_prefixes.is_empty() || self.path_prefixes.iter().any(|p| d.path.starts_with(p))
+	}
+}
+
+/// First matching rule wins; `default_actions` apply when none match.
+/// Deployments override the built-in policy with `response_policy.json` in the data directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponsePolicy {
+	pub rules: Vec<PolicyRule>,
+	#[serde(default = "default_actions")]
+	pub default_actions: Vec<ResponseAction>,
+}
+
+fn default_actions() -> Vec<ResponseAction> {
+	vec![ResponseAction::Log, ResponseAction::Notify]
+}
+
+impl Default for ResponsePolicy {
+	fn default() -> Self {
+		Self {
+			rules: vec![PolicyRule {
+				min_severity: Some(8),
+				actions: vec![ResponseAction::Log, ResponseAction::Notify, ResponseAction::Quarantine],
+				..Default::default()
+			}],
+			default_actions: default_actions(),
+		}
+	}
+}
+
+impl ResponsePolicy {
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref();
+		let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+		serde_json::from_str(&text).with_context(|| format!("Invalid response policy in {}", path.display()))
+	}
+
+	/// The deployment's `response_policy.json`, or the built-in default.
+	pub fn load_default() -> Result<Self> {
+		let path = app_data_dir().join("response_policy.json");
+		if path.exists() { Self::load(path) } else { Ok(Self::default()) }
+	}
+
+	pub fn actions_for(&self, d: &Detection) -> &[ResponseAction] {
+		self.rules.iter().find(|r| r.matches(d)).map(|r| r.actions.as_slice()).unwrap_or(&self.default_actions)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionOutcome {
+	Done,
+	Skipped,
+	Failed,
+}
+
+/// One line of the action log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionRecord {
+	pub timestamp_ms: i64,
+	pub path: PathBuf,
+	pub detection: String,
+	pub action: ResponseAction,
+	pub outcome: ActionOutcome,
+	pub detail: String,
+}
+
+/// Append-only JSON-lines log of every action taken.
+pub struct ActionLog {
+	path: PathBuf,
+	lock: Mutex<()>,
+}
+
+impl ActionLog {
+	pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+			fs::create_dir_all(dir).with_context(|| format!("Failed to create dir: {}", dir.display()))?;
+		}
+		Ok(Self { path, lock: Mutex::new(()) })
+	}
+
+	pub fn open_default() -> Result<Self> {
+		Self::open(app_data_dir().join("actions.jsonl"))
+	}
+
+	pub fn append(&self, record: &ActionRecord) -> Result<()> {
+		let _guard = self.lock.lock().unwrap();
+		let mut f = fs::OpenOptions::new().create(true).append(true).open(&self.path)
+			.with_context(|| format!("Failed to open {}", self.path.display()))?;
+		writeln!(f, "{}", serde_json::to_string(record)?)?;
+		Ok(())
+	}
+
+	pub fn read_all(&self) -> Result<Vec<ActionRecord>> {
+		let f = match fs::File::open(&self.path) {
+			Ok(f) => f,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+			Err(e) => return Err(e).with_context(|| format!("Failed to open {}", self.path.display())),
+		};
+		let mut records = Vec::new();
+		for line in BufReader::new(f).lines() {
+			let line = line?;
+			if line.trim().is_empty() { continue; }
+			records.push(serde_json::from_str(&line)?);
+		}
+		Ok(records)
+	}
+}
+
+fn detection_name(d: &Detection) -> String {
+	match &d.kind {
+		DetectionKind::Signature { name, .. } => name.clone(),
+		DetectionKind::Heuristic { description } => format!("Heuristic: {}", description),
+	}
+}
+
+/// Applies a [`ResponsePolicy`] to detections and records what it did.
+pub struct Responder {
+	policy: ResponsePolicy,
+	log: ActionLog,
+	notify: Option<Sender<Detection>>,
+	protected: Vec<PathBuf>,
+}
+
+impl Responder {
+	pub fn new(policy: ResponsePolicy, log: ActionLog, notify: Option<Sender<Detection>>) -> Self {
+		Self { policy, log, notify, protected: Vec::new() }
+	}
+
+	/// Files that are never quarantined, such as canaries: an alert about a decoy must leave
+	/// the decoy in place.
+	pub fn with_protected_paths(mut self, paths: Vec<PathBuf>) -> Self {
+		self.protected = paths;
+		self
+	}
+
+	/// Runs the policy's actions for `d`. `actor_pid` is the process that touched the file,
+	/// when the source knows it (fanotify does, inotify does not).
+	pub fn handle(&self, d: &Detection, actor_pid: Option<u32>) -> Vec<ActionRecord> {
+		let mut records = Vec::new();
+		for action in self.policy.actions_for(d) {
+			let (outcome, detail) = match self.run(*action, d, actor_pid) {
+				Ok((outcome, detail)) => (outcome, detail),
+				Err(e) => (ActionOutcome::Failed, format!("{:#}", e)),
+			};
+			let record = ActionRecord {
+				timestamp_ms: chrono::Utc::now().timestamp_millis(),
+				path: d.path.clone(),
+				detection: detection_name(d),
+				action: *action,
+				outcome,
+				detail,
+			};
+			if let Err(e) = self.log.append(&record) { log::error!("Failed to record response action: {:#}", e); }
+			records.push(record);
+		}
+		records
+	}
+
+	fn run(&self, action: ResponseAction, d: &Detection, actor_pid: Option<u32>) -> Result<(ActionOutcome, String)> {
+		match action {
+			ResponseAction::Log => {
+				log::warn!("Detection {} in {} (severity {})", detection_name(d), d.path.display(), d.severity);
+				Ok((ActionOutcome::Done, String::new()))
+			}
+			ResponseAction::Notify => match &self.notify {
+				Some(tx) if tx.send(d.clone()).is_ok() => Ok((ActionOutcome::Done, String::new())),
+				Some(_) => Ok((ActionOutcome::Failed, "notification channel closed".into())),
+				None => Ok((ActionOutcome::Skipped, "no notification channel".into())),
+			},
+			ResponseAction::Quarantine => {
+				if self.protected.contains(&d.path) { return Ok((ActionOutcome::Skipped, "protected path".into())); }
+				if !d.path.is_file() { return Ok((ActionOutcome::Skipped, "file no longer exists".into())); }
+				let qpath = quarantine::quarantine_file_with(&d.path, Some(d))?;
+				Ok((ActionOutcome::Done, qpath.display().to_string()))
+			}
+			ResponseAction::KillProcess => {
+				let mut pids = processes_using(&d.path);
+				if let Some(pid) = actor_pid.filter(|p| !pids.contains(p)) { pids.push(pid); }
+				let own = std::process::id();
+				pids.retain(|p| *p != own && *p > 1);
+				if pids.is_empty() { return Ok((ActionOutcome::Skipped, "no process found".into())); }
+				let sys = sysinfo::System::new_all();
+				let killed: Vec<String> = pids
+					.iter()
+					.filter(|pid| sys.process(sysinfo::Pid::from_u32(**pid)).is_some_and(|p| p.kill()))
+					.map(|pid| pid.to_string())
+					.collect();
+				if killed.is_empty() { return Ok((ActionOutcome::Failed, format!("could not kill {:?}", pids))); }
+				Ok((ActionOutcome::Done, format!("killed pid {}", killed.join(", "))))
+			}
+			ResponseAction::BlockC2 => {
+				let Some(config) = &d.config else { return Ok((ActionOutcome::Skipped, "no C2 config extracted".into())) };
+				if config.c2.is_empty() { return Ok((ActionOutcome::Skipped, "config has no C2 endpoints".into())); }
+				if !firewall::is_supported() { return Ok((ActionOutcome::Skipped, "no firewall backend on this platform".into())); }
+				let blocked = firewall::block_c2(config)?;
+				if blocked.is_empty() { return Ok((ActionOutcome::Skipped, "no C2 IP addresses to block".into())); }
+				Ok((ActionOutcome::Done, format!("blocked {}", blocked.join(", "))))
+			}
+		}
+	}
+
+	/// Handles detections from a realtime channel until it closes.
+	pub fn spawn(self, rx: Receiver<Detection>) -> thread::JoinHandle<()> {
+		thread::spawn(move || {
+			for d in rx { self.handle(&d, None); }
+		})
+	}
+}
+
+/// Processes running `path` or (on Linux) holding it open.
+pub(crate) fn processes_using(path: &Path) -> Vec<u32> {
+	let mut sys = sysinfo::System::new();
+	sys.refresh_processes();
+	let mut pids: Vec<u32> = sys.processes().values().filter(|p| p.exe() == Some(path)).map(|p| p.pid().as_u32()).collect();
+	#[cfg(target_os = "linux")]
+	if let Ok(procs) = fs::read_dir("/proc") {
+		for entry in procs.flatten() {
+			let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else { continue };
+			let Ok(fds) = fs::read_dir(entry.path().join("fd")) else { continue };
+			if fds.flatten().any(|fd| fs::read_link(fd.path()).is_ok_and(|t| t == path)) && !pids.contains(&pid) {
+				pids.push(pid);
+			}
+		}
+	}
+	pids
+}
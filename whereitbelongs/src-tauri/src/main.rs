@@ -3,14 +3,25 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Mutex;
 use tauri::State;
-use wib_core::{scan_paths, ScanOptions};
+use wib_core::{scan_paths, Allowlist, ScanOptions, Scanner};
+use wib_core::quarantine::{ExportOptions, FalsePositive, Quarantine, RestoreOptions, RestoreOutcome};
 use wib_core::unrat as unrat_core;
+use wib_services::realtime::start_realtime;
+use wib_services::{start_canary_monitor, ActionLog, BehaviorOptions, CanaryGuard, CanaryStore, MountScanOptions, RealtimeGuard, RealtimeOptions, RealtimeState, RealtimeStatus, Responder, ResponsePolicy};
 use wib_database::{init_db, DbConfig, ensure_default_superadmin, authenticate_user, Role};
 
 struct AppState {
 	db_path: String,
 	db_key: [u8; 32],
+	/// Role of the signed-in account, if any.
+	session_role: Mutex<Option<Role>>,
+	/// Email of the signed-in account, recorded on approvals.
+	session_user: Mutex<Option<String>>,
+	realtime: Mutex<Option<RealtimeGuard>>,
+	/// Shared by every detection source, including realtime restarts.
+	responder: Option<crossbeam_channel::Sender<wib_core::Detection>>,
 }
 
 #[derive(Serialize)]
@@ -41,12 +52,113 @@ fn cmd_login(state: State<AppState>, req: LoginRequest) -> Result<LoginResponse,
 	ensure_default_superadmin(&conn, &cfg).map_err(|e| e.to_string())?;
 	let auth = authenticate_user(&conn, &cfg, &req.email, &req.password).map_err(|e| e.to_string())?;
 	if let Some(acc) = auth {
+		*state.session_role.lock().unwrap() = Some(acc.role.clone());
+		*state.session_user.lock().unwrap() = Some(req.email.clone());
 		Ok(LoginResponse { ok: true, role: Some(format!("{:?}", acc.role)) })
 	} else {
 		Ok(LoginResponse { ok: false, role: None })
 	}
 }
 
+#[derive(Deserialize)]
+struct ExportRequest { ids: Vec<String>, archive: String, password: Option<String> }
+
+#[derive(Serialize)]
+struct ExportResponse { exported: usize, sidecar: String }
+
+#[tauri::command]
+fn cmd_quarantine_export(state: State<AppState>, req: ExportRequest) -> Result<ExportResponse, String> {
+	let allowed = state.session_role.lock().unwrap().as_ref().is_some_and(Role::can_export_samples);
+	if !allowed { return Err("Exporting samples requires Admin or above".into()); }
+	let q = Quarantine::open_default().map_err(|e| e.to_string())?;
+	let mut opts = ExportOptions::default();
+	if let Some(p) = req.password { opts.password = p; }
+	let archive = PathBuf::from(req.archive);
+	let manifest = q.export(&req.ids, &archive, &opts).map_err(|e| e.to_string())?;
+	Ok(ExportResponse { exported: manifest.samples.len(), sidecar: wib_core::quarantine::sidecar_path(&archive).to_string_lossy().to_string() })
+}
+
+#[derive(Deserialize)]
+struct RestoreRequest { id: String, false_positive_reason: Option<String> }
+
+#[derive(Serialize)]
+struct RestoreResponse { restored_to: Option<String>, still_detected: Option<String> }
+
+/// Re-scans before restoring; a still-detected file needs `false_positive_reason` to be restored,
+/// which only a signed-in Admin or above may give.
+#[tauri::command]
+fn cmd_quarantine_restore(state: State<AppState>, req: RestoreRequest) -> Result<RestoreResponse, String> {
+	let fp = match req.false_positive_reason {
+		Some(reason) => {
+			let allowed = state.session_role.lock().unwrap().as_ref().is_some_and(Role::can_approve_false_positives);
+			let approved_by = state.session_user.lock().unwrap().clone().filter(|_| allowed);
+			let Some(approved_by) = approved_by else { return Err("Approving a false positive requires Admin or above".into()) };
+			Some(FalsePositive { approved_by, reason })
+		}
+		None => None,
+	};
+	let q = Quarantine::open_default().map_err(|e| e.to_string())?;
+	let mut scanner = Scanner::new();
+	scanner.set_allowlist(&Allowlist::load_default().map_err(|e| e.to_string())?);
+	let outcome = q.restore_rescanned(&req.id, &RestoreOptions::default(), &scanner, fp.as_ref()).map_err(|e| e.to_string())?;
+	Ok(match outcome {
+		RestoreOutcome::Restored { path, .. } => RestoreResponse { restored_to: Some(path.to_string_lossy().to_string()), still_detected: None },
+		RestoreOutcome::StillDetected(d) => RestoreResponse { restored_to: None, still_detected: Some(format!("{:?}", d.kind)) },
+	})
+}
+
+fn default_watch_paths() -> Vec<PathBuf> {
+	[dirs::download_dir(), dirs::document_dir(), dirs::desktop_dir()].into_iter().flatten().collect()
+}
+
+/// Channel into a [`Responder`] running the deployment's response policy; canaries are never quarantined.
+fn start_responder() -> Result<crossbeam_channel::Sender<wib_core::Detection>> {
+	let (tx, rx) = crossbeam_channel::unbounded::<wib_core::Detection>();
+	let (notify_tx, notify_rx) = crossbeam_channel::unbounded::<wib_core::Detection>();
+	std::thread::spawn(move || {
+		for d in notify_rx { log::warn!("Realtime detection: {:?} in {}", d.kind, d.path.display()); }
+	});
+	Responder::new(ResponsePolicy::load_default()?, ActionLog::open_default()?, Some(notify_tx))
+		.with_protected_paths(CanaryStore::open_default()?.paths())
+		.spawn(rx);
+	Ok(tx)
+}
+
+fn start_default_realtime(responder: Option<&crossbeam_channel::Sender<wib_core::Detection>>) -> Result<RealtimeGuard> {
+	let Some(tx) = responder else { anyhow::bail!("Response handling is not running") };
+	let scan_options = ScanOptions { exclude_paths: CanaryStore::open_default()?.paths(), ..Default::default() };
+	let opts = RealtimeOptions { paths: default_watch_paths(), behavior: Some(BehaviorOptions::default()), scan_options, ..Default::default() };
+	start_realtime(opts, tx.clone())
+}
+
+/// Plants canaries in the documents and desktop folders.
+fn deploy_default_canaries() -> Result<CanaryStore> {
+	let mut store = CanaryStore::open_default()?;
+	for dir in [dirs::document_dir(), dirs::desktop_dir()].into_iter().flatten() {
+		store.deploy(dir)?;
+	}
+	store.refresh()?;
+	Ok(store)
+}
+
+#[tauri::command]
+fn cmd_realtime_status(state: State<AppState>) -> Result<Option<RealtimeStatus>, String> {
+	Ok(state.realtime.lock().unwrap().as_ref().map(RealtimeGuard::status))
+}
+
+/// Turns protection on (starting or resuming the monitor) or off (pausing it).
+#[tauri::command]
+fn cmd_realtime_set(state: State<AppState>, enabled: bool) -> Result<Option<RealtimeStatus>, String> {
+	let mut rt = state.realtime.lock().unwrap();
+	match (rt.as_ref().map(RealtimeGuard::state), enabled) {
+		(None | Some(RealtimeState::Stopped), true) => *rt = Some(start_default_realtime(state.responder.as_ref()).map_err(|e| e.to_string())?),
+		(Some(_), true) => rt.as_ref().unwrap().resume(),
+		(Some(_), false) => rt.as_ref().unwrap().pause(),
+		(None, false) => {}
+	}
+	Ok(rt.as_ref().map(RealtimeGuard::status))
+}
+
 fn main() {
 	let db_dir = std::env::var("WIB_DATA_DIR").unwrap_or_else(|_| {
 		let d = dirs::data_dir().unwrap_or(std::env::temp_dir());
@@ -55,10 +167,30 @@ fn main() {
 	std::fs::create_dir_all(&db_dir).ok();
 	let db_path = format!("{}/accounts.db", db_dir.trim_end_matches('/'));
 	let key: [u8; 32] = [7u8; 32];
+	// canaries go down first so the responder knows to leave them alone
+	let canaries = deploy_default_canaries().map_err(|e| log::error!("Failed to deploy canaries: {:#}", e)).ok();
+	let responder = start_responder().map_err(|e| log::error!("Failed to start response handling: {:#}", e)).ok();
+	// lives until the app exits
+	let _canaries: Option<CanaryGuard> = canaries.zip(responder.clone()).and_then(|(store, tx)| {
+		start_canary_monitor(store.canaries(), tx).map_err(|e| log::error!("Failed to monitor canaries: {:#}", e)).ok()
+	});
+	#[cfg(target_os = "linux")]
+	let _mounts = responder.clone().and_then(|tx| {
+		wib_services::mounts::start_mount_monitor(MountScanOptions::default(), std::sync::Arc::new(Scanner::new()), tx, None)
+			.map_err(|e| log::error!("Failed to start mount monitor: {:#}", e))
+			.ok()
+	});
+	let realtime = match start_default_realtime(responder.as_ref()) {
+		Ok(g) => Some(g),
+		Err(e) => {
+			log::error!("Failed to start realtime protection: {:#}", e);
+			None
+		}
+	};
 
 	tauri::Builder::default()
-		.manage(AppState { db_path, db_key: key })
-		.invoke_handler(tauri::generate_handler![cmd_scan, cmd_unrat_recover, cmd_login])
+		.manage(AppState { db_path, db_key: key, session_role: Mutex::new(None), session_user: Mutex::new(None), realtime: Mutex::new(realtime), responder })
+		.invoke_handler(tauri::generate_handler![cmd_scan, cmd_unrat_recover, cmd_login, cmd_quarantine_export, cmd_quarantine_restore, cmd_realtime_status, cmd_realtime_set])
 		.run(tauri::generate_context!())
 		.expect("error while running tauri application");
 }
\ No newline at end of file
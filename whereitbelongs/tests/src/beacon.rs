@@ -0,0 +1,81 @@
+use std::net::SocketAddr;
+use wib_services::{BeaconAnalyzer, BeaconOptions, Connection, NetEvent, NetEventKind, Protocol};
+
+fn event(kind: NetEventKind, at_ms: i64, process: &str, remote: &str, bytes: Option<(u64, u64)>) -> NetEvent {
+	NetEvent {
+		timestamp_ms: at_ms,
+		description: String::new(),
+		kind,
+		connection: Some(Connection {
+			protocol: Protocol::Tcp,
+			local: "10.0.2.15:40000".parse().unwrap(),
+			remote: remote.parse::<SocketAddr>().unwrap(),
+			state: "ESTABLISHED".into(),
+			uid: 1000,
+			inode: at_ms as u64,
+			pid: Some(4242),
+			process: Some(process.rsplit('/').next().unwrap().into()),
+			exe: Some(if process.starts_with('/') { process.into() } else { format!("/tmp/.cache/{process}").into() }),
+			bytes_sent: bytes.map(|b| b.0),
+			bytes_received: bytes.map(|b| b.1),
+		}),
+	}
+}
+
+// Opens at the given offsets (seconds), each connection closing with `bytes`.
+fn feed(analyzer: &mut BeaconAnalyzer, process: &str, times: &[f64], bytes: (u64, u64)) -> Vec<f64> {
+	let mut confidences = Vec::new();
+	for t in times {
+		let ms = (t * 1000.0) as i64;
+		confidences.extend(analyzer.observe(&event(NetEventKind::Opened, ms, process, "203.0.113.7:443", None)).map(|f| f.confidence));
+		analyzer.observe(&event(NetEventKind::Closed, ms + 500, process, "203.0.113.7:443", Some(bytes)));
+	}
+	confidences
+}
+
+#[test]
+fn jittered_beacon_is_flagged_and_noise_is_not() {
+	let mut analyzer = BeaconAnalyzer::new(BeaconOptions::default());
+	// 60s sleep with up to ±10% jitter, small check-ins
+	let jitter = [0.0, 4.1, -3.2, 5.5, -1.0, 2.2, -5.9, 0.7, 3.3, -2.4, 1.8, -4.4];
+	let beacon: Vec<f64> = jitter.iter().enumerate().map(|(i, j)| i as f64 * 60.0 + j).collect();
+	let found = feed(&mut analyzer, "svc-update", &beacon, (900, 300));
+	assert_eq!(found.len(), 2, "reported when crossing, then again after six more");
+	assert!(found[0] > 0.8, "{found:?}");
+
+	let noise = [0.0, 3.0, 95.0, 97.5, 300.0, 302.0, 303.5, 800.0, 1400.0, 1402.0, 1500.0];
+	assert!(feed(&mut analyzer, "chatty", &noise, (900, 300)).is_empty());
+
+	let finding = &analyzer.findings()[0];
+	assert!(finding.process.ends_with("svc-update"));
+	assert!((finding.mean_interval_secs - 60.0).abs() < 1.0);
+	assert!(finding.asymmetry.unwrap() > 0.0);
+	assert!(finding.to_detection().severity < 8);
+}
+
+#[test]
+fn bulk_downloads_and_allowlisted_processes_score_low() {
+	let mut analyzer = BeaconAnalyzer::new(BeaconOptions::default());
+	let hourly: Vec<f64> = (0..12).map(|i| i as f64 * 3600.0).collect();
+	assert!(feed(&mut analyzer, "/usr/lib/firefox/firefox", &hourly, (900, 300)).is_empty());
+	assert!(analyzer.findings().is_empty());
+	// the name alone is not trusted
+	assert!(!feed(&mut analyzer, "firefox", &hourly, (900, 300)).is_empty());
+	analyzer.allow("firefox");
+	assert!(feed(&mut analyzer, "mirror-sync", &hourly, (2_000, 50_000_000)).is_empty());
+	let sync = &analyzer.findings()[0];
+	assert!(sync.periodicity > 0.99 && sync.confidence < 0.7);
+
+	analyzer.allow("mirror-sync");
+	assert!(analyzer.findings().is_empty());
+}
+
+#[test]
+fn idle_flows_are_forgotten() {
+	let mut analyzer = BeaconAnalyzer::new(BeaconOptions { idle_secs: 3600.0, ..Default::default() });
+	let minutely: Vec<f64> = (0..8).map(|i| i as f64 * 60.0).collect();
+	feed(&mut analyzer, "svc-update", &minutely, (900, 300));
+	assert_eq!(analyzer.findings().len(), 1);
+	feed(&mut analyzer, "other", &[3.0 * 3600.0], (900, 300));
+	assert!(analyzer.findings().is_empty());
+}
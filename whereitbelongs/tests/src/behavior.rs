@@ -0,0 +1,91 @@
+use anyhow::Result;
+use std::fs;
+use wib_core::DetectionKind;
+use wib_services::{BehaviorEngine, BehaviorOptions, FileActivity};
+
+// xorshift bytes stand in for ciphertext
+fn noise(len: usize, mut seed: u64) -> Vec<u8> {
+	(0..len).map(|_| {
+		seed ^= seed << 13;
+		seed ^= seed >> 7;
+		seed ^= seed << 17;
+		seed as u8
+	}).collect()
+}
+
+#[test]
+fn mass_encrypt_and_rename_raises_one_alert() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let opts = BehaviorOptions { extension_changes: 10, entropy_jumps: 10, ..Default::default() };
+	let mut engine = BehaviorEngine::new(opts, vec![dir.path().to_path_buf()]);
+	let mut alerts = Vec::new();
+	for i in 0..15u64 {
+		let plain = dir.path().join(format!("report{i}.txt"));
+		fs::write(&plain, "quarterly numbers, nothing to see here\n".repeat(100))?;
+		assert!(engine.observe(&FileActivity::Created(plain.clone()), None).is_none());
+		fs::write(&plain, noise(8192, i + 1))?;
+		let locked = plain.with_extension("txt.locked");
+		fs::rename(&plain, &locked)?;
+		alerts.extend(engine.observe(&FileActivity::Renamed { from: plain, to: locked }, None));
+	}
+	assert_eq!(alerts.len(), 1);
+	assert_eq!(alerts[0].severity, 10);
+	let DetectionKind::Heuristic { description } = &alerts[0].kind else { panic!("expected heuristic alert") };
+	assert!(description.contains("10 extension changes"), "{description}");
+	Ok(())
+}
+
+#[test]
+fn encrypted_copies_replacing_deleted_originals_count_as_extension_changes() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let opts = BehaviorOptions { extension_changes: 10, ..Default::default() };
+	let mut engine = BehaviorEngine::new(opts, vec![dir.path().to_path_buf()]);
+	let mut alerts = Vec::new();
+	for i in 0..10u64 {
+		let plain = dir.path().join(format!("report{i}.docx"));
+		let locked = dir.path().join(format!("report{i}.docx.locked"));
+		fs::write(&locked, noise(8192, i + 1))?;
+		alerts.extend(engine.observe(&FileActivity::Created(locked), None));
+		alerts.extend(engine.observe(&FileActivity::Removed(plain), None));
+	}
+	assert_eq!(alerts.len(), 1);
+	let DetectionKind::Heuristic { description } = &alerts[0].kind else { panic!("expected heuristic alert") };
+	assert!(description.contains("10 extension changes"), "{description}");
+	Ok(())
+}
+
+#[test]
+fn ransom_note_is_flagged_but_ordinary_readme_is_not() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let mut engine = BehaviorEngine::new(BehaviorOptions::default(), vec![dir.path().to_path_buf()]);
+	let readme = dir.path().join("README.txt");
+	fs::write(&readme, "Build with cargo build --release.")?;
+	assert!(engine.observe(&FileActivity::Created(readme), None).is_none());
+	let note = dir.path().join("HOW_TO_DECRYPT_FILES.txt");
+	fs::write(&note, "All your files are encrypted. Send 0.1 Bitcoin to recover them.")?;
+	assert!(engine.observe(&FileActivity::Created(note), None).is_some());
+	Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn suspends_the_reported_writer() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let mut engine = BehaviorEngine::new(BehaviorOptions { suspend: true, ..Default::default() }, vec![dir.path().to_path_buf()]);
+	let mut child = std::process::Command::new("sleep").arg("30").spawn()?;
+	let note = dir.path().join("HOW_TO_DECRYPT_FILES.txt");
+	fs::write(&note, "All your files are encrypted. Send 0.1 Bitcoin to recover them.")?;
+	assert!(engine.observe(&FileActivity::Created(note), Some(child.id())).is_some());
+	// SIGSTOP lands asynchronously
+	let state = || fs::read_to_string(format!("/proc/{}/stat", child.id())).ok().and_then(|s| s.rsplit(") ").next()?.chars().next());
+	let mut stopped = false;
+	for _ in 0..50 {
+		stopped = state() == Some('T');
+		if stopped { break; }
+		std::thread::sleep(std::time::Duration::from_millis(20));
+	}
+	child.kill()?;
+	child.wait()?;
+	assert!(stopped);
+	Ok(())
+}
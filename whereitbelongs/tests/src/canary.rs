@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::fs;
+use std::process::Command;
+use std::time::Duration;
+use wib_core::{DetectionKind, ScanOptions, Scanner};
+use wib_services::{start_canary_monitor, CanaryStore};
+
+#[test]
+fn canaries_are_tracked_refreshed_and_skipped_by_scans() -> Result<()> {
+	let docs = tempfile::tempdir()?;
+	let data = tempfile::tempdir()?;
+	let mut store = CanaryStore::open(data.path().join("canaries.json"))?;
+	let canary = store.deploy(docs.path())?;
+	assert_eq!(store.deploy(docs.path())?.path, canary.path);
+	fs::write(docs.path().join("dropper.txt"), "remcos connect")?;
+	fs::write(&canary.path, "remcos connect")?;
+
+	let opts = ScanOptions { exclude_paths: store.paths(), ..Default::default() };
+	let found = Scanner::new().scan_paths(&[docs.path()], &opts);
+	assert_eq!(found.len(), 1);
+	assert!(found[0].path.ends_with("dropper.txt"));
+
+	assert_eq!(store.refresh()?, vec![canary.path.clone()]);
+	let reopened = CanaryStore::open(data.path().join("canaries.json"))?;
+	assert!(reopened.is_canary(&canary.path));
+	assert!(fs::read_to_string(&canary.path)?.starts_with("account,username,password"));
+	assert!(store.refresh()?.is_empty());
+	Ok(())
+}
+
+#[test]
+fn touching_a_canary_alerts_with_the_acting_process() -> Result<()> {
+	let docs = tempfile::tempdir()?;
+	let data = tempfile::tempdir()?;
+	let mut store = CanaryStore::open(data.path().join("canaries.json"))?;
+	let canary = store.deploy(docs.path())?;
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let guard = start_canary_monitor(store.canaries(), tx)?;
+
+	// refreshing an intact canary must stay quiet
+	store.refresh()?;
+	assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+
+	let child = Command::new("sh").arg("-c").arg(format!("echo encrypted >> '{}'", canary.path.display())).spawn()?;
+	let pid = child.id();
+	child.wait_with_output()?;
+	let d = rx.recv_timeout(Duration::from_secs(5))?;
+	assert_eq!(d.path, canary.path);
+	assert_eq!(d.severity, 10);
+	let DetectionKind::Heuristic { description } = &d.kind else { panic!("expected heuristic alert") };
+	assert!(description.contains("modified"), "{description}");
+	if guard.attributes_processes() { assert!(description.contains(&format!("pid {pid}")), "{description}"); }
+	Ok(())
+}
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use std::fs;
+use wib_core::clamav::{import_str, ClamFormat};
+use wib_core::{DetectionKind, ScanOptions, Scanner};
+
+#[test]
+fn imports_ndb_and_reports_unsupported_lines() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let sigs = dir.path().join("custom.ndb");
+	fs::write(
+		&sigs,
+		"Win.Trojan.Dropper-1:0:*:6576696c7061796c6f6164{2-4}6d61726b\n\
+		Doc.Macro.Bad-2:2:*:aabbcc\n\
+		Win.Packed.Thing-3:1:S1+4:aabbcc\n\
+		Txt.Tool.Agent-4:7:0,16:68656c6c6f(20|2d)776f726c64\n",
+	)?;
+	let mut scanner = Scanner::new();
+	let report = scanner.load_clamav(&sigs)?;
+	assert_eq!(report.imported, 2);
+	assert_eq!(report.issues.iter().map(|i| i.line).collect::<Vec<_>>(), vec![2, 3]);
+
+	let sample = dir.path().join("sample.bin");
+	fs::write(&sample, b"\x00\x01evilpayload--mark\x00")?;
+	let text = dir.path().join("note.txt");
+	fs::write(&text, "say HELLO-World")?;
+	let opts = ScanOptions::default();
+	let names: Vec<String> = scanner
+		.scan_paths(&[sample, text], &opts)
+		.into_iter()
+		.filter_map(|d| match d.kind {
+			DetectionKind::Signature { name, .. } => Some(name),
+			_ => None,
+		})
+		.collect();
+	assert!(names.contains(&"Win.Trojan.Dropper-1".to_string()));
+	assert!(names.contains(&"Txt.Tool.Agent-4".to_string()));
+	Ok(())
+}
+
+#[test]
+fn imports_hash_and_logical_signatures() {
+	let (hashes, report) = import_str(ClamFormat::Hdb, "d0263a8bf5f8a422df6b0777c6864be2:14:Bad.Hash-1\nnot-a-hash:12:Broken\n");
+	assert_eq!((hashes.len(), report.issues.len()), (1, 1));
+	assert_eq!(hashes.match_buffer(b"hash-me please"), Some("Bad.Hash-1"));
+	let (logical, report) =
+		import_str(ClamFormat::Ldb, "Win.Trojan.Logic-1;Engine:51-255,Target:0;0&(1|2)>1;616263;646566::i;676869\n");
+	assert!(report.issues.is_empty(), "{:?}", report.issues);
+	assert_eq!(logical.match_buffer(b"abc DEF def"), Some("Win.Trojan.Logic-1"));
+	assert_eq!(logical.match_buffer(b"abc def"), None);
+}
+
+#[test]
+fn entry_point_signatures_survive_overflowing_sections() {
+	let mut pe = vec![0u8; 0x360];
+	pe[..2].copy_from_slice(b"MZ");
+	pe[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+	pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+	pe[0x46..0x48].copy_from_slice(&1u16.to_le_bytes());
+	pe[0x54..0x56].copy_from_slice(&0xe0u16.to_le_bytes());
+	pe[0x58..0x5a].copy_from_slice(&0x10bu16.to_le_bytes());
+	pe[0x68..0x6c].copy_from_slice(&0xffff_f100u32.to_le_bytes());
+	// one section whose virtual address + size wraps past u32::MAX
+	for (off, v) in [(8, 0x2000u32), (12, 0xffff_f000), (16, 0x200), (20, 0x160)] {
+		pe[0x138 + off..0x13c + off].copy_from_slice(&v.to_le_bytes());
+	}
+	pe[0x160..0x163].copy_from_slice(&[0xaa, 0xbb, 0xcc]);
+	let (sigs, report) = import_str(ClamFormat::Ndb, "Win.Test.Ep-1:1:EP+0:aabbcc\nWin.Test.Any-2:1:*:aabbcc\n");
+	assert_eq!(report.imported, 2);
+	assert_eq!(sigs.match_buffer(&pe), Some("Win.Test.Any-2"));
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use wib_server::{ClamdConfig, ClamdServer};
+
+fn roundtrip(addr: &str, request: &[u8]) -> Result<String> {
+	let mut conn = TcpStream::connect(addr)?;
+	conn.write_all(request)?;
+	let mut out = String::new();
+	conn.read_to_string(&mut out)?;
+	Ok(out)
+}
+
+#[test]
+fn answers_ping_scan_and_instream() -> Result<()> {
+	let listener = TcpListener::bind("127.0.0.1:0")?;
+	let addr = listener.local_addr()?.to_string();
+	let server = Arc::new(ClamdServer::new(ClamdConfig::default())?);
+	std::thread::spawn(move || server.serve_tcp(listener));
+
+	assert_eq!(roundtrip(&addr, b"zPING\0")?, "PONG\0");
+	assert_eq!(roundtrip(&addr, b"nPING\n")?, "PONG\n");
+
+	let dir = tempfile::tempdir()?;
+	let bad = dir.path().join("dropper.ps1");
+	std::fs::write(&bad, "remcos connect")?;
+	let reply = roundtrip(&addr, format!("nSCAN {}\n", bad.display()).as_bytes())?;
+	assert_eq!(reply, format!("{}: RAT.Remcos.heur FOUND\n", bad.display()));
+
+	let mut req = b"zINSTREAM\0".to_vec();
+	for chunk in [&b"hello "[..], &b"world"[..]] {
+		req.extend((chunk.len() as u32).to_be_bytes());
+		req.extend(chunk);
+	}
+	req.extend(0u32.to_be_bytes());
+	assert_eq!(roundtrip(&addr, &req)?, "stream: OK\0");
+	Ok(())
+}
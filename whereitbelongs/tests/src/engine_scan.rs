@@ -1,6 +1,6 @@
 use anyhow::Result;
 use std::fs;
-use wib_core::{scan_paths, ScanOptions, DetectionKind};
+use wib_core::{scan_bytes, scan_paths, scan_reader, virtual_path, ScanOptions, DetectionKind};
 
 #[test]
 fn detects_simple_rat_pattern() -> Result<()> {
@@ -11,4 +11,15 @@ fn detects_simple_rat_pattern() -> Result<()> {
 	let detections = scan_paths(&[file_path], opts);
 	assert!(detections.iter().any(|d| matches!(d.kind, DetectionKind::Signature{..} | DetectionKind::Heuristic{..})), "no detection found");
 	Ok(())
-}
\ No newline at end of file
+}
+#[test]
+fn scans_buffers_and_readers_with_virtual_path() -> Result<()> {
+	let payload = b"powershell -enc SQBFAFgA; remcos connect";
+	let detections = scan_bytes(payload, "upload.ps1");
+	assert_eq!(detections.len(), 1);
+	assert_eq!(detections[0].path, virtual_path("upload.ps1"));
+	let streamed = scan_reader(std::io::Cursor::new(payload.to_vec()), "upload.ps1")?;
+	assert_eq!(streamed[0].sha256, detections[0].sha256);
+	assert!(scan_bytes(b"hello world", "notes.txt").is_empty());
+	Ok(())
+}
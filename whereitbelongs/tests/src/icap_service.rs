@@ -0,0 +1,84 @@
+use anyhow::Result;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use wib_server::{IcapConfig, IcapServer};
+
+fn respmod(body: &str, preview: bool) -> Vec<u8> {
+	let req_hdr = "GET http://downloads.example.com/tool.ps1 HTTP/1.1\r\nHost: downloads.example.com\r\n\r\n";
+	let res_hdr = "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n";
+	let mut out = format!(
+		"RESPMOD icap://127.0.0.1/respmod ICAP/1.0\r\nHost: 127.0.0.1\r\nAllow: 204\r\n{}Encapsulated: req-hdr=0, res-hdr={}, res-body={}\r\n\r\n{}{}",
+		if preview { "Preview: 1024\r\n" } else { "" },
+		req_hdr.len(),
+		req_hdr.len() + res_hdr.len(),
+		req_hdr,
+		res_hdr
+	);
+	out.push_str(&format!("{:x}\r\n{}\r\n", body.len(), body));
+	out.push_str(if preview { "0; ieof\r\n\r\n" } else { "0\r\n\r\n" });
+	out.into_bytes()
+}
+
+fn status_line(reader: &mut BufReader<TcpStream>) -> Result<String> {
+	let mut line = String::new();
+	reader.read_line(&mut line)?;
+	Ok(line.trim_end().to_string())
+}
+
+#[test]
+fn passes_clean_bodies_and_blocks_infected_ones() -> Result<()> {
+	let listener = TcpListener::bind("127.0.0.1:0")?;
+	let addr = listener.local_addr()?;
+	let server = Arc::new(IcapServer::new(IcapConfig::default())?);
+	std::thread::spawn(move || server.serve_tcp(listener));
+
+	let mut conn = TcpStream::connect(addr)?;
+	let mut reader = BufReader::new(conn.try_clone()?);
+	conn.write_all(&respmod("just a readme", true))?;
+	assert_eq!(status_line(&mut reader)?, "ICAP/1.0 204 No Content");
+	let mut line = String::new();
+	while reader.read_line(&mut line)? > 2 { line.clear(); }
+
+	// same connection: infected body gets a block page
+	conn.write_all(&respmod("remcos connect", false))?;
+	assert_eq!(status_line(&mut reader)?, "ICAP/1.0 200 OK");
+	conn.shutdown(std::net::Shutdown::Write)?;
+	let mut rest = String::new();
+	reader.read_to_string(&mut rest)?;
+	assert!(rest.contains("HTTP/1.1 403 Forbidden"));
+	assert!(rest.contains("RAT.Remcos.heur"));
+	Ok(())
+}
+
+#[test]
+fn streams_oversized_bodies_back_whole() -> Result<()> {
+	let listener = TcpListener::bind("127.0.0.1:0")?;
+	let addr = listener.local_addr()?;
+	let server = Arc::new(IcapServer::new(IcapConfig { max_body_bytes: 10, ..IcapConfig::default() })?);
+	std::thread::spawn(move || server.serve_tcp(listener));
+
+	// no `Allow: 204`, and the second chunk crosses the buffer limit while the third would fit again
+	let res_hdr = "HTTP/1.1 200 OK\r\n\r\n";
+	let request = format!(
+		"RESPMOD icap://127.0.0.1/respmod ICAP/1.0\r\nHost: 127.0.0.1\r\nEncapsulated: res-hdr=0, res-body={}\r\n\r\n{}4\r\nabcd\r\nc\r\nefghijklmnop\r\n2\r\nqr\r\n0\r\n\r\n",
+		res_hdr.len(),
+		res_hdr
+	);
+	let mut conn = TcpStream::connect(addr)?;
+	conn.write_all(request.as_bytes())?;
+	conn.shutdown(std::net::Shutdown::Write)?;
+	let mut reader = BufReader::new(conn);
+	assert_eq!(status_line(&mut reader)?, "ICAP/1.0 200 OK");
+	let mut rest = String::new();
+	reader.read_to_string(&mut rest)?;
+	let (_, body) = rest.split_once(res_hdr).expect("encapsulated response header");
+	let mut lines = body.split("\r\n");
+	let mut echoed = String::new();
+	while let Some(size) = lines.next().map(|l| usize::from_str_radix(l, 16)).transpose()? {
+		if size == 0 { break; }
+		echoed.push_str(lines.next().unwrap_or_default());
+	}
+	assert_eq!(echoed, "abcdefghijklmnopqr");
+	Ok(())
+}
@@ -0,0 +1,23 @@
+use wib_core::ioc::refang;
+use wib_core::{extract_iocs, Ioc, IocKind, IocOptions};
+
+#[test]
+fn extracts_refanged_iocs_and_drops_private_ranges() {
+	let text = "beacon hxxps://c2.evil-domain[.]com/gate.php and 185[.]220[.]101[.]7, fallback 192.168.1.20\n\
+		mail ops[@]badmail.ru, drop C:\\Users\\Public\\svchost.exe, eth 0x52908400098527886E0F7030069857D2E4169EE7";
+	let iocs = extract_iocs(text, &IocOptions::default());
+	let has = |kind: IocKind, value: &str| iocs.iter().any(|i| i.kind == kind && i.value == value);
+	assert!(has(IocKind::Url, "https://c2.evil-domain.com/gate.php"));
+	assert!(has(IocKind::Domain, "c2.evil-domain.com"));
+	assert!(has(IocKind::Ipv4, "185.220.101.7"));
+	assert!(has(IocKind::Email, "ops@badmail.ru"));
+	assert!(has(IocKind::FilePath, "C:\\Users\\Public\\svchost.exe"));
+	assert!(has(IocKind::EthereumAddress, "0x52908400098527886E0F7030069857D2E4169EE7"));
+	assert!(!iocs.iter().any(|i| i.value == "192.168.1.20"), "private address should be filtered");
+	let ip = iocs.iter().find(|i| i.kind == IocKind::Ipv4).unwrap();
+	assert_eq!(ip.defanged(), "185[.]220[.]101[.]7");
+	let url = Ioc { kind: IocKind::Url, value: "http://myhttpserver.com/http".into() };
+	assert_eq!(url.defanged(), "hxxp://myhttpserver[.]com/http");
+	let domain = Ioc { kind: IocKind::Domain, value: "myhttpserver.com".into() };
+	assert_eq!(refang(&domain.defanged()), "myhttpserver.com");
+}
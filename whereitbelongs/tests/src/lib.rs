@@ -1,4 +1,37 @@
 #[cfg(test)]
 mod engine_scan;
 #[cfg(test)]
-mod db_auth;
\ No newline at end of file
+mod db_auth;
+#[cfg(test)]
+mod rat_config;
+
+#[cfg(test)]
+mod ioc_extract;
+#[cfg(test)]
+mod clamd_protocol;
+#[cfg(test)]
+mod icap_service;
+#[cfg(test)]
+mod mail_scan;
+#[cfg(test)]
+mod clamav_import;
+#[cfg(test)]
+mod quarantine;
+#[cfg(test)]
+mod realtime;
+#[cfg(test)]
+mod response;
+#[cfg(test)]
+mod behavior;
+#[cfg(test)]
+mod canary;
+#[cfg(test)]
+mod mounts;
+#[cfg(test)]
+mod netmon;
+#[cfg(test)]
+mod beacon;
+#[cfg(test)]
+mod netioc;
+#[cfg(test)]
+mod pcap;
@@ -0,0 +1,82 @@
+use anyhow::Result;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use wib_core::{scan_paths, ScanOptions};
+use wib_server::{MilterAction, MilterConfig, MilterServer};
+
+// "remcos connect" as base64
+const MESSAGE: &str = "From: billing@example.com\r\nTo: victim@example.org\r\nSubject: Invoice\r\nMIME-Version: 1.0\r\n\
+	Content-Type: multipart/mixed; boundary=\"XYZ\"\r\n\r\n--XYZ\r\nContent-Type: text/plain\r\n\r\nPlease see attached.\r\n\
+	--XYZ\r\nContent-Type: application/octet-stream; name=\"invoice.js\"\r\nContent-Disposition: attachment; filename=\"invoice.js\"\r\n\
+	Content-Transfer-Encoding: base64\r\n\r\ncmVtY29z\r\nIGNvbm5lY3Q=\r\n--XYZ--\r\n";
+
+#[test]
+fn scans_eml_attachments_through_scan_paths() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let path = dir.path().join("invoice.eml");
+	std::fs::write(&path, MESSAGE)?;
+	let detections = scan_paths(&[path], ScanOptions::default());
+	assert_eq!(detections.len(), 1);
+	assert_eq!(detections[0].member.as_deref(), Some("invoice.js"));
+	Ok(())
+}
+
+#[test]
+fn body_lines_starting_with_from_do_not_split_the_message() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let path = dir.path().join("newsletter.eml");
+	std::fs::write(&path, MESSAGE.replace("Please see attached.\r\n", "Please see attached.\r\n\r\nFrom the billing team\r\n\r\nRegards.\r\n"))?;
+	let detections = scan_paths(&[path], ScanOptions::default());
+	assert_eq!(detections.len(), 1);
+	assert_eq!(detections[0].member.as_deref(), Some("invoice.js"));
+	Ok(())
+}
+
+fn send(conn: &mut TcpStream, cmd: u8, data: &[u8]) -> Result<()> {
+	conn.write_all(&((data.len() + 1) as u32).to_be_bytes())?;
+	conn.write_all(&[cmd])?;
+	conn.write_all(data)?;
+	Ok(())
+}
+
+fn recv(conn: &mut TcpStream) -> Result<(u8, Vec<u8>)> {
+	let mut len = [0u8; 4];
+	conn.read_exact(&mut len)?;
+	let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+	conn.read_exact(&mut buf)?;
+	Ok((buf[0], buf[1..].to_vec()))
+}
+
+#[test]
+fn milter_tags_infected_message() -> Result<()> {
+	let listener = TcpListener::bind("127.0.0.1:0")?;
+	let addr = listener.local_addr()?;
+	let config = MilterConfig { action: MilterAction::Tag, ..Default::default() };
+	let server = Arc::new(MilterServer::new(config)?);
+	std::thread::spawn(move || server.serve_tcp(listener));
+
+	let mut conn = TcpStream::connect(addr)?;
+	let mut optneg = Vec::new();
+	for v in [6u32, 0x1ff, 0x1fffff] { optneg.extend(v.to_be_bytes()); }
+	send(&mut conn, b'O', &optneg)?;
+	assert_eq!(recv(&mut conn)?.0, b'O');
+	let (head, body) = MESSAGE.split_once("\r\n\r\n").unwrap();
+	for line in head.split("\r\n") {
+		let (k, v) = line.split_once(": ").unwrap();
+		send(&mut conn, b'L', format!("{}\0{}\0", k, v).as_bytes())?;
+		assert_eq!(recv(&mut conn)?.0, b'c');
+	}
+	send(&mut conn, b'N', &[])?;
+	assert_eq!(recv(&mut conn)?.0, b'c');
+	send(&mut conn, b'B', body.as_bytes())?;
+	assert_eq!(recv(&mut conn)?.0, b'c');
+	send(&mut conn, b'E', &[])?;
+	let (cmd, data) = recv(&mut conn)?;
+	assert_eq!(cmd, b'h');
+	let header = String::from_utf8_lossy(&data).to_string();
+	assert!(header.starts_with("X-WIB-Result\0infected; threat=RAT.Remcos.heur"), "{}", header);
+	assert_eq!(recv(&mut conn)?.0, b'a');
+	send(&mut conn, b'Q', &[])?;
+	Ok(())
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use wib_core::Scanner;
+use wib_services::mounts::{mount_of, needs_polling, parse_mountinfo, scan_mount};
+use wib_services::{MountInfo, MountScanOptions};
+
+const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+36 22 8:17 / /media/usb\\040stick rw,nosuid,nodev master:2 - vfat /dev/sdb1 rw,uid=1000
+40 22 0:35 / /mnt/nfs rw - nfs4 server:/export rw,vers=4.2
+";
+
+fn usb(root: &Path) -> MountInfo {
+	MountInfo {
+		mount_id: 99,
+		parent_id: 1,
+		device: "8:17".into(),
+		root: "/".into(),
+		mount_point: root.to_path_buf(),
+		options: "rw".into(),
+		fs_type: "vfat".into(),
+		source: "/dev/sdb1".into(),
+	}
+}
+
+#[test]
+fn mountinfo_is_parsed_and_filtered() {
+	let mounts = parse_mountinfo(MOUNTINFO);
+	assert_eq!(mounts.len(), 3);
+	assert_eq!(mounts[1].mount_point, Path::new("/media/usb stick"));
+	assert_eq!(mounts[2].fs_type, "nfs4");
+	assert_eq!(mount_of(&mounts, Path::new("/media/usb stick/a.exe")).unwrap().mount_id, 36);
+	let opts = MountScanOptions::default();
+	assert!(opts.wants(&mounts[1]));
+	assert!(!opts.wants(&mounts[2]));
+	assert!(needs_polling(&mounts[2].fs_type) && needs_polling("fuse.sshfs"));
+	assert!(!needs_polling("ext4") && !needs_polling("fuseblk"));
+}
+
+#[test]
+fn usb_worm_artifacts_are_reported_within_budget() -> Result<()> {
+	let stick = tempfile::tempdir()?;
+	fs::write(stick.path().join("AUTORUN.INF"), "[AutoRun]\r\nopen=setup.exe /silent\r\nicon=setup.exe,0\r\n")?;
+	fs::create_dir(stick.path().join("Photos"))?;
+	let mut lnk = vec![0x4c, 0, 0, 0, 0x01, 0x14, 0x02, 0x00];
+	lnk.resize(0x4c, 0);
+	lnk.extend("%COMSPEC% /c start .\\_\\payload.js".encode_utf16().flat_map(u16::to_le_bytes));
+	fs::write(stick.path().join("Photos.lnk"), &lnk)?;
+	fs::write(stick.path().join("notes.txt"), "remcos connect")?;
+
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let report = scan_mount(&usb(stick.path()), &Scanner::new(), &MountScanOptions::default(), &tx, &AtomicBool::new(false));
+	assert!(!report.truncated);
+	assert_eq!(report.detections, 3);
+	let descriptions: Vec<String> = rx.try_iter().map(|d| format!("{:?}", d.kind)).collect();
+	assert!(descriptions.iter().any(|d| d.contains("autorun.inf launches setup.exe /silent")));
+	assert!(descriptions.iter().any(|d| d.contains("disguised as folder Photos launches %comspec%")));
+
+	let tight = MountScanOptions { max_bytes: 1, ..Default::default() };
+	assert!(scan_mount(&usb(stick.path()), &Scanner::new(), &tight, &tx, &AtomicBool::new(false)).truncated);
+	Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn new_mounts_are_scanned() -> Result<()> {
+	use std::process::Command;
+	use std::sync::Arc;
+	use std::time::Duration;
+	use wib_services::mounts::start_mount_monitor;
+
+	let base = tempfile::tempdir()?;
+	let point = base.path().join("stick");
+	fs::create_dir(&point)?;
+	let opts = MountScanOptions { fs_types: Vec::new(), mount_prefixes: vec![base.path().to_path_buf()], settle: Duration::from_millis(300), ..Default::default() };
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let (report_tx, report_rx) = crossbeam_channel::unbounded();
+	let _guard = start_mount_monitor(opts, Arc::new(Scanner::new()), tx, Some(report_tx))?;
+	// needs root and a mount binary; skip where the sandbox forbids it
+	if !Command::new("mount").args(["-t", "tmpfs", "wib-test"]).arg(&point).status().is_ok_and(|s| s.success()) { return Ok(()); }
+	fs::write(point.join("dropper.txt"), "remcos connect")?;
+	let detection = rx.recv_timeout(Duration::from_secs(5));
+	let report = report_rx.recv_timeout(Duration::from_secs(5));
+	let _ = Command::new("umount").arg(&point).status();
+	assert!(detection?.path.ends_with("dropper.txt"));
+	assert_eq!(report?.fs_type, "tmpfs");
+	Ok(())
+}
@@ -0,0 +1,115 @@
+use anyhow::Result;
+use std::time::Duration;
+use wib_services::{spawn_ioc_matcher, Connection, FeedFormat, IndicatorKind, IocStore, NetEvent, NetEventKind, Protocol};
+
+const FEODO_JSON: &str = r#"[
+  {"ip_address": "198.51.100.23", "port": 447, "status": "online", "hostname": null, "malware": "QakBot"},
+  {"ip_address": "203.0.113.9", "port": 8080, "status": "offline", "hostname": null, "malware": "Emotet"}
+]"#;
+
+const THREATFOX_JSON: &str = r#"{
+  "1201": [{"ioc_value": "evil-c2.example:6606", "ioc_type": "domain", "threat_type": "botnet_cc", "malware_printable": "AsyncRAT"}],
+  "1202": [{"ioc_value": "hxxp://update-check[.]example/gate.php", "ioc_type": "url", "malware_printable": "Remcos"}],
+  "1203": [{"ioc_value": "44d88612fea8a8f36de82e1278abb02f", "ioc_type": "md5_hash", "malware_printable": "EICAR"}]
+}"#;
+
+const FEODO_CSV: &str = "\
+################################################################
+# abuse.ch Feodo Tracker Botnet C2 IP Blocklist (CSV)           #
+################################################################
+#
+# \"first_seen_utc\",\"dst_ip\",\"dst_port\",\"c2_status\",\"last_online\",\"malware\"
+\"2024-03-01 10:00:00\",\"192.0.2.77\",\"443\",\"online\",\"2024-03-04\",\"Pikabot\"
+# END 1 entries
+";
+
+const HOSTS: &str = "\
+127.0.0.1 localhost
+0.0.0.0 tracker.example ads.example # ad networks
+";
+
+const PLAIN: &str = "\
+; analyst list
+100.64.0.0/10
+2001:db8:bad::/48
+not an indicator
+";
+
+fn opened(remote: &str) -> NetEvent {
+	NetEvent {
+		timestamp_ms: 0,
+		description: String::new(),
+		kind: NetEventKind::Opened,
+		connection: Some(Connection {
+			protocol: Protocol::Tcp,
+			local: "10.0.2.15:50000".parse().unwrap(),
+			remote: remote.parse().unwrap(),
+			state: "ESTABLISHED".into(),
+			uid: 1000,
+			inode: 1,
+			pid: Some(777),
+			process: Some("dropper".into()),
+			exe: Some("/tmp/dropper".into()),
+			bytes_sent: None,
+			bytes_received: None,
+		}),
+	}
+}
+
+#[test]
+fn ingests_feed_formats_and_matches_indicators() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	for (name, text) in [("feodo.json", FEODO_JSON), ("threatfox.json", THREATFOX_JSON), ("feodo.csv", FEODO_CSV), ("hosts", HOSTS), ("analyst.txt", PLAIN)] {
+		std::fs::write(dir.path().join(name), text)?;
+	}
+	let mut store = IocStore::new();
+	let reports = store.load_dir(dir.path())?;
+	let summary: Vec<_> = reports.iter().map(|r| (r.feed.as_str(), r.format.unwrap(), r.imported, r.skipped)).collect();
+	assert_eq!(
+		summary,
+		[
+			("analyst", FeedFormat::Plain, 2, 1),
+			("feodo", FeedFormat::Csv, 1, 0),
+			("feodo", FeedFormat::FeodoJson, 2, 0),
+			("hosts", FeedFormat::Hosts, 2, 0),
+			("threatfox", FeedFormat::ThreatFoxJson, 2, 1),
+		]
+	);
+
+	let qakbot = store.match_ip("198.51.100.23".parse()?).unwrap();
+	assert_eq!((qakbot.source.feed.as_str(), qakbot.source.threat.as_deref()), ("feodo", Some("QakBot")));
+	assert_eq!(store.match_ip("::ffff:192.0.2.77".parse()?).unwrap().source.threat.as_deref(), Some("Pikabot"));
+	let cgnat = store.match_ip("100.100.1.1".parse()?).unwrap();
+	assert_eq!((cgnat.kind, cgnat.indicator.as_str()), (IndicatorKind::Cidr, "100.64.0.0/10"));
+	assert!(store.match_ip("2001:db8:bad:1::5".parse()?).is_some());
+	assert!(store.match_ip("192.0.2.78".parse()?).is_none());
+
+	assert_eq!(store.match_domain("stage2.EVIL-C2.example.").unwrap().indicator, "evil-c2.example");
+	assert!(store.match_domain("localhost").is_none());
+	assert_eq!(store.match_url("http://update-check.example/gate.php").unwrap().kind, IndicatorKind::Url);
+	assert_eq!(store.match_url("https://ads.example/banner").unwrap().kind, IndicatorKind::Domain);
+	Ok(())
+}
+
+#[test]
+fn matcher_raises_one_detection_per_flow_naming_the_feed() -> Result<()> {
+	let mut store = IocStore::new();
+	store.load_str("feodo", FEODO_JSON, None)?;
+	let (events_tx, events_rx) = crossbeam_channel::unbounded();
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let handle = spawn_ioc_matcher(store, events_rx, tx, false);
+	for remote in ["198.51.100.23:447", "198.51.100.23:443", "192.0.2.1:443"] {
+		events_tx.send(opened(remote))?;
+	}
+	drop(events_tx);
+	handle.join().unwrap();
+
+	let detections: Vec<_> = rx.try_iter().collect();
+	assert_eq!(detections.len(), 1);
+	let d = &detections[0];
+	assert_eq!(d.path, std::path::Path::new("/tmp/dropper"));
+	assert!(format!("{:?}", d.kind).contains("in feed feodo, QakBot by dropper (pid 777)"), "{:?}", d.kind);
+	assert_eq!(d.iocs[0].value, "198.51.100.23");
+	assert!(rx.recv_timeout(Duration::from_millis(10)).is_err());
+	Ok(())
+}
@@ -0,0 +1,58 @@
+use anyhow::Result;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+use wib_services::netmon::{parse_proc_net, start_with_interval};
+use wib_services::{NetEventKind, Protocol};
+
+const TCP: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0CEA 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41234 1 0000000000000000 100 0 0 10 0
+   1: 0F02000A:A1B2 2E5E7D8E:01BB 01 00000000:00000000 02:000A7D3E 00000000  1000        0 41299 2 0000000000000000 20 4 30 10 -1
+   2: 0F02000A:A1B4 2E5E7D8E:01BB 06 00000000:00000000 03:00001234 00000000     0        0 0 3 0000000000000000
+";
+
+const TCP6: &str = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:0016 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1717 1 0000000000000000 100 0 0 10 0
+";
+
+#[test]
+fn proc_net_tables_are_parsed() {
+	let tcp = parse_proc_net(TCP, Protocol::Tcp);
+	assert_eq!(tcp.len(), 2, "TIME_WAIT leftovers have no inode");
+	assert_eq!(tcp[0].local, "127.0.0.1:3306".parse::<SocketAddr>().unwrap());
+	assert_eq!(tcp[0].state, "LISTEN");
+	assert_eq!(tcp[1].remote, "142.125.94.46:443".parse::<SocketAddr>().unwrap());
+	assert_eq!((tcp[1].state.as_str(), tcp[1].uid, tcp[1].inode), ("ESTABLISHED", 1000, 41299));
+	let tcp6 = parse_proc_net(TCP6, Protocol::Tcp);
+	assert_eq!(tcp6[0].local, "[::1]:22".parse::<SocketAddr>().unwrap());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn monitor_reports_opened_and_closed_connections_with_owner() -> Result<()> {
+	let listener = TcpListener::bind("127.0.0.1:0")?;
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let _monitor = start_with_interval(Duration::from_millis(100), tx);
+	std::thread::sleep(Duration::from_millis(300));
+	let client = TcpStream::connect(listener.local_addr()?)?;
+	let local = client.local_addr()?;
+	let ours = |kind: NetEventKind| {
+		std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(5)).ok())
+			.find(|e| e.kind == kind && e.connection.as_ref().is_some_and(|c| c.local == local))
+	};
+	let opened = ours(NetEventKind::Opened).unwrap().connection.unwrap();
+	assert_eq!(opened.state, "ESTABLISHED");
+	assert_eq!(opened.remote, listener.local_addr()?);
+	assert_eq!(opened.pid, Some(std::process::id()));
+	assert!(opened.exe.is_some());
+	let (mut server, _) = listener.accept()?;
+	(&client).write_all(&[0x42; 1500])?;
+	server.read_exact(&mut [0; 1500])?;
+	std::thread::sleep(Duration::from_millis(300));
+	drop(client);
+	let closed = ours(NetEventKind::Closed).unwrap().connection.unwrap();
+	assert_eq!((closed.bytes_sent, closed.bytes_received), (Some(1500), Some(0)));
+	Ok(())
+}
@@ -0,0 +1,237 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::net::Ipv4Addr;
+use wib_core::DetectionKind;
+use wib_services::pcap::{analyze_bytes, read_capture};
+use wib_services::{IocStore, PcapOptions};
+
+const CLIENT: [u8; 4] = [10, 0, 0, 5];
+const SYN: u8 = 0x02;
+const ACK: u8 = 0x10;
+const PSH_ACK: u8 = 0x18;
+
+fn tcp_frame(src: [u8; 4], dst: [u8; 4], sport: u16, dport: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+	let mut tcp = Vec::new();
+	tcp.extend(sport.to_be_bytes());
+	tcp.extend(dport.to_be_bytes());
+	tcp.extend(seq.to_be_bytes());
+	tcp.extend(0u32.to_be_bytes());
+	tcp.extend([0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+	tcp.extend(payload);
+	ip_frame(src, dst, 6, &tcp)
+}
+
+fn udp_frame(src: [u8; 4], dst: [u8; 4], sport: u16, dport: u16, payload: &[u8]) -> Vec<u8> {
+	let mut udp = Vec::new();
+	udp.extend(sport.to_be_bytes());
+	udp.extend(dport.to_be_bytes());
+	udp.extend((8 + payload.len() as u16).to_be_bytes());
+	udp.extend([0, 0]);
+	udp.extend(payload);
+	ip_frame(src, dst, 17, &udp)
+}
+
+fn ip_frame(src: [u8; 4], dst: [u8; 4], protocol: u8, payload: &[u8]) -> Vec<u8> {
+	let mut frame = vec![0x02, 0, 0, 0, 0, 1, 0x02, 0, 0, 0, 0, 2, 0x08, 0x00];
+	frame.extend([0x45, 0]);
+	frame.extend((20 + payload.len() as u16).to_be_bytes());
+	frame.extend([0, 0, 0x40, 0, 64, protocol, 0, 0]);
+	frame.extend(src);
+	frame.extend(dst);
+	frame.extend(payload);
+	frame
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+	let mut out = vec![tag];
+	match content.len() {
+		n if n < 0x80 => out.push(n as u8),
+		n => out.extend([0x82, (n >> 8) as u8, n as u8]),
+	}
+	out.extend(content);
+	out
+}
+
+fn certificate(cn: &str) -> Vec<u8> {
+	let name = tlv(0x30, &tlv(0x31, &tlv(0x30, &[tlv(0x06, &[0x55, 0x04, 0x03]), tlv(0x0c, cn.as_bytes())].concat())));
+	let sha256_rsa = tlv(0x30, &tlv(0x06, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b]));
+	let validity = tlv(0x30, &[tlv(0x17, b"240101000000Z"), tlv(0x18, b"99991231235959Z")].concat());
+	let tbs = tlv(0x30, &[tlv(0xa0, &tlv(0x02, &[2])), tlv(0x02, &[1]), sha256_rsa.clone(), name.clone(), validity, name, tlv(0x30, &[])].concat());
+	tlv(0x30, &[tbs, sha256_rsa, tlv(0x03, &[0])].concat())
+}
+
+fn handshake(kind: u8, body: &[u8]) -> Vec<u8> {
+	let len = body.len() as u32;
+	[&[kind], &len.to_be_bytes()[1..], body].concat()
+}
+
+fn record(body: &[u8]) -> Vec<u8> {
+	[&[0x16, 0x03, 0x01][..], &(body.len() as u16).to_be_bytes(), body].concat()
+}
+
+fn client_hello() -> Vec<u8> {
+	let sni = [&[0x00, 0x12, 0x00, 0x00, 0x0f][..], b"evil-c2.example"].concat();
+	let extensions: Vec<u8> = [
+		&[0x1a, 0x1a, 0x00, 0x00][..],
+		&[0x00, 0x00, 0x00, sni.len() as u8],
+		&sni,
+		&[0x00, 0x0a, 0x00, 0x06, 0x00, 0x04, 0x00, 0x1d, 0x00, 0x17],
+		&[0x00, 0x0b, 0x00, 0x02, 0x01, 0x00],
+	]
+	.concat();
+	let body = [
+		&[0x03, 0x03][..],
+		&[0x11; 32],
+		&[0x00],
+		&[0x00, 0x06, 0x0a, 0x0a, 0xc0, 0x2f, 0x00, 0x2f],
+		&[0x01, 0x00],
+		&(extensions.len() as u16).to_be_bytes(),
+		&extensions,
+	]
+	.concat();
+	record(&handshake(1, &body))
+}
+
+fn server_flight() -> Vec<u8> {
+	let hello = [&[0x03, 0x03][..], &[0x22; 32], &[0x00, 0xc0, 0x2f, 0x00], &[0x00, 0x05, 0xff, 0x01, 0x00, 0x01, 0x00]].concat();
+	let cert = certificate("AsyncRAT Server");
+	let entry = [&(cert.len() as u32).to_be_bytes()[1..], &cert].concat();
+	let chain = [&(entry.len() as u32).to_be_bytes()[1..], &entry].concat();
+	record(&[handshake(2, &hello), handshake(11, &chain), handshake(14, &[])].concat())
+}
+
+fn dns_message(response: bool) -> Vec<u8> {
+	let mut msg = vec![0x12, 0x34, if response { 0x81 } else { 0x01 }, 0x00, 0, 1, 0, response as u8, 0, 0, 0, 0];
+	msg.extend(b"\x07evil-c2\x07example\x00\x00\x01\x00\x01");
+	if response { msg.extend([0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 203, 0, 113, 9]); }
+	msg
+}
+
+/// Frames of a small RAT-infected session, timestamps one millisecond apart.
+fn session() -> Vec<Vec<u8>> {
+	let (njrat, tls, web, remcos) = ([198, 51, 100, 23], [203, 0, 113, 9], [192, 0, 2, 10], [192, 0, 2, 44]);
+	let beacon = b"125\0inf|'|'|SGFjS2VkXzQ2|'|'|DESKTOP-1|'|'|admin";
+	let (head, tail) = beacon.split_at(9);
+	let flight = server_flight();
+	let (flight_a, flight_b) = flight.split_at(40);
+	vec![
+		udp_frame(CLIENT, [10, 0, 0, 1], 53000, 53, &dns_message(false)),
+		udp_frame([10, 0, 0, 1], CLIENT, 53, 53000, &dns_message(true)),
+		tcp_frame(CLIENT, njrat, 49200, 5552, 1000, SYN, &[]),
+		tcp_frame(njrat, CLIENT, 5552, 49200, 7000, SYN | ACK, &[]),
+		// out of order, with the delimiter split across segments and a retransmission
+		tcp_frame(CLIENT, njrat, 49200, 5552, 1001 + 9, PSH_ACK, tail),
+		tcp_frame(CLIENT, njrat, 49200, 5552, 1001, PSH_ACK, head),
+		tcp_frame(CLIENT, njrat, 49200, 5552, 1001, PSH_ACK, head),
+		tcp_frame(CLIENT, tls, 49201, 443, 2000, SYN, &[]),
+		tcp_frame(tls, CLIENT, 443, 49201, 9000, SYN | ACK, &[]),
+		tcp_frame(CLIENT, tls, 49201, 443, 2001, PSH_ACK, &client_hello()),
+		tcp_frame(tls, CLIENT, 443, 49201, 9001, PSH_ACK, flight_a),
+		tcp_frame(tls, CLIENT, 443, 49201, 9001 + 40, PSH_ACK, flight_b),
+		tcp_frame(CLIENT, web, 49202, 80, 3000, PSH_ACK, b"GET /gate.php?id=7 HTTP/1.1\r\nHost: Update-Check.example\r\nUser-Agent: Mozilla/4.0\r\n\r\n"),
+		tcp_frame(CLIENT, remcos, 49203, 2404, 4000, PSH_ACK, &[0x24, 0x04, 0xff, 0x00, 0x08, 0, 0, 0, 0x01, 0, 0, 0, 0xde, 0xad, 0xbe, 0xef]),
+	]
+}
+
+fn pcap(frames: &[Vec<u8>]) -> Vec<u8> {
+	let mut out = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 1, 0, 0, 0];
+	for (i, f) in frames.iter().enumerate() {
+		out.extend(1_700_000_000u32.to_le_bytes());
+		out.extend((i as u32 * 1000).to_le_bytes());
+		out.extend((f.len() as u32).to_le_bytes());
+		out.extend((f.len() as u32).to_le_bytes());
+		out.extend(f);
+	}
+	out
+}
+
+/// Big-endian pcapng with nanosecond timestamps.
+fn pcapng(frames: &[Vec<u8>]) -> Vec<u8> {
+	let block = |kind: u32, body: &[u8]| {
+		let padded = [body, &vec![0; (4 - body.len() % 4) % 4]].concat();
+		let len = (12 + padded.len() as u32).to_be_bytes();
+		[&kind.to_be_bytes()[..], &len, &padded, &len].concat()
+	};
+	let mut out = block(0x0a0d0d0a, &[0x1a, 0x2b, 0x3c, 0x4d, 0, 1, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+	out.extend(block(1, &[0, 1, 0, 0, 0, 0, 0xff, 0xff, 0, 9, 0, 1, 9, 0, 0, 0, 0, 0, 0, 0]));
+	for (i, f) in frames.iter().enumerate() {
+		let ns = 1_700_000_000_000_000_000u64 + i as u64 * 1_000_000;
+		let header = [0u32, (ns >> 32) as u32, ns as u32, f.len() as u32, f.len() as u32].map(u32::to_be_bytes).concat();
+		out.extend(block(6, &[header, f.clone()].concat()));
+	}
+	out
+}
+
+#[test]
+fn reassembles_streams_and_flags_rat_traffic() -> Result<()> {
+	let report = analyze_bytes(&pcap(&session()), &PcapOptions::default())?;
+	assert_eq!((report.packets, report.skipped, report.streams.len()), (14, 0, 4));
+	assert!(report.streams.iter().all(|s| s.gaps == 0 && s.client.ip() == Ipv4Addr::from(CLIENT)));
+	let njrat = &report.streams[0];
+	assert_eq!((njrat.bytes_to_server, njrat.last_seen_us - njrat.first_seen_us), (48, 4000));
+
+	let families: BTreeSet<_> = report.findings.iter().map(|f| (f.family.clone().unwrap(), f.marker.as_str())).collect();
+	let expected = [("AsyncRAT".to_string(), "certificate"), ("Remcos".into(), "handshake"), ("njRAT".into(), "delimiter")];
+	assert_eq!(families, expected.into_iter().collect());
+	let finding = report.findings.iter().find(|f| f.marker == "delimiter").unwrap();
+	assert!(finding.description.ends_with("125.inf|'|'|SGFjS2VkXzQ2|'|'|DESKTOP-1|'|'|admin"), "{}", finding.description);
+	let DetectionKind::Signature { name, family } = finding.to_detection("capture.pcap".as_ref()).kind else { panic!("expected a signature") };
+	assert_eq!((name.as_str(), family.as_str()), ("Net.njRAT.delimiter", "njRAT"));
+
+	let tls = &report.tls[0];
+	assert_eq!(tls.sni.as_deref(), Some("evil-c2.example"));
+	assert_eq!(tls.ja3.as_deref(), Some("771,49199-47,0-10-11,29-23,0"));
+	assert_eq!(tls.ja3_hash.as_deref(), Some("d801c4787a46ad17a7f406c7b8764827"));
+	assert_eq!(tls.ja3s_hash.as_deref(), Some("fbe78c619e7ea20046131294ad087f05"));
+	let cert = tls.certificate.as_ref().unwrap();
+	assert_eq!((cert.subject_cn.as_deref(), cert.not_after.as_deref(), cert.self_signed), (Some("AsyncRAT Server"), Some("9999-12-31"), true));
+
+	assert_eq!((report.dns.len(), report.dns[0].name.as_str(), report.dns[0].query_type.as_str()), (1, "evil-c2.example", "A"));
+	assert_eq!(report.dns[0].answers, ["203.0.113.9".parse::<std::net::IpAddr>()?]);
+	assert_eq!(report.http[0].url(), "http://update-check.example/gate.php?id=7");
+	assert_eq!(report.http[0].user_agent.as_deref(), Some("Mozilla/4.0"));
+	Ok(())
+}
+
+#[test]
+fn reads_pcapng_and_matches_iocs() -> Result<()> {
+	let frames = session();
+	let packets = read_capture(&pcapng(&frames))?;
+	assert_eq!(packets.len(), frames.len());
+	assert_eq!((packets[0].link_type, packets[1].timestamp_us), (1, 1_700_000_000_001_000));
+	assert_eq!(packets[5].data, frames[5]);
+
+	let report = analyze_bytes(&pcapng(&frames), &PcapOptions::default())?;
+	assert_eq!((report.streams.len(), report.findings.len()), (4, 3));
+	let mut store = IocStore::new();
+	store.load_str("analyst", "198.51.100.0/24\nevil-c2.example\nhttp://update-check.example/gate.php?id=7\n", None)?;
+	let hits: BTreeSet<_> = report.match_iocs(&store).into_iter().map(|h| (h.context, h.observed, h.matched.source.feed)).collect();
+	let expected: BTreeSet<_> = [
+		("tcp server", "198.51.100.23"),
+		("dns query", "evil-c2.example"),
+		("tls sni", "evil-c2.example"),
+		("http request", "http://update-check.example/gate.php?id=7"),
+	]
+	.into_iter()
+	.map(|(c, o)| (c.to_string(), o.to_string(), "analyst".to_string()))
+	.collect();
+	assert_eq!(hits, expected);
+	Ok(())
+}
+
+#[test]
+fn records_answers_from_dns_over_tcp() -> Result<()> {
+	let resolver = [10, 0, 0, 1];
+	let framed = |msg: Vec<u8>| [(msg.len() as u16).to_be_bytes().to_vec(), msg].concat();
+	let query = framed(dns_message(false));
+	let frames = [
+		tcp_frame(CLIENT, resolver, 53001, 53, 100, SYN, &[]),
+		tcp_frame(resolver, CLIENT, 53, 53001, 500, SYN | ACK, &[]),
+		tcp_frame(CLIENT, resolver, 53001, 53, 101, PSH_ACK, &query),
+		tcp_frame(resolver, CLIENT, 53, 53001, 501, PSH_ACK, &framed(dns_message(true))),
+	];
+	let report = analyze_bytes(&pcap(&frames), &PcapOptions::default())?;
+	assert_eq!(report.dns.len(), 1);
+	assert_eq!(report.dns[0].answers, ["203.0.113.9".parse::<std::net::IpAddr>()?]);
+	Ok(())
+}
@@ -0,0 +1,247 @@
+use anyhow::Result;
+use std::fs;
+use std::time::{Duration, SystemTime};
+use std::io::Read;
+use wib_core::quarantine::{
+	read_quarantine_header, restore_from_quarantine, sidecar_path, ExportManifest, ExportOptions, FalsePositive, OnConflict,
+	Quarantine, QuarantineError, RestoreOptions, RestoreOutcome, RetentionPolicy,
+};
+use wib_core::{Allowlist, ScanOptions, Scanner};
+use wib_database::Role;
+
+#[test]
+fn container_hides_payload_and_restores_metadata() -> Result<()> {
+	let qdir = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let sample = work.path().join("dropper.exe");
+	let body = b"MZ remcos connect".repeat(5000);
+	fs::write(&sample, &body)?;
+	let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+	fs::File::options().write(true).open(&sample)?.set_modified(mtime)?;
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		fs::set_permissions(&sample, fs::Permissions::from_mode(0o751))?;
+	}
+
+	let q = Quarantine::open(qdir.path())?;
+	let qpath = q.container_path(&q.quarantine(&sample, None, "tester")?.id);
+	assert!(!sample.exists());
+	let stored = fs::read(&qpath)?;
+	assert!(stored.starts_with(b"WIBQ"));
+	assert!(!stored.windows(6).any(|w| w == b"remcos"));
+
+	let header = read_quarantine_header(&qpath)?;
+	assert_eq!(header.original_path, fs::canonicalize(work.path())?.join("dropper.exe"));
+	assert_eq!(header.size, body.len() as u64);
+
+	restore_from_quarantine(&qpath, &sample)?;
+	assert_eq!(fs::read(&sample)?, body);
+	let meta = fs::metadata(&sample)?;
+	assert_eq!(meta.modified()?, mtime);
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		assert_eq!(meta.permissions().mode() & 0o7777, 0o751);
+	}
+	Ok(())
+}
+
+#[test]
+fn reading_a_container_without_its_key_fails_without_minting_one() -> Result<()> {
+	let qdir = tempfile::tempdir()?;
+	let elsewhere = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let q = Quarantine::open(qdir.path())?;
+	let sample = work.path().join("stub.exe");
+	fs::write(&sample, b"MZ remcos connect")?;
+	let entry = q.quarantine(&sample, None, "tester")?;
+	let moved = elsewhere.path().join("stray.qf");
+	fs::copy(q.container_path(&entry.id), &moved)?;
+
+	assert!(read_quarantine_header(&moved).is_err());
+	assert!(restore_from_quarantine(&moved, &sample).is_err());
+	assert!(!elsewhere.path().join("quarantine.key").exists());
+	Ok(())
+}
+
+#[test]
+fn index_keeps_identical_files_apart_and_restores_by_id() -> Result<()> {
+	let qdir = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let q = Quarantine::open(qdir.path())?;
+	let a = work.path().join("a.bin");
+	let b = work.path().join("b.bin");
+	fs::write(&a, b"same bytes")?;
+	fs::write(&b, b"same bytes")?;
+	let ea = q.quarantine(&a, None, "alice")?;
+	let eb = q.quarantine(&b, None, "bob")?;
+	assert_ne!(ea.id, eb.id);
+	assert_eq!(ea.sha256, eb.sha256);
+	assert_eq!(q.entries()?.len(), 2);
+	assert_eq!(q.get(&eb.id)?.unwrap().quarantined_by, "bob");
+
+	// something new now lives at a.bin
+	fs::write(&a, b"replacement")?;
+	assert!(q.restore(&ea.id, &RestoreOptions::default()).is_err());
+	let restored = q.restore(&ea.id, &RestoreOptions { on_conflict: OnConflict::Rename, ..Default::default() })?;
+	assert_eq!(restored.file_name().unwrap(), "a (1).bin");
+	assert_eq!(fs::read(&restored)?, b"same bytes");
+	assert_eq!(fs::read(&a)?, b"replacement");
+
+	q.delete(&eb.id)?;
+	assert!(q.entries()?.is_empty());
+	assert!(!q.container_path(&eb.id).exists());
+	Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_refused_and_left_in_place() -> Result<()> {
+	let qdir = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let target = work.path().join("payload.bin");
+	let link = work.path().join("link.bin");
+	fs::write(&target, b"remcos connect")?;
+	std::os::unix::fs::symlink(&target, &link)?;
+	let q = Quarantine::open(qdir.path())?;
+	let err = q.quarantine(&link, None, "tester").unwrap_err();
+	assert!(matches!(err.downcast_ref::<QuarantineError>(), Some(QuarantineError::Symlink { .. })));
+	assert!(link.is_symlink() && target.exists());
+	assert!(q.entries()?.is_empty());
+	Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn failed_removal_is_reported_and_retryable() -> Result<()> {
+	use std::os::unix::fs::PermissionsExt;
+	let qdir = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let locked = work.path().join("locked");
+	fs::create_dir(&locked)?;
+	let sample = locked.join("payload.bin");
+	fs::write(&sample, b"remcos connect")?;
+	fs::set_permissions(&locked, fs::Permissions::from_mode(0o555))?;
+	// root ignores directory permissions, so there is nothing to test
+	if fs::write(locked.join("probe"), b"").is_ok() {
+		return Ok(());
+	}
+
+	let q = Quarantine::open(qdir.path())?;
+	let err = q.quarantine(&sample, None, "tester").unwrap_err();
+	let Some(QuarantineError::RemovalFailed { id, .. }) = err.downcast_ref::<QuarantineError>() else { panic!("unexpected error: {err}") };
+	assert!(sample.exists());
+	let entry = q.get(id)?.unwrap();
+	assert!(entry.pending_removal);
+
+	fs::set_permissions(&locked, fs::Permissions::from_mode(0o755))?;
+	q.remove_original(id)?;
+	assert!(!sample.exists());
+	assert!(!q.get(id)?.unwrap().pending_removal);
+	Ok(())
+}
+
+#[test]
+fn purge_enforces_retention_but_spares_pinned() -> Result<()> {
+	let qdir = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let q = Quarantine::open(qdir.path())?;
+	let mut ids = Vec::new();
+	for i in 0..4 {
+		let p = work.path().join(format!("s{i}.bin"));
+		fs::write(&p, vec![i as u8; 1000])?;
+		ids.push(q.quarantine(&p, None, "tester")?.id);
+		std::thread::sleep(Duration::from_millis(5));
+	}
+	q.set_pinned(&ids[0], true)?;
+
+	let report = q.purge_with(&RetentionPolicy { keep_newest: Some(2), ..Default::default() })?;
+	let purged: Vec<_> = report.purged.iter().map(|e| e.id.as_str()).collect();
+	assert_eq!(purged, [ids[1].as_str()]);
+	assert_eq!(report.remaining, 3);
+
+	// the pinned entry alone does not fit, so everything else goes
+	let report = q.purge_with(&RetentionPolicy { max_total_bytes: Some(1), ..Default::default() })?;
+	assert_eq!(report.purged.len(), 2);
+	let left: Vec<_> = q.entries()?.into_iter().map(|e| e.id).collect();
+	assert_eq!(left, [ids[0].clone()]);
+	assert!(!q.container_path(&ids[3]).exists());
+	Ok(())
+}
+
+#[test]
+fn export_writes_password_protected_zip_and_sidecar() -> Result<()> {
+	let qdir = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let q = Quarantine::open(qdir.path())?;
+	let sample = work.path().join("stub.exe");
+	fs::write(&sample, b"MZ remcos connect")?;
+	let entry = q.quarantine(&sample, None, "tester")?;
+
+	let archive = work.path().join("samples.zip");
+	let manifest = q.export(std::slice::from_ref(&entry.id), &archive, &ExportOptions::default())?;
+	assert_eq!(manifest.samples[0].md5, "224862d674155b1e56164dfc7cdd48dd");
+	assert_eq!(manifest.samples[0].sha256, entry.sha256);
+
+	let mut zip = zip::ZipArchive::new(fs::File::open(&archive)?)?;
+	assert!(zip.by_index_decrypt(0, b"wrong").is_err());
+	let mut member = zip.by_index_decrypt(0, b"infected")?;
+	assert_eq!(member.name(), entry.sha256);
+	let mut body = Vec::new();
+	member.read_to_end(&mut body)?;
+	assert_eq!(body, b"MZ remcos connect");
+
+	let sidecar: ExportManifest = serde_json::from_slice(&fs::read(sidecar_path(&archive))?)?;
+	assert_eq!(sidecar.samples[0].original_path, entry.original_path);
+
+	assert!(Role::Admin.can_export_samples() && Role::SuperAdmin.can_export_samples());
+	assert!(!Role::PowerUser.can_export_samples());
+	Ok(())
+}
+
+#[test]
+fn restore_rescans_and_allowlists_false_positives() -> Result<()> {
+	let qdir = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let mut q = Quarantine::open(qdir.path())?;
+	q.set_allowlist(Allowlist::load(work.path().join("allowlist.json"))?);
+	let scanner = Scanner::new();
+	let sample = work.path().join("notes.txt");
+	fs::write(&sample, b"remcos connect")?;
+	let entry = q.quarantine(&sample, None, "tester")?;
+
+	let RestoreOutcome::StillDetected(d) = q.restore_rescanned(&entry.id, &RestoreOptions::default(), &scanner, None)? else { panic!("restored a detected file") };
+	assert_eq!(d.path, entry.original_path);
+	assert!(!sample.exists());
+
+	let fp = FalsePositive { approved_by: "analyst".into(), reason: "test fixture".into() };
+	let outcome = q.restore_rescanned(&entry.id, &RestoreOptions::default(), &scanner, Some(&fp))?;
+	assert!(matches!(outcome, RestoreOutcome::Restored { overridden: Some(_), .. }));
+	assert!(sample.exists());
+
+	let allowlist = Allowlist::load(work.path().join("allowlist.json"))?;
+	assert_eq!(allowlist.get(&entry.sha256).unwrap().approved_by, "analyst");
+	let mut scanner = Scanner::new();
+	scanner.set_allowlist(&allowlist);
+	assert!(scanner.scan_paths(std::slice::from_ref(&sample), &ScanOptions::default()).is_empty());
+	let err = q.quarantine(&sample, None, "tester").unwrap_err();
+	assert!(matches!(err.downcast_ref::<QuarantineError>(), Some(QuarantineError::Allowlisted { .. })));
+	Ok(())
+}
+
+#[test]
+fn false_positives_approved_on_an_opened_quarantine_persist() -> Result<()> {
+	let qdir = tempfile::tempdir()?;
+	let work = tempfile::tempdir()?;
+	let q = Quarantine::open(qdir.path())?;
+	let sample = work.path().join("notes.txt");
+	fs::write(&sample, b"remcos connect")?;
+	let entry = q.quarantine(&sample, None, "tester")?;
+	let fp = FalsePositive { approved_by: "analyst".into(), reason: "vendor tool".into() };
+	q.restore_rescanned(&entry.id, &RestoreOptions::default(), &Scanner::new(), Some(&fp))?;
+
+	assert!(Allowlist::load(qdir.path().join("allowlist.json"))?.contains(&entry.sha256));
+	assert!(Quarantine::open(qdir.path())?.allowlist().contains(&entry.sha256));
+	Ok(())
+}
@@ -0,0 +1,172 @@
+use anyhow::Result;
+use std::fs;
+use wib_core::{extract_config, scan_paths, ScanOptions};
+
+fn utf16(s: &str) -> Vec<u8> {
+	s.encode_utf16().flat_map(|u| u.to_le_bytes()).chain([0u8, 0u8]).collect()
+}
+
+const ASYNC_MASTER: &str = "QXN5bmNNYXN0ZXJLZXkwMTIzNDU2Nzg5";
+// hosts, ports, mutex, group, install dir, install name, version
+const ASYNC_BLOBS: [&str; 7] = [
+	"VfjuhlQcI5qZ3z1XOznLu2G3fy/0M3SeQrPxh1CAzykBAQEBAQEBAQEBAQEBAQEBq+dp2OOFesCgrk60Eczq5g==",
+	"a9LLEJ2xnhXOUq2N5F8YRXrSMe/dHTmNzBGVKW+QCDoCAgICAgICAgICAgICAgICVDPH3YynLXN1WKvajWoqbw==",
+	"S0hvuy3If0xJlFlAMdTc3nibdMHVh6QqTP66rrJUDu4DAwMDAwMDAwMDAwMDAwMDNFseuH/A4RKH/sQGJlNzPJDuvC1QzXSJI/oYKU9PSWk=",
+	"ZBdMI5Q3WuXXCMWWFSUmEv28REugR9fq/mcWhXvgv0YEBAQEBAQEBAQEBAQEBAQEdzsGEFAQKlx61wRPaIWttw==",
+	"RBhXITAK/ygBXA5v5sQz3KxQAqr2tHHyz4LPPxsMoPAFBQUFBQUFBQUFBQUFBQUF+90s2XnYJ7Xfdl30xqJiiA==",
+	"FlHiE4u9MDDMQU6/aPuYh12JLJRahcIHAgl6O4EpQfIGBgYGBgYGBgYGBgYGBgYG7s0Ci0QbaM6u0wO6GDLdJQ==",
+	"Rfo5qvaBIBr8AIEtIVGbugvOyZ+5JSLPSSF9xdZPXTgHBwcHBwcHBwcHBwcHBwcHMIH27g1KCdXRNxtH+HQFmg==",
+];
+
+const QUASAR_MASTER: &str = "QuasarKey1234567890ABCDEF";
+// hosts, mutex, tag, version
+const QUASAR_BLOBS: [&str; 4] = [
+	"sasGl3CyH+wlp+3MR0+JkrX3Ka42S39s52WiBGaBW1ABAQEBAQEBAQEBAQEBAQEByZPQuO7HQs1nC7j1QgpGn6TFdBXKH8lDIC2PbQnEOiK/2kY2vbi+MpyABAtzJrKM",
+	"ABQaOmHQrt2MIiAzXGIPJLhqmMssg//RcYTBuCfgm4kCAgICAgICAgICAgICAgICPzwvLhYzZL4SKSL4O8Fob/YMWp95DZHK17nshNxnhzE=",
+	"rxoAaCa1KGlsZNIRMp1IGXbWyxuh54jVUqrUpvT/1s0DAwMDAwMDAwMDAwMDAwMDppKLRjtJlECMhzsm5Nt3ng==",
+	"QXgd7EBZEZ9qxHgx2XrBD60/NZQDjC3nX4NSXB7Jp8gEBAQEBAQEBAQEBAQEBAQEI5hrUL/oQjL1ICuqW2NxZg==",
+];
+
+fn string_heap<'a>(strings: impl IntoIterator<Item = &'a str>) -> Vec<u8> {
+	let mut sample = b"MZ\x90\x00 .NET stub\x00\x00".to_vec();
+	for s in strings {
+		sample.extend(utf16(s));
+	}
+	sample
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+	let mut s: Vec<u8> = (0..=255).collect();
+	let mut j = 0u8;
+	for i in 0..256 {
+		j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+		s.swap(i, j as usize);
+	}
+	let (mut i, mut j) = (0u8, 0u8);
+	data.iter()
+		.map(|b| {
+			i = i.wrapping_add(1);
+			j = j.wrapping_add(s[i as usize]);
+			s.swap(i as usize, j as usize);
+			b ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+		})
+		.collect()
+}
+
+/// PE32 with one section holding an RT_RCDATA resource named SETTINGS.
+fn pe_with_settings(blob: &[u8]) -> Vec<u8> {
+	let put16 = |buf: &mut Vec<u8>, off: usize, v: u16| buf[off..off + 2].copy_from_slice(&v.to_le_bytes());
+	let put32 = |buf: &mut Vec<u8>, off: usize, v: u32| buf[off..off + 4].copy_from_slice(&v.to_le_bytes());
+	let mut pe = vec![0u8; 0x280 + blob.len()];
+	pe[..2].copy_from_slice(b"MZ");
+	put32(&mut pe, 0x3c, 0x40);
+	pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+	put16(&mut pe, 0x46, 1);
+	put16(&mut pe, 0x54, 0xe0);
+	put16(&mut pe, 0x58, 0x10b);
+	// resource directory entry, then the section header
+	put32(&mut pe, 0xc8, 0x1000);
+	put32(&mut pe, 0x140, 0x1000);
+	put32(&mut pe, 0x144, 0x1000);
+	put32(&mut pe, 0x148, 0x80 + blob.len() as u32);
+	put32(&mut pe, 0x14c, 0x200);
+	// root -> RT_RCDATA -> "SETTINGS" -> language -> data entry
+	let rsrc = 0x200;
+	put16(&mut pe, rsrc + 14, 1);
+	put32(&mut pe, rsrc + 16, 10);
+	put32(&mut pe, rsrc + 20, 0x8000_0018);
+	put16(&mut pe, rsrc + 0x18 + 12, 1);
+	put32(&mut pe, rsrc + 0x28, 0x8000_0060);
+	put32(&mut pe, rsrc + 0x2c, 0x8000_0030);
+	put16(&mut pe, rsrc + 0x30 + 14, 1);
+	put32(&mut pe, rsrc + 0x40, 0x409);
+	put32(&mut pe, rsrc + 0x44, 0x50);
+	put32(&mut pe, rsrc + 0x50, 0x1080);
+	put32(&mut pe, rsrc + 0x54, blob.len() as u32);
+	put16(&mut pe, rsrc + 0x60, 8);
+	let name = utf16("SETTINGS");
+	pe[rsrc + 0x62..rsrc + 0x72].copy_from_slice(&name[..16]);
+	pe[rsrc + 0x80..].copy_from_slice(blob);
+	pe
+}
+
+#[test]
+fn extracts_njrat_config_from_string_heap() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let file_path = dir.path().join("stub.exe");
+	let mut sample = b"MZ\x90\x00 njrat stub\x00\x00".to_vec();
+	for s in ["SGFjS2Vk", "0.7d", "evil.ddns.net", "5552", "server.exe", "TEMP", "5cd8f17f4086744065eb0992a09e05a2", "|'|'|"] {
+		sample.extend(utf16(s));
+	}
+	fs::write(&file_path, sample)?;
+	let detections = scan_paths(&[file_path], ScanOptions::default());
+	let cfg = detections.iter().find_map(|d| d.config.clone()).expect("no config extracted");
+	assert_eq!(cfg.family, "njRAT");
+	assert_eq!(cfg.c2[0].host, "evil.ddns.net");
+	assert_eq!(cfg.c2[0].port, Some(5552));
+	assert_eq!(cfg.campaign_id.as_deref(), Some("HacKed"));
+	assert_eq!(cfg.version.as_deref(), Some("0.7d"));
+	assert_eq!(cfg.install_path.as_deref(), Some("%TEMP%\\server.exe"));
+	assert_eq!(cfg.mutex.as_deref(), Some("5cd8f17f4086744065eb0992a09e05a2"));
+	Ok(())
+}
+
+#[test]
+fn extracts_remcos_config_from_rc4_settings_resource() {
+	let key = b"\x13\x37secretkey";
+	let plain = b"evil.example.com:2404:1\x1ebackup.example.net:8080:0|\x1e\x1e\x1f|RemoteHost|\x1e\x1e\x1f|Rmc-ABC123|\x1e\x1e\x1f|remcos.exe";
+	let mut blob = vec![key.len() as u8];
+	blob.extend(key);
+	blob.extend(rc4(key, plain));
+	let cfg = extract_config("Remcos", &pe_with_settings(&blob)).expect("no config extracted");
+	let c2: Vec<_> = cfg.c2.iter().map(|c| (c.host.as_str(), c.port)).collect();
+	assert_eq!(c2, [("evil.example.com", Some(2404)), ("backup.example.net", Some(8080))]);
+	assert_eq!(cfg.campaign_id.as_deref(), Some("RemoteHost"));
+	assert_eq!(cfg.mutex.as_deref(), Some("Rmc-ABC123"));
+	assert_eq!(cfg.install_path.as_deref(), Some("remcos.exe"));
+	assert_eq!(cfg.encryption_key.as_deref(), Some("13377365637265746b6579"));
+}
+
+#[test]
+fn extracts_asyncrat_config_with_derived_aes_key() {
+	let sample = string_heap(ASYNC_BLOBS.into_iter().chain([ASYNC_MASTER]));
+	let cfg = extract_config("AsyncRAT", &sample).expect("no config extracted");
+	let c2: Vec<_> = cfg.c2.iter().map(|c| (c.host.as_str(), c.port)).collect();
+	assert_eq!(c2, [("c2.example.org", Some(6606)), ("c2.example.org", Some(7707))]);
+	assert_eq!(cfg.mutex.as_deref(), Some("AsyncMutex_6SI8OkPnk"));
+	assert_eq!(cfg.campaign_id.as_deref(), Some("Default"));
+	assert_eq!(cfg.install_path.as_deref(), Some("%AppData%\\svchost.exe"));
+	assert_eq!(cfg.version.as_deref(), Some("0.5.7B"));
+	assert_eq!(cfg.encryption_key.as_deref(), Some("AsyncMasterKey0123456789"));
+}
+
+#[test]
+fn extracts_quasar_config_with_derived_aes_key() {
+	let sample = string_heap(QUASAR_BLOBS.into_iter().chain([QUASAR_MASTER]));
+	let cfg = extract_config("Quasar", &sample).expect("no config extracted");
+	let c2: Vec<_> = cfg.c2.iter().map(|c| (c.host.as_str(), c.port)).collect();
+	assert_eq!(c2, [("quasar.example.net", Some(4782)), ("10.0.0.9", Some(4783))]);
+	assert_eq!(cfg.mutex.as_deref(), Some("QSR_MUTEX_abc123"));
+	assert_eq!(cfg.campaign_id.as_deref(), Some("Office04"));
+	assert_eq!(cfg.version.as_deref(), Some("1.4.1"));
+}
+
+#[test]
+fn wrong_key_or_tampered_blobs_yield_no_config() {
+	// a different master key fails every HMAC check
+	assert!(extract_config("Quasar", &string_heap(QUASAR_BLOBS.into_iter().chain(["QuasarKey0000000000ABCDEF"]))).is_none());
+	let tampered = ASYNC_BLOBS[2].replacen('S', "T", 1);
+	assert!(extract_config("AsyncRAT", &string_heap([tampered.as_str(), ASYNC_MASTER])).is_none());
+	let mut blob = vec![4u8];
+	blob.extend(b"abcd");
+	blob.extend(rc4(b"dcba", b"evil.example.com:2404:1|\x1e\x1e\x1f|RemoteHost"));
+	assert!(extract_config("Remcos", &pe_with_settings(&blob)).is_none());
+}
+
+#[test]
+fn empty_input_yields_no_config() {
+	for family in ["njRAT", "Remcos", "AsyncRAT", "Quasar"] {
+		assert!(extract_config(family, &[]).is_none());
+		assert!(extract_config(family, &[0x4d]).is_none());
+	}
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use std::fs;
+use std::time::Duration;
+use wib_services::realtime::start_realtime;
+use wib_services::{RealtimeOptions, RealtimeState, WatchBackend};
+
+#[test]
+fn bursts_of_writes_are_scanned_once() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let opts = RealtimeOptions { paths: vec![dir.path().to_path_buf()], debounce: Duration::from_millis(200), workers: 2, ..Default::default() };
+	let guard = start_realtime(opts, tx)?;
+	let path = dir.path().join("dropper.txt");
+	for i in 0..20 {
+		fs::write(&path, format!("chunk {i} remcos connect"))?;
+	}
+	let d = rx.recv_timeout(Duration::from_secs(5))?;
+	assert_eq!(d.path, path);
+	assert!(rx.recv_timeout(Duration::from_millis(600)).is_err());
+	let m = guard.metrics();
+	assert_eq!(m.scanned, 1);
+	assert!(m.coalesced > 0);
+	Ok(())
+}
+
+#[test]
+fn guard_pauses_resumes_and_changes_paths() -> Result<()> {
+	let first = tempfile::tempdir()?;
+	let second = tempfile::tempdir()?;
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let opts = RealtimeOptions { paths: vec![first.path().to_path_buf()], debounce: Duration::from_millis(50), workers: 1, ..Default::default() };
+	let mut guard = start_realtime(opts, tx)?;
+	assert_eq!(guard.state(), RealtimeState::Running);
+
+	guard.pause();
+	fs::write(first.path().join("a.txt"), "remcos connect")?;
+	assert!(rx.recv_timeout(Duration::from_millis(400)).is_err());
+	guard.resume();
+	fs::write(first.path().join("b.txt"), "remcos connect")?;
+	assert!(rx.recv_timeout(Duration::from_secs(5))?.path.ends_with("b.txt"));
+
+	guard.add_path(second.path())?;
+	guard.remove_path(first.path())?;
+	assert_eq!(guard.watched_paths(), [second.path().to_path_buf()]);
+	fs::write(first.path().join("c.txt"), "remcos connect")?;
+	fs::write(second.path().join("d.txt"), "remcos connect")?;
+	assert!(rx.recv_timeout(Duration::from_secs(5))?.path.ends_with("d.txt"));
+
+	guard.stop();
+	assert_eq!(guard.status().state, RealtimeState::Stopped);
+	assert!(guard.add_path(first.path()).is_err());
+	fs::write(second.path().join("e.txt"), "remcos connect")?;
+	assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+	Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn fanotify_denies_opening_detected_files() -> Result<()> {
+	use std::sync::Arc;
+	use wib_services::fanotify::{start_on_access, OnAccessOptions};
+	let dir = tempfile::tempdir()?;
+	let bad = dir.path().join("bad.txt");
+	let good = dir.path().join("good.txt");
+	fs::write(&bad, "remcos connect")?;
+	fs::write(&good, "hello")?;
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let opts = OnAccessOptions { paths: vec![dir.path().to_path_buf()], workers: 1, ..Default::default() };
+	// needs CAP_SYS_ADMIN and a kernel with fanotify permission events
+	let Ok(mut guard) = start_on_access(opts, Arc::new(wib_core::Scanner::new()), tx) else { return Ok(()) };
+	let cat = |p: &std::path::Path| std::process::Command::new("cat").arg(p).output();
+	assert!(cat(&good)?.status.success());
+	assert!(!cat(&bad)?.status.success());
+	assert!(!cat(&bad)?.status.success());
+	assert_eq!(rx.recv_timeout(Duration::from_secs(2))?.path, bad);
+	let stats = guard.stats();
+	assert!(stats.denied >= 2 && stats.cache_hits >= 1);
+	guard.stop();
+	assert!(cat(&bad)?.status.success());
+	Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn behavior_alerts_suspend_the_writing_process() -> Result<()> {
+	use wib_services::BehaviorOptions;
+	let staging = tempfile::tempdir()?;
+	let watched = tempfile::tempdir()?;
+	// needs CAP_SYS_ADMIN to learn who wrote a file
+	if wib_services::fanotify::track_writers(&[watched.path().to_path_buf()]).is_err() { return Ok(()); }
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let behavior = BehaviorOptions { suspend: true, ..Default::default() };
+	let opts = RealtimeOptions { paths: vec![watched.path().to_path_buf()], behavior: Some(behavior), workers: 1, ..Default::default() };
+	let _guard = start_realtime(opts, tx)?;
+	// the note is written outside the watched tree and moved in whole, then the writer idles
+	let script = format!(
+		"printf 'All your files are encrypted. Send Bitcoin to decrypt them.' > {0}/note.txt && mv {0}/note.txt {1}/HOW_TO_DECRYPT.txt && exec sleep 30",
+		staging.path().display(),
+		watched.path().display()
+	);
+	let mut child = std::process::Command::new("sh").arg("-c").arg(script).spawn()?;
+	let alert = rx.recv_timeout(Duration::from_secs(5));
+	let state = || fs::read_to_string(format!("/proc/{}/stat", child.id())).ok().and_then(|s| s.rsplit(") ").next()?.chars().next());
+	let mut stopped = false;
+	for _ in 0..50 {
+		stopped = state() == Some('T');
+		if stopped { break; }
+		std::thread::sleep(Duration::from_millis(100));
+	}
+	child.kill()?;
+	child.wait()?;
+	assert!(alert?.path.ends_with("HOW_TO_DECRYPT.txt"));
+	assert!(stopped);
+	Ok(())
+}
+
+#[test]
+fn polled_paths_see_changes() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let backends = [(dir.path().to_path_buf(), WatchBackend::Poll { interval: Duration::from_millis(100) })].into();
+	let opts = RealtimeOptions { paths: vec![dir.path().to_path_buf()], backends, debounce: Duration::from_millis(50), workers: 1, ..Default::default() };
+	let guard = start_realtime(opts, tx)?;
+	assert_eq!(guard.status().polled, vec![dir.path().to_path_buf()]);
+	let path = dir.path().join("share.txt");
+	fs::write(&path, "nothing yet")?;
+	std::thread::sleep(Duration::from_millis(300));
+	fs::write(&path, "remcos connect, now with a different length")?;
+	assert_eq!(rx.recv_timeout(Duration::from_secs(5))?.path, path);
+	Ok(())
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use wib_core::{Detection, DetectionKind};
+use wib_services::{ActionLog, ActionOutcome, Responder, ResponseAction, ResponsePolicy};
+
+fn detection(path: &str, family: &str, severity: u8) -> Detection {
+	Detection {
+		path: PathBuf::from(path),
+		kind: DetectionKind::Signature { name: format!("{family}.Generic"), family: family.into() },
+		severity,
+		sha256: None,
+		config: None,
+		iocs: Vec::new(),
+		member: None,
+	}
+}
+
+#[test]
+fn policy_selects_actions_and_every_action_is_logged() -> Result<()> {
+	let policy: ResponsePolicy = serde_json::from_str(
+		r#"{
+			"rules": [
+				{ "families": ["njrat"], "path_prefixes": ["/srv/share"], "actions": ["notify", "kill_process"] },
+				{ "min_severity": 9, "actions": ["log", "block_c2"] }
+			],
+			"default_actions": ["log"]
+		}"#,
+	)?;
+	let share = detection("/srv/share/payload.exe", "NjRAT", 5);
+	assert_eq!(policy.actions_for(&share), [ResponseAction::Notify, ResponseAction::KillProcess]);
+	assert_eq!(policy.actions_for(&detection("/tmp/payload.exe", "NjRAT", 5)), [ResponseAction::Log]);
+
+	let dir = tempfile::tempdir()?;
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let responder = Responder::new(policy, ActionLog::open(dir.path().join("actions.jsonl"))?, Some(tx));
+	let records = responder.handle(&share, None);
+	assert_eq!(rx.try_recv()?.path, share.path);
+	assert_eq!(records[0].outcome, ActionOutcome::Done);
+	// nothing has the file open and we never kill ourselves
+	assert_eq!(records[1].outcome, ActionOutcome::Skipped);
+
+	let records = responder.handle(&detection("/tmp/x.exe", "Remcos", 10), Some(std::process::id()));
+	assert_eq!(records[1].action, ResponseAction::BlockC2);
+	assert_eq!(records[1].outcome, ActionOutcome::Skipped);
+
+	let logged = ActionLog::open(dir.path().join("actions.jsonl"))?.read_all()?;
+	assert_eq!(logged.len(), 4);
+	assert_eq!(logged[3].detection, "Remcos.Generic");
+	Ok(())
+}
+
+#[test]
+fn default_policy_quarantines_only_high_severity() {
+	let policy = ResponsePolicy::default();
+	assert!(policy.actions_for(&detection("/tmp/a", "AsyncRAT", 9)).contains(&ResponseAction::Quarantine));
+	assert!(!policy.actions_for(&detection("/tmp/a", "AsyncRAT", 5)).contains(&ResponseAction::Quarantine));
+	// C2 blocking edits the host firewall, so deployments opt into it explicitly
+	assert!(!policy.actions_for(&detection("/tmp/a", "AsyncRAT", 10)).contains(&ResponseAction::BlockC2));
+}
+
+#[test]
+fn protected_paths_are_never_quarantined() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let decoy = dir.path().join("Passwords.xlsx");
+	std::fs::write(&decoy, "decoy")?;
+	let responder = Responder::new(ResponsePolicy::default(), ActionLog::open(dir.path().join("actions.jsonl"))?, None)
+		.with_protected_paths(vec![decoy.clone()]);
+	let records = responder.handle(&detection(decoy.to_str().unwrap(), "Canary", 10), None);
+	let quarantine = records.iter().find(|r| r.action == ResponseAction::Quarantine).unwrap();
+	assert_eq!(quarantine.outcome, ActionOutcome::Skipped);
+	assert!(decoy.exists());
+	Ok(())
+}